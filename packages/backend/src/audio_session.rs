@@ -0,0 +1,400 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{
+            AtomicU8,
+            AtomicU64,
+            Ordering,
+        },
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use anyhow::Result;
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+use windows::{
+    Win32::{
+        Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+        Foundation::CloseHandle,
+        Media::Audio::{
+            AudioSessionStateActive,
+            Endpoints::IAudioMeterInformation,
+            IAudioSessionControl2,
+            IAudioSessionManager2,
+            IMMDeviceEnumerator,
+            IMMNotificationClient,
+            MMDeviceEnumerator,
+            eMultimedia,
+            eRender,
+        },
+        System::{
+            Com::{
+                CLSCTX_ALL,
+                COINIT_MULTITHREADED,
+                CoCreateInstance,
+                CoInitializeEx,
+                STGM_READ,
+            },
+            Threading::{
+                OpenProcess,
+                PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+                QueryFullProcessImageNameW,
+            },
+        },
+    },
+    core::{
+        Interface,
+        PWSTR,
+    },
+};
+
+use crate::{
+    config,
+    dispatcher,
+    model::{
+        AppMessage,
+        PlayStatePayload,
+        PlaybackStatus,
+    },
+    smtc_core::{
+        self,
+        SmtcEvent,
+    },
+    tasks::{
+        self,
+        CancellationToken,
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const PROCESS_NAME: &str = "cloudmusic.exe";
+/// 峰值低于这个值时视为没有声音
+const PEAK_EPSILON: f32 = 1e-4;
+/// 输出设备的名称在这段时间内复用上次查询的结果
+const DEVICE_NAME_TTL: Duration = Duration::from_secs(5);
+
+const STATUS_UNKNOWN: u8 = 0;
+const STATUS_PLAYING: u8 = 1;
+const STATUS_PAUSED: u8 = 2;
+
+/// 前端最近一次报告的播放状态
+static REPORTED_STATUS: AtomicU8 = AtomicU8::new(STATUS_UNKNOWN);
+
+/// 本次启动以来根据音频会话修正播放状态的次数
+static CORRECTION_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// 最近一次查询到的默认输出设备名称和查询时刻
+static OUTPUT_DEVICE: Mutex<Option<(Instant, Option<String>)>> = Mutex::new(None);
+
+static DEVICE_WATCH: Mutex<Option<DeviceWatch>> = Mutex::new(None);
+
+/// 取消订阅时需要同一个枚举器和回调对象
+struct DeviceWatch {
+    enumerator: IMMDeviceEnumerator,
+    client: IMMNotificationClient,
+}
+
+// Safety: 设备枚举器支持在任意线程上调用，回调对象没有内部状态
+#[allow(clippy::non_send_fields_in_send_ty)]
+unsafe impl Send for DeviceWatch {}
+
+pub fn init() {
+    let session_config = config::get().audio_session;
+    if !session_config.enabled {
+        return;
+    }
+
+    let threshold = Duration::from_secs(session_config.mismatch_threshold_secs.max(1));
+    let spawn_result = tasks::spawn("audio-session-thread", move |token| {
+        if let Err(e) = run_monitor_loop(threshold, &token) {
+            warn!("音频会话监视已停止: {e:?}");
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动音频会话监视线程: {e}");
+    }
+}
+
+pub fn update_reported_status(status: PlaybackStatus) {
+    let value = match status {
+        PlaybackStatus::Playing => STATUS_PLAYING,
+        PlaybackStatus::Paused => STATUS_PAUSED,
+    };
+    REPORTED_STATUS.store(value, Ordering::Relaxed);
+}
+
+pub fn correction_count() -> u64 {
+    CORRECTION_COUNT.load(Ordering::Relaxed)
+}
+
+/// 默认输出设备的名称，例如 "Speakers (Realtek(R) Audio)"
+///
+/// 网易云跟随系统的默认设备输出，所以这就是正在播放的设备
+pub fn output_device_name() -> Option<String> {
+    let Ok(mut cached) = OUTPUT_DEVICE.lock() else {
+        return None;
+    };
+    if let Some((queried_at, name)) = cached.as_ref()
+        && queried_at.elapsed() < DEVICE_NAME_TTL
+    {
+        return name.clone();
+    }
+
+    let name = query_output_device_name()
+        .inspect_err(|e| debug!("读取默认输出设备名称失败: {e:?}"))
+        .ok()
+        .filter(|name| !name.is_empty());
+    *cached = Some((Instant::now(), name.clone()));
+    name
+}
+
+/// 订阅默认输出设备的变化，和音频会话监视不同，这个订阅总是开启
+pub fn watch_devices() {
+    let Ok(mut watch) = DEVICE_WATCH.lock() else {
+        return;
+    };
+    if watch.is_some() {
+        return;
+    }
+
+    match register_device_watch() {
+        Ok(registered) => {
+            debug!("已订阅默认输出设备的变化");
+            *watch = Some(registered);
+        }
+        Err(e) => warn!("订阅默认输出设备的变化失败: {e:?}"),
+    }
+}
+
+/// 插件卸载后系统不能再调用回调，所以关闭时必须取消订阅
+pub fn unwatch_devices() {
+    let Some(watch) = DEVICE_WATCH.lock().ok().and_then(|mut watch| watch.take()) else {
+        return;
+    };
+    if let Err(e) = unsafe {
+        watch
+            .enumerator
+            .UnregisterEndpointNotificationCallback(&watch.client)
+    } {
+        warn!("取消订阅默认输出设备的变化失败: {e:?}");
+    }
+}
+
+fn register_device_watch() -> Result<DeviceWatch> {
+    let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+    let enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+    let client: IMMNotificationClient = notifier::DeviceNotifier.into();
+    unsafe { enumerator.RegisterEndpointNotificationCallback(&client)? };
+    Ok(DeviceWatch { enumerator, client })
+}
+
+fn on_default_device_changed() {
+    let previous = OUTPUT_DEVICE
+        .lock()
+        .ok()
+        .and_then(|mut cached| cached.take())
+        .and_then(|(_, name)| name);
+    let name = output_device_name();
+    if name == previous {
+        return;
+    }
+
+    info!(?previous, ?name, "默认输出设备已改变");
+    smtc_core::dispatch_event(&SmtcEvent::AudioDeviceChanged { name });
+}
+
+fn query_output_device_name() -> Result<String> {
+    // 当前线程已经初始化过 COM 时会返回 S_FALSE 或 RPC_E_CHANGED_MODE，都可以继续使用
+    let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+    let enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+    let device = unsafe { enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)? };
+    let properties = unsafe { device.OpenPropertyStore(STGM_READ)? };
+    let name = unsafe { properties.GetValue(&PKEY_Device_FriendlyName)? };
+    Ok(name.to_string())
+}
+
+fn run_monitor_loop(threshold: Duration, token: &CancellationToken) -> Result<()> {
+    unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.ok()?;
+    let enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+
+    let mut process_names = HashMap::new();
+    let mut mismatch_since: Option<Instant> = None;
+
+    while token.sleep(POLL_INTERVAL) {
+        let reported = match REPORTED_STATUS.load(Ordering::Relaxed) {
+            STATUS_PLAYING => PlaybackStatus::Playing,
+            STATUS_PAUSED => PlaybackStatus::Paused,
+            _ => continue,
+        };
+
+        // 默认设备可能随时改变，所以每次都重新获取
+        let observed = match is_rendering_audio(&enumerator, &mut process_names) {
+            Ok(true) => PlaybackStatus::Playing,
+            Ok(false) => PlaybackStatus::Paused,
+            Err(e) => {
+                debug!("读取音频会话失败: {e:?}");
+                continue;
+            }
+        };
+
+        if observed == reported {
+            mismatch_since = None;
+            continue;
+        }
+
+        // 歌曲中的静音片段也会让峰值短暂归零，所以只有持续不一致时才修正
+        let since = *mismatch_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < threshold {
+            continue;
+        }
+        mismatch_since = None;
+
+        warn!(
+            ?reported,
+            ?observed,
+            "前端报告的播放状态与音频输出持续不一致，已自动修正"
+        );
+        CORRECTION_COUNT.fetch_add(1, Ordering::Relaxed);
+        smtc_core::dispatch_event(&SmtcEvent::PlayStateMismatch { reported, observed });
+        dispatcher::post(AppMessage::UpdatePlayState(PlayStatePayload {
+            status: observed,
+        }));
+    }
+
+    Ok(())
+}
+
+/// 网易云是否正在默认输出设备上输出声音
+fn is_rendering_audio(
+    enumerator: &IMMDeviceEnumerator,
+    process_names: &mut HashMap<u32, bool>,
+) -> Result<bool> {
+    let device = unsafe { enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)? };
+    let manager: IAudioSessionManager2 = unsafe { device.Activate(CLSCTX_ALL, None)? };
+    let sessions = unsafe { manager.GetSessionEnumerator()? };
+
+    for i in 0..unsafe { sessions.GetCount()? } {
+        let control = unsafe { sessions.GetSession(i)? };
+        let control2: IAudioSessionControl2 = control.cast()?;
+        let pid = unsafe { control2.GetProcessId()? };
+
+        let is_cloudmusic = *process_names
+            .entry(pid)
+            .or_insert_with(|| process_name_matches(pid, PROCESS_NAME));
+        if !is_cloudmusic || unsafe { control.GetState()? } != AudioSessionStateActive {
+            continue;
+        }
+
+        let meter: IAudioMeterInformation = control.cast()?;
+        if unsafe { meter.GetPeakValue()? } > PEAK_EPSILON {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn process_name_matches(pid: u32, expected: &str) -> bool {
+    let Ok(handle) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }) else {
+        return false;
+    };
+
+    let mut buffer = [0u16; 260];
+    let mut len = buffer.len() as u32;
+    let result = unsafe {
+        QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &raw mut len,
+        )
+    };
+    let _ = unsafe { CloseHandle(handle) };
+
+    if result.is_err() {
+        return false;
+    }
+
+    String::from_utf16_lossy(&buffer[..len as usize])
+        .rsplit(['\\', '/'])
+        .next()
+        .is_some_and(|name| name.eq_ignore_ascii_case(expected))
+}
+
+/// `#[implement]` 生成的代码会触发这些 lint
+#[allow(clippy::inline_always, clippy::ref_as_ptr)]
+mod notifier {
+    use windows::{
+        Win32::{
+            Foundation::PROPERTYKEY,
+            Media::Audio::{
+                DEVICE_STATE,
+                EDataFlow,
+                ERole,
+                IMMNotificationClient,
+                IMMNotificationClient_Impl,
+                eMultimedia,
+                eRender,
+            },
+        },
+        core::{
+            PCWSTR,
+            implement,
+        },
+    };
+
+    #[implement(IMMNotificationClient)]
+    pub struct DeviceNotifier;
+
+    impl IMMNotificationClient_Impl for DeviceNotifier_Impl {
+        fn OnDeviceStateChanged(
+            &self,
+            _device_id: &PCWSTR,
+            _new_state: DEVICE_STATE,
+        ) -> windows::core::Result<()> {
+            Ok(())
+        }
+
+        fn OnDeviceAdded(&self, _device_id: &PCWSTR) -> windows::core::Result<()> {
+            Ok(())
+        }
+
+        fn OnDeviceRemoved(&self, _device_id: &PCWSTR) -> windows::core::Result<()> {
+            Ok(())
+        }
+
+        fn OnDefaultDeviceChanged(
+            &self,
+            flow: EDataFlow,
+            role: ERole,
+            _device_id: &PCWSTR,
+        ) -> windows::core::Result<()> {
+            // 每个角色都会收到一次通知，网易云使用的是多媒体角色
+            if flow == eRender && role == eMultimedia {
+                super::on_default_device_changed();
+            }
+            Ok(())
+        }
+
+        fn OnPropertyValueChanged(
+            &self,
+            _device_id: &PCWSTR,
+            _key: &PROPERTYKEY,
+        ) -> windows::core::Result<()> {
+            Ok(())
+        }
+    }
+}
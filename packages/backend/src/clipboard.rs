@@ -0,0 +1,78 @@
+use std::{
+    iter,
+    ptr,
+};
+
+use windows::Win32::{
+    Foundation::{
+        GlobalFree,
+        HANDLE,
+    },
+    System::{
+        DataExchange::{
+            CloseClipboard,
+            EmptyClipboard,
+            OpenClipboard,
+            SetClipboardData,
+        },
+        Memory::{
+            GMEM_MOVEABLE,
+            GlobalAlloc,
+            GlobalLock,
+            GlobalUnlock,
+        },
+    },
+};
+
+use crate::{
+    cover,
+    formatting,
+    model::CopyShareTextPayload,
+};
+
+const DEFAULT_SHARE_TEMPLATE: &str = "{title} - {artist} {url}";
+
+/// 来自 `Win32_System_Ole`，为了一个常量不值得引入整个模块
+const CF_UNICODETEXT: u32 = 13;
+
+pub fn copy_share_text(payload: &CopyShareTextPayload) -> Result<String, String> {
+    let metadata = cover::current_metadata().ok_or("还没有收到歌曲元数据")?;
+    let template = payload
+        .template
+        .as_deref()
+        .unwrap_or(DEFAULT_SHARE_TEMPLATE);
+    let text = formatting::render_template(template, &metadata);
+    set_text(&text).map_err(|e| format!("写入剪贴板失败: {e}"))?;
+    Ok(text)
+}
+
+fn set_text(text: &str) -> windows::core::Result<()> {
+    let wide: Vec<u16> = text.encode_utf16().chain(iter::once(0)).collect();
+
+    unsafe {
+        // 剪贴板可能正被其他程序占用，此时直接返回错误
+        OpenClipboard(None)?;
+        let result = (|| {
+            EmptyClipboard()?;
+
+            let memory = GlobalAlloc(GMEM_MOVEABLE, wide.len() * size_of::<u16>())?;
+            let target = GlobalLock(memory).cast::<u16>();
+            if target.is_null() {
+                let _ = GlobalFree(Some(memory));
+                return Err(windows::core::Error::from_thread());
+            }
+            ptr::copy_nonoverlapping(wide.as_ptr(), target, wide.len());
+            // 解锁后锁计数归零时也会返回 FALSE，不需要检查
+            let _ = GlobalUnlock(memory);
+
+            // 成功后内存归系统所有，失败时需要自己释放
+            if let Err(e) = SetClipboardData(CF_UNICODETEXT, Some(HANDLE(memory.0))) {
+                let _ = GlobalFree(Some(memory));
+                return Err(e);
+            }
+            Ok(())
+        })();
+        let _ = CloseClipboard();
+        result
+    }
+}
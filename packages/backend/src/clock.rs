@@ -0,0 +1,153 @@
+use std::{
+    ffi::c_void,
+    sync::Mutex,
+    time::{
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+use windows::Win32::{
+    Foundation::{
+        ERROR_SUCCESS,
+        HANDLE,
+    },
+    System::Power::{
+        DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+        HPOWERNOTIFY,
+        PowerRegisterSuspendResumeNotification,
+        PowerUnregisterSuspendResumeNotification,
+    },
+    UI::WindowsAndMessaging::{
+        DEVICE_NOTIFY_CALLBACK,
+        PBT_APMRESUMEAUTOMATIC,
+        PBT_APMRESUMESUSPEND,
+    },
+};
+
+/// 单调时钟上的一个时刻与对应的 Unix 时间
+///
+/// 计算时间戳时从锚点出发按单调时钟推算，系统时间被修改后不会让进度条跳动，
+/// 跳转进度或从睡眠中恢复时再重新对齐系统时间
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    instant: Instant,
+    unix_ms: i64,
+}
+
+impl Anchor {
+    fn now() -> Self {
+        let unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        Self {
+            instant: Instant::now(),
+            unix_ms,
+        }
+    }
+
+    fn unix_ms_at(&self, instant: Instant) -> i64 {
+        self.unix_ms + instant.saturating_duration_since(self.instant).as_millis() as i64
+    }
+}
+
+static ANCHOR: Mutex<Option<Anchor>> = Mutex::new(None);
+
+/// 按单调时钟推算的当前 Unix 时间 (毫秒)
+pub fn unix_now_ms() -> i64 {
+    let Ok(mut anchor) = ANCHOR.lock() else {
+        return Anchor::now().unix_ms;
+    };
+    anchor
+        .get_or_insert_with(Anchor::now)
+        .unix_ms_at(Instant::now())
+}
+
+/// 重新对齐系统时间
+pub fn reanchor(reason: &'static str) {
+    let next = Anchor::now();
+    let Ok(mut anchor) = ANCHOR.lock() else {
+        return;
+    };
+    if let Some(previous) = anchor.replace(next) {
+        let drift_ms = next.unix_ms - previous.unix_ms_at(next.instant);
+        debug!(reason, drift_ms, "已重新对齐系统时间");
+    }
+}
+
+/// `PowerRegisterSuspendResumeNotification` 返回的注册句柄
+static POWER_NOTIFY: Mutex<Option<isize>> = Mutex::new(None);
+
+/// 系统会保存这个结构的地址，所以必须是静态的
+static SUBSCRIBE_PARAMETERS: SubscribeParameters =
+    SubscribeParameters(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+        Callback: Some(on_power_event),
+        Context: std::ptr::null_mut(),
+    });
+
+struct SubscribeParameters(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS);
+
+// Safety: 结构中只有函数指针和空指针，不会被修改
+unsafe impl Sync for SubscribeParameters {}
+
+unsafe extern "system" fn on_power_event(
+    _context: *const c_void,
+    event: u32,
+    _setting: *const c_void,
+) -> u32 {
+    if event == PBT_APMRESUMEAUTOMATIC || event == PBT_APMRESUMESUSPEND {
+        reanchor("从睡眠中恢复");
+    }
+    ERROR_SUCCESS.0
+}
+
+/// 注册睡眠恢复通知，暂停插件后恢复时会再次调用
+pub fn init() {
+    let Ok(mut registration) = POWER_NOTIFY.lock() else {
+        return;
+    };
+    if registration.is_some() {
+        return;
+    }
+
+    let mut handle: *mut c_void = std::ptr::null_mut();
+    let result = unsafe {
+        PowerRegisterSuspendResumeNotification(
+            DEVICE_NOTIFY_CALLBACK,
+            HANDLE(
+                std::ptr::from_ref(&SUBSCRIBE_PARAMETERS.0)
+                    .cast_mut()
+                    .cast(),
+            ),
+            &raw mut handle,
+        )
+    };
+    if result == ERROR_SUCCESS {
+        info!("已注册睡眠恢复通知");
+        *registration = Some(handle as isize);
+    } else {
+        warn!("注册睡眠恢复通知失败，从睡眠中恢复后不会重新对齐时间: {result:?}");
+    }
+}
+
+/// 插件卸载后系统不能再调用回调，所以关闭时必须取消注册
+pub fn shutdown() {
+    let Some(handle) = POWER_NOTIFY
+        .lock()
+        .ok()
+        .and_then(|mut handle| handle.take())
+    else {
+        return;
+    };
+    let result = unsafe { PowerUnregisterSuspendResumeNotification(HPOWERNOTIFY(handle)) };
+    if result != ERROR_SUCCESS {
+        warn!("取消睡眠恢复通知失败: {result:?}");
+    }
+}
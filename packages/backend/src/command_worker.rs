@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        Mutex,
+        mpsc::{
+            self,
+            RecvTimeoutError,
+            Sender,
+        },
+    },
+    time::Duration,
+};
+
+use tracing::{
+    debug_span,
+    warn,
+};
+
+use crate::{
+    dispatcher,
+    model::{
+        AppMessage,
+        CommandStatus,
+    },
+    request_id::{
+        self,
+        RequestId,
+    },
+    smtc_core::{
+        self,
+        SmtcEvent,
+    },
+    tasks,
+};
+
+/// 等待命令时检查取消标记的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct AsyncCommand {
+    msg: AppMessage,
+    request_id: RequestId,
+}
+
+static SENDER: Mutex<Option<Sender<AsyncCommand>>> = Mutex::new(None);
+
+/// 启动执行耗时命令的线程
+///
+/// `send_command` 在 CEF 渲染线程上调用，等待 Discord 线程或 `WinRT` 操作的命令
+/// 如果同步执行会卡住网易云的界面，所以放到这个线程上，结果通过 `CommandCompleted` 事件返回
+pub fn init() {
+    let (tx, rx) = mpsc::channel::<AsyncCommand>();
+    let spawn_result = tasks::spawn("command-worker-thread", move |token| {
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(command) => run(command),
+                Err(RecvTimeoutError::Timeout) if !token.is_cancelled() => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    match spawn_result {
+        Ok(()) => {
+            if let Ok(mut sender) = SENDER.lock() {
+                *sender = Some(tx);
+            }
+        }
+        Err(e) => warn!("无法启动命令工作线程: {e}"),
+    }
+}
+
+/// 把命令交给工作线程，前端用 `request_id` 对应之后收到的结果
+pub fn submit(msg: AppMessage, request_id: RequestId) -> Result<(), String> {
+    let sender = SENDER
+        .lock()
+        .map_err(|e| format!("命令工作线程锁中毒: {e}"))?
+        .clone()
+        .ok_or("命令工作线程未启动")?;
+    sender
+        .send(AsyncCommand { msg, request_id })
+        .map_err(|e| format!("发送命令到工作线程失败: {e}"))
+}
+
+fn run(command: AsyncCommand) {
+    let _span = debug_span!("async_command", request_id = &*command.request_id).entered();
+    let _request_id = request_id::enter(Some(command.request_id));
+
+    let event = match dispatcher::run_async_command(&command.msg) {
+        Ok(data) => SmtcEvent::CommandCompleted {
+            status: CommandStatus::Success,
+            message: None,
+            data: Some(data),
+        },
+        Err(message) => SmtcEvent::CommandCompleted {
+            status: CommandStatus::Error,
+            message: Some(message),
+            data: None,
+        },
+    };
+    smtc_core::dispatch_reply(&event);
+}
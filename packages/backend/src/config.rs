@@ -0,0 +1,656 @@
+use std::{
+    env,
+    fs,
+    path::PathBuf,
+    sync::{
+        LazyLock,
+        RwLock,
+    },
+};
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::platform;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// 持久化在数据目录中的后端配置
+///
+/// 这里只存放需要在前端连接之前就生效的配置 (例如日志)，
+/// 其余配置仍由前端保存并通过命令下发
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct BackendConfig {
+    pub logging: LoggingConfig,
+    pub smtc: SmtcConfig,
+    pub updater: UpdaterConfig,
+    pub http_server: HttpServerConfig,
+    pub audio_session: AudioSessionConfig,
+    pub watchdog: WatchdogConfig,
+    pub formatting: FormattingConfig,
+    pub discord: DiscordConfig,
+    pub pause_policy: PausePolicyConfig,
+    pub recorder: RecorderConfig,
+    pub sinks: SinksConfig,
+    pub enrichment: EnrichmentConfig,
+    pub listening_session: ListeningSessionConfig,
+}
+
+impl BackendConfig {
+    /// 去掉机密字段 (例如机器人令牌) 后的副本
+    ///
+    /// 事件会被转发到 HTTP 事件流，任何网页都可以读取，所以发送配置前必须先调用这个方法
+    #[must_use]
+    pub fn without_secrets(mut self) -> Self {
+        if let Some(assets) = &mut self.discord.cover.external_assets {
+            assets.token.clear();
+        }
+        self
+    }
+}
+
+/// Discord 的其余配置 (显示模式等) 由前端通过 `DiscordConfig` 命令下发
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub cover: DiscordCoverConfig,
+    pub reconnect: DiscordReconnectConfig,
+    /// 在一起听房间中时是否显示房间人数
+    pub show_party: bool,
+    /// 其他应用 (例如游戏或 Spotify) 占据系统媒体会话并正在播放时隐藏 Discord 状态
+    pub suppress_when_other_player_active: bool,
+    /// 暂停后在这个时间 (毫秒) 内切换到新歌时视为一次切歌，不清除 Activity，
+    /// 避免无缝播放和淡入淡出切歌时状态闪烁，为 0 时关闭
+    pub transition_grace_ms: u64,
+    /// "🎧 Listen" 之外的第二个按钮，Discord 最多显示两个按钮
+    pub secondary_button: DiscordSecondaryButton,
+    /// 同时运行多个 Discord 客户端时优先连接的客户端，没有找到时连接第一个
+    pub client: DiscordClientFlavor,
+    /// 直接连接 `discord-ipc-<pipe>`，设置后忽略 `client`
+    pub pipe: Option<u8>,
+    /// 与其他遵守同一约定的插件 (包括其他 InfLink-rs 实例) 共用 Discord 状态，
+    /// 已有插件在发布时让出，直到对方释放
+    pub cooperative: bool,
+    /// 在小图标的提示文字中显示网易云正在使用的输出设备，例如 "on Speakers"
+    pub show_output_device: bool,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        Self {
+            cover: DiscordCoverConfig::default(),
+            reconnect: DiscordReconnectConfig::default(),
+            show_party: true,
+            suppress_when_other_player_active: false,
+            transition_grace_ms: 1500,
+            secondary_button: DiscordSecondaryButton::None,
+            client: DiscordClientFlavor::Any,
+            pipe: None,
+            cooperative: false,
+            show_output_device: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscordSecondaryButton {
+    #[default]
+    None,
+    /// 打开专辑页面，需要前端提供专辑 ID
+    Album,
+    /// 打开第一位歌手的页面，需要前端提供歌手 ID
+    Artist,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscordClientFlavor {
+    /// 按管道顺序连接第一个可用的客户端
+    #[default]
+    Any,
+    Stable,
+    Ptb,
+    Canary,
+}
+
+/// 暂停播放时各个输出的行为
+///
+/// SMTC 总是保留并显示为已暂停，因为系统媒体浮窗需要它来恢复播放
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PausePolicyConfig {
+    /// 未设置时沿用前端的 "暂停时显示" 开关，即 `clear` 或 `badge`
+    pub discord: Option<DiscordPausePolicy>,
+    /// 暂停时 `/now-playing` 的内容
+    pub http_server: PausePolicy,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscordPausePolicy {
+    /// 清除 Activity
+    Clear,
+    /// 保留 Activity，进度停在暂停的位置
+    Keep,
+    /// 保留 Activity，并在小图标上显示暂停状态和进度
+    Badge,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PausePolicy {
+    /// 当作没有正在播放的歌曲
+    Clear,
+    #[default]
+    Keep,
+}
+
+/// 连接 Discord IPC 失败后的重试策略
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DiscordReconnectConfig {
+    /// 第一次重试前等待的秒数，之后每次失败翻倍
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+    /// 连续失败多少次后停止重试，直到重新启用 Discord RPC，未设置时一直重试
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for DiscordReconnectConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_secs: 5,
+            max_delay_secs: 60,
+            max_attempts: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DiscordCoverConfig {
+    /// 是否给网易云图片 CDN 的封面地址加上缩略图参数，关闭时使用原图
+    pub rewrite_url: bool,
+    /// 缩略图的边长 (像素)，高分屏下封面模糊时可以调大
+    pub size: u32,
+    /// JPEG 质量 (1-100)
+    pub quality: u8,
+    /// 设置后通过 Discord 的媒体代理显示封面，网易云 CDN 被屏蔽时封面也能正常加载
+    pub external_assets: Option<DiscordExternalAssets>,
+}
+
+/// Discord 的 `mp:external/` 资源只属于创建它的应用，需要使用自己的应用和机器人令牌，
+/// 设置后 Rich Presence 也会改用这个应用显示
+///
+/// 自己的应用需要上传名为 `ncm_icon` 的图片资源，否则没有封面时不显示图标
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct DiscordExternalAssets {
+    pub application_id: String,
+    /// 应用的机器人令牌，不会出现在发送给前端的 `ConfigChanged` 事件中
+    pub token: String,
+}
+
+impl Default for DiscordCoverConfig {
+    fn default() -> Self {
+        Self {
+            rewrite_url: true,
+            size: 150,
+            quality: 90,
+            external_assets: None,
+        }
+    }
+}
+
+/// 各个输出端显示时长和长文本的方式
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct FormattingConfig {
+    pub discord: TextFormat,
+    pub http_server: TextFormat,
+}
+
+impl Default for FormattingConfig {
+    fn default() -> Self {
+        Self {
+            // Discord 的 details 和 state 字段最多 128 个字符
+            discord: TextFormat {
+                max_width: Some(128),
+                duration_style: DurationStyle::Compact,
+            },
+            http_server: TextFormat::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TextFormat {
+    /// 文本的最大显示宽度，中日韩字符按两个宽度计算，超出时截断并以省略号结尾
+    pub max_width: Option<usize>,
+    pub duration_style: DurationStyle,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationStyle {
+    /// `3:05`、`1:02:03`
+    #[default]
+    Compact,
+    /// `03:05`、`1:02:03`
+    Padded,
+}
+
+/// 监视插件自身线程的 CPU 占用，用于区分插件和网易云本身的开销
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    pub sample_interval_secs: u64,
+    /// 插件所有线程合计占用单个逻辑核心的百分比
+    pub cpu_threshold_percent: f64,
+    /// 连续超过阈值多少次采样后发出警告
+    pub sustained_samples: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_interval_secs: 10,
+            cpu_threshold_percent: 20.0,
+            sustained_samples: 3,
+        }
+    }
+}
+
+/// 根据网易云实际的音频输出修正播放状态，用于前端崩溃后状态不同步的情况
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct AudioSessionConfig {
+    pub enabled: bool,
+    /// 播放状态与音频输出持续不一致多少秒后进行修正
+    pub mismatch_threshold_secs: u64,
+}
+
+impl Default for AudioSessionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mismatch_threshold_secs: 10,
+        }
+    }
+}
+
+/// 本地 HTTP 服务，供 Xbox Game Bar 小组件或 OBS 浏览器源等网页叠加层读取正在播放的歌曲
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpServerConfig {
+    pub enabled: bool,
+    /// 监听的端口，只会监听 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for HttpServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9863,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct UpdaterConfig {
+    /// 是否在启动后检查并下载新版本
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct RecorderConfig {
+    /// 是否把收到的每个命令 (不含封面数据) 追加到数据目录的 `command-recording.jsonl`，
+    /// 用于复现难以重现的状态问题
+    pub enabled: bool,
+}
+
+/// 连续收听的会话，结束时发送 `SessionSummary` 事件
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListeningSessionConfig {
+    /// 暂停超过这个时间 (分钟) 后结束当前会话，为 0 时不统计
+    pub end_after_pause_mins: u64,
+}
+
+impl Default for ListeningSessionConfig {
+    fn default() -> Self {
+        Self {
+            end_after_pause_mins: 30,
+        }
+    }
+}
+
+/// 前端发来的元数据缺少字段时，在交给各个输出端之前尝试补全
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct EnrichmentConfig {
+    /// 没有封面时根据歌曲 ID 从网易云 API 获取专辑封面 URL
+    pub cover: ResolverConfig,
+    /// 没有时长时根据歌曲 ID 从网易云 API 获取
+    pub duration: ResolverConfig,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ResolverConfig {
+    pub enabled: bool,
+    /// 单次查询的超时 (毫秒)，查询在后台进行，不会阻塞其他命令
+    pub timeout_ms: u64,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// 各个输出端最近一次被前端启用或禁用的状态，由 `EnableSink`/`DisableSink` 写入
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SinksConfig {
+    pub smtc: bool,
+    pub discord: bool,
+    /// 输出端工作线程最多积压的消息数，超过后丢弃新消息，修改后重启插件生效
+    pub queue_len: usize,
+}
+
+impl Default for SinksConfig {
+    fn default() -> Self {
+        Self {
+            smtc: false,
+            discord: false,
+            queue_len: 256,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// 自定义日志目录，未设置时使用数据目录
+    pub log_dir: Option<PathBuf>,
+    /// 文件日志的格式
+    pub format: LogFormat,
+    /// 是否记录各个 span 的耗时，开启后可以在诊断信息中查看
+    pub span_timing: bool,
+    /// 是否将日志输出到 `OutputDebugString`，以便在 DebugView/WinDbg 中查看
+    pub debug_output: bool,
+    /// 将日志实时转发到的 WebSocket 地址，例如 `ws://127.0.0.1:9000`
+    pub forward_url: Option<String>,
+    /// 日志文件的轮转方式
+    pub rotation: LogRotation,
+    /// 最多保留的日志文件数
+    pub max_log_files: usize,
+    /// 按大小轮转时单个日志文件的最大大小 (MB)
+    pub max_file_size_mb: u64,
+    /// 是否在日志中隐藏封面 base64 数据、密钥和 URL 查询字符串
+    pub redact: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            log_dir: None,
+            format: LogFormat::default(),
+            span_timing: false,
+            debug_output: false,
+            forward_url: None,
+            rotation: LogRotation::default(),
+            max_log_files: 7,
+            max_file_size_mb: 10,
+            redact: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SmtcConfig {
+    /// 拖动进度条时，最后一次跳转请求之后需要等待的时间 (毫秒)，为 0 时不进行防抖
+    pub seek_debounce_ms: u64,
+    /// 在这个时间 (毫秒) 内重复按下同一个按钮时只响应第一次，为 0 时不合并
+    ///
+    /// 默认关闭，因为连续按下一首通常就是想跳过多首歌
+    pub button_coalesce_ms: u64,
+    /// 按住媒体键时 Windows 会连续发送下一首/上一首，持续超过这个时间 (毫秒)
+    /// 后改为在歌曲内快进/快退
+    pub hold_seek_threshold_ms: u64,
+    /// 按住时每一步快进或快退的时长 (毫秒)，为 0 时关闭按住检测
+    ///
+    /// 默认关闭，因为按住时的第一次按下无法与单击区分，仍会先切换一次歌曲
+    pub hold_seek_step_ms: u64,
+    /// 在这个时间 (毫秒) 内连按两次播放/暂停视为下一首，连按三次视为上一首，
+    /// 为 0 时关闭。开启后单次按下也要等待这个时间才会生效
+    pub gesture_window_ms: u64,
+    /// 封面解码后的大小上限 (KB)，超过时改用封面 URL，为 0 时不限制
+    pub max_cover_kb: u64,
+    /// 给 SMTC 所属的播放器设置一段静音的播放源，部分读取全局媒体会话的应用
+    /// 在没有播放项时会间歇性读到空的歌曲信息
+    pub silent_source: bool,
+    /// 启用后先隐藏会话，收到第一首歌的元数据后才显示在媒体浮窗中，
+    /// 避免浮窗中短暂出现没有内容的网易云条目
+    pub defer_until_metadata: bool,
+    /// 暂停超过这个时间 (分钟) 后从媒体浮窗中隐藏，再次播放时恢复，为 0 时不隐藏
+    pub hide_after_paused_mins: u64,
+    /// 从封面中提取的主要颜色数量，通过 `CoverPalette` 事件发给前端，为 0 时不提取
+    pub palette_colors: u32,
+    pub match_hints: MatchHints,
+}
+
+impl Default for SmtcConfig {
+    fn default() -> Self {
+        Self {
+            seek_debounce_ms: 150,
+            button_coalesce_ms: 0,
+            hold_seek_threshold_ms: 600,
+            hold_seek_step_ms: 0,
+            gesture_window_ms: 0,
+            max_cover_kb: 4096,
+            silent_source: true,
+            defer_until_metadata: false,
+            hide_after_paused_mins: 0,
+            palette_colors: 0,
+            match_hints: MatchHints::default(),
+        }
+    }
+}
+
+/// 写入 SMTC 流派字段的标识符，让歌词软件等应用可以精确匹配歌曲
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct MatchHints {
+    /// 写入 `{prefix}-{歌曲 ID}`
+    pub include_song_id: bool,
+    /// 写入 `{prefix}-ALBUM-{专辑 ID}`
+    pub include_album_id: bool,
+    /// 为每个歌手写入 `{prefix}-ARTIST-{歌手 ID}`
+    pub include_artist_id: bool,
+    pub prefix: String,
+}
+
+impl Default for MatchHints {
+    fn default() -> Self {
+        Self {
+            include_song_id: true,
+            include_album_id: false,
+            include_artist_id: false,
+            prefix: "NCM".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 纯文本格式
+    #[default]
+    Text,
+    /// JSON Lines 格式，包含时间戳、级别、目标、字段和 span 信息
+    Json,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// 每小时创建一个新文件
+    Hourly,
+    /// 每天创建一个新文件
+    #[default]
+    Daily,
+    /// 文件超过 `max_file_size_mb` 后创建一个新文件
+    Size,
+}
+
+static CONFIG: LazyLock<RwLock<BackendConfig>> = LazyLock::new(|| RwLock::new(load()));
+
+/// 便携模式的标记文件，放在插件所在的目录或它的上级目录中
+///
+/// 文件内容为空时数据保存在标记文件旁边的 `InfLink-rs` 目录，
+/// 否则内容是相对于标记文件所在目录的数据目录
+const PORTABLE_MARKER: &str = "inflink-portable";
+
+/// 向上查找标记文件的层数，插件可能被 `BetterNCM` 解压到 `plugins_runtime` 下的子目录中，
+/// 标记文件放在那里会在插件更新时被删除
+const PORTABLE_MARKER_DEPTH: usize = 4;
+
+/// 直接指定数据目录的环境变量，例如让共用网易云的多个 Windows 账户使用不同的目录
+const DATA_DIR_ENV: &str = "INFLINK_DATA_DIR";
+
+const DATA_DIR_NAME: &str = "InfLink-rs";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DataDirSource {
+    /// `%APPDATA%\InfLink-rs`
+    AppData,
+    /// 环境变量 `INFLINK_DATA_DIR`
+    Environment,
+    /// 找到了便携模式的标记文件
+    Portable,
+}
+
+static DATA_DIR: LazyLock<Option<(PathBuf, DataDirSource)>> = LazyLock::new(resolve_data_dir);
+
+/// 插件的数据目录 (配置、日志、录制和更新)，默认为 `%APPDATA%\InfLink-rs`
+pub fn data_dir() -> Option<PathBuf> {
+    DATA_DIR.as_ref().map(|(path, _)| path.clone())
+}
+
+pub fn data_dir_source() -> Option<DataDirSource> {
+    DATA_DIR.as_ref().map(|&(_, source)| source)
+}
+
+fn resolve_data_dir() -> Option<(PathBuf, DataDirSource)> {
+    if let Some(dir) = env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Some((PathBuf::from(dir), DataDirSource::Environment));
+    }
+
+    let marker = platform::plugin_dir().and_then(|plugin_dir| {
+        plugin_dir
+            .ancestors()
+            .take(PORTABLE_MARKER_DEPTH)
+            .map(|dir| dir.join(PORTABLE_MARKER))
+            .find(|marker| marker.is_file())
+    });
+    if let Some(marker) = marker
+        && let Some(base) = marker.parent()
+    {
+        let custom = fs::read_to_string(&marker).unwrap_or_default();
+        let custom = custom.trim();
+        let dir = base.join(if custom.is_empty() {
+            DATA_DIR_NAME
+        } else {
+            custom
+        });
+        return Some((dir, DataDirSource::Portable));
+    }
+
+    dirs::data_dir().map(|path| (path.join(DATA_DIR_NAME), DataDirSource::AppData))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    data_dir().map(|path| path.join(CONFIG_FILE_NAME))
+}
+
+/// 获取当前的后端配置
+///
+/// 配置会在第一次调用时从磁盘读取，这通常发生在日志系统初始化之前，
+/// 所以这里的错误只能输出到标准错误
+pub fn get() -> BackendConfig {
+    CONFIG
+        .read()
+        .map_or_else(|e| e.into_inner().clone(), |config| config.clone())
+}
+
+/// 修改当前配置并写回配置文件
+pub fn update(f: impl FnOnce(&mut BackendConfig)) -> Result<(), String> {
+    let mut config = CONFIG.write().map_err(|e| format!("配置锁中毒: {e}"))?;
+    f(&mut config);
+    save(&config)
+}
+
+fn save(config: &BackendConfig) -> Result<(), String> {
+    let path = config_path().ok_or_else(|| "无法获取数据目录".to_string())?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建数据目录失败: {e}"))?;
+    }
+
+    let content = toml::to_string_pretty(config).map_err(|e| format!("序列化配置失败: {e}"))?;
+    fs::write(&path, content).map_err(|e| format!("写入配置文件 {} 失败: {e}", path.display()))
+}
+
+/// 重新读取配置文件，内容有变化时返回旧配置和新配置
+///
+/// 文件无法解析时 (例如编辑器只写入了一半) 保留当前配置并返回错误
+pub fn reload() -> Result<Option<(BackendConfig, BackendConfig)>, String> {
+    let new_config = read_file()?;
+    let mut config = CONFIG.write().map_err(|e| format!("配置锁中毒: {e}"))?;
+    if *config == new_config {
+        return Ok(None);
+    }
+
+    let old_config = std::mem::replace(&mut *config, new_config.clone());
+    drop(config);
+    Ok(Some((old_config, new_config)))
+}
+
+fn load() -> BackendConfig {
+    read_file().unwrap_or_else(|e| {
+        eprintln!("[InfLink-rs] {e}");
+        BackendConfig::default()
+    })
+}
+
+fn read_file() -> Result<BackendConfig, String> {
+    let Some(path) = config_path() else {
+        return Ok(BackendConfig::default());
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BackendConfig::default()),
+        Err(e) => return Err(format!("读取配置文件 {} 失败: {e}", path.display())),
+    };
+
+    toml::from_str(&content).map_err(|e| format!("解析配置文件 {} 失败: {e}", path.display()))
+}
@@ -0,0 +1,139 @@
+use std::{
+    fs,
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{
+    EventKind,
+    RecursiveMode,
+    Watcher,
+};
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+
+use crate::{
+    config::{
+        self,
+        BackendConfig,
+    },
+    discord,
+    gsmtc,
+    http_server,
+    smtc_core,
+    tasks,
+};
+
+/// 编辑器保存文件时通常会连续产生多个事件，等文件稳定后再读取
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// 检查取消标记的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 监视配置文件，文件被修改后重新加载并应用可以在运行时生效的配置
+pub fn init() {
+    let (Some(dir), Some(path)) = (config::data_dir(), config::config_path()) else {
+        return;
+    };
+
+    // 监视所在目录而不是文件本身，这样文件被删除后重新创建也能收到通知
+    if let Err(e) = fs::create_dir_all(&dir) {
+        warn!("创建数据目录 {} 失败: {e}", dir.display());
+        return;
+    }
+
+    let spawn_result = tasks::spawn("config-watch-thread", move |token| {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!("创建配置文件监视器失败: {e}");
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            warn!("监视数据目录 {} 失败: {e}", dir.display());
+            return;
+        }
+        debug!("开始监视配置文件 {}", path.display());
+
+        while !token.is_cancelled() {
+            let event = match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) => event,
+                Ok(Err(e)) => {
+                    debug!("配置文件监视器报告错误: {e}");
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+
+            let is_config = event.paths.iter().any(|p| p == &path);
+            if !is_config || matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            if !token.sleep(DEBOUNCE) {
+                break;
+            }
+            // 丢弃防抖期间积累的事件
+            while rx.try_recv().is_ok() {}
+
+            reload();
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动配置文件监视线程: {e}");
+    }
+}
+
+fn reload() {
+    match config::reload() {
+        // 内容与内存中的配置相同，例如 `config::update` 自己写入的文件
+        Ok(None) => {}
+        Ok(Some((old, new))) => {
+            info!("配置文件已修改，重新加载配置");
+            apply(&old, &new);
+            smtc_core::notify_config_changed(new);
+        }
+        Err(e) => warn!("{e}，继续使用当前配置"),
+    }
+}
+
+fn apply(old: &BackendConfig, new: &BackendConfig) {
+    if old.formatting.discord != new.formatting.discord
+        || old.discord != new.discord
+        || old.pause_policy.discord != new.pause_policy.discord
+    {
+        discord::reload_config();
+    }
+
+    if !old.discord.suppress_when_other_player_active
+        && new.discord.suppress_when_other_player_active
+    {
+        gsmtc::init();
+    }
+
+    if new.http_server.enabled && !http_server::is_running() {
+        http_server::init();
+    } else if old.http_server != new.http_server {
+        info!("HTTP 服务的关闭和端口修改需要重启网易云后生效");
+    }
+
+    let restart_required = [
+        ("logging", old.logging != new.logging),
+        ("recorder", old.recorder != new.recorder),
+        ("smtc", old.smtc != new.smtc),
+        ("updater", old.updater != new.updater),
+        ("audio_session", old.audio_session != new.audio_session),
+        ("watchdog", old.watchdog != new.watchdog),
+    ];
+    for (section, changed) in restart_required {
+        if changed {
+            info!(section, "该部分配置需要重启网易云后生效");
+        }
+    }
+}
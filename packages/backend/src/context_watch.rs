@@ -0,0 +1,56 @@
+use std::{
+    sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    time::Duration,
+};
+
+use tracing::warn;
+
+use crate::{
+    logger,
+    smtc_core,
+    tasks,
+};
+
+/// 检查回调所在的 V8 上下文是否仍然有效的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// 定期检查事件回调和日志回调所在的 V8 上下文，在上下文被销毁 (例如页面跳转) 后立即清除回调
+///
+/// 插件无法注册 CEF 的 `OnContextReleased` 通知，它属于网易云自己的 `CefRenderProcessHandler`，
+/// 所以只能在渲染线程上轮询 `is_valid`，而不是等到下一次调用回调失败时才发现
+///
+/// 可以重复调用，线程已经在运行时直接返回
+pub fn start() {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("context-watch-thread", |token| {
+        while token.sleep(CHECK_INTERVAL) {
+            if !smtc_core::has_event_callback() && !logger::has_callback() {
+                continue;
+            }
+
+            let post_result = cef_safe::renderer_post_task(|| {
+                smtc_core::release_callback_if_context_invalid();
+                logger::release_callback_if_context_invalid();
+            });
+            if post_result.is_err() {
+                warn!("提交 V8 上下文检查任务失败");
+            }
+        }
+
+        // 任务被取消 (例如卸载插件) 后，下次注册回调时允许再次启动
+        STARTED.store(false, Ordering::SeqCst);
+    });
+
+    if let Err(e) = spawn_result {
+        STARTED.store(false, Ordering::SeqCst);
+        warn!("无法启动 V8 上下文检查线程: {e}");
+    }
+}
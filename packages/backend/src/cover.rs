@@ -0,0 +1,189 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use base64::{
+    Engine,
+    engine::general_purpose,
+};
+use serde::Serialize;
+use tracing::debug;
+use windows::{
+    Graphics::Imaging::{
+        BitmapDecoder,
+        BitmapEncoder,
+        BitmapInterpolationMode,
+    },
+    Storage::Streams::{
+        DataReader,
+        DataWriter,
+        InMemoryRandomAccessStream,
+    },
+};
+
+use crate::{
+    model::{
+        CoverFormat,
+        GetCurrentCoverPayload,
+        SharedMetadata,
+    },
+    winrt::{
+        JoinTimeout,
+        LOCAL_TIMEOUT,
+    },
+};
+
+/// 当前歌曲的元数据，封面仍以前端传来的 base64 保存，需要时才解码
+static CURRENT: Mutex<Option<SharedMetadata>> = Mutex::new(None);
+
+/// `GetCurrentCover` 的返回值
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentCover {
+    ncm_id: Option<u64>,
+    /// 只有 URL 封面时为空
+    mime_type: Option<&'static str>,
+    base64: Option<String>,
+    path: Option<PathBuf>,
+    /// 前端只提供了封面 URL 时，后端没有可复用的图片数据，只返回 URL
+    url: Option<String>,
+}
+
+pub fn set_current(metadata: SharedMetadata) {
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = Some(metadata);
+    }
+}
+
+pub fn current_metadata() -> Option<SharedMetadata> {
+    CURRENT.lock().ok().and_then(|current| current.clone())
+}
+
+pub fn current_cover(payload: GetCurrentCoverPayload) -> Result<CurrentCover, String> {
+    let metadata = CURRENT
+        .lock()
+        .map_err(|_| "封面缓存已损坏".to_string())?
+        .clone()
+        .ok_or("还没有收到歌曲元数据")?;
+    let cover = metadata.cover.as_ref().ok_or("当前歌曲没有封面")?;
+
+    let Some(base64_data) = &cover.base64 else {
+        let url = cover.url.clone().ok_or("当前歌曲没有封面")?;
+        return Ok(CurrentCover {
+            ncm_id: metadata.ncm_id,
+            mime_type: None,
+            base64: None,
+            path: None,
+            url: Some(url),
+        });
+    };
+
+    let mut bytes = general_purpose::STANDARD
+        .decode(base64_data)
+        .map_err(|e| format!("解码封面 Base64 失败: {e}"))?;
+    if let Some(max_size) = payload.max_size {
+        bytes = resize(&bytes, max_size).map_err(|e| format!("缩小封面失败: {e}"))?;
+    }
+
+    let (mime_type, extension) = if bytes.starts_with(b"\x89PNG") {
+        ("image/png", "png")
+    } else {
+        ("image/jpeg", "jpg")
+    };
+
+    let mut result = CurrentCover {
+        ncm_id: metadata.ncm_id,
+        mime_type: Some(mime_type),
+        base64: None,
+        path: None,
+        url: cover.url.clone(),
+    };
+    match payload.format {
+        CoverFormat::Base64 => {
+            result.base64 = Some(general_purpose::STANDARD.encode(&bytes));
+        }
+        CoverFormat::File => {
+            result.path = Some(write_temp_file(&bytes, metadata.ncm_id, extension)?);
+        }
+    }
+    Ok(result)
+}
+
+/// 1×1 的 PNG 图片，用于自检
+const TEST_IMAGE_BASE64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+/// 用与处理真实封面相同的 `WinRT` 图像组件解码测试图片
+pub fn self_test() -> Result<(), String> {
+    let bytes = general_purpose::STANDARD
+        .decode(TEST_IMAGE_BASE64)
+        .map_err(|e| e.to_string())?;
+    let (width, height) = decode_size(&bytes).map_err(|e| format!("{e:?}"))?;
+    if (width, height) != (1, 1) {
+        return Err(format!("解码后的尺寸为 {width}×{height}"));
+    }
+    Ok(())
+}
+
+fn decode_size(bytes: &[u8]) -> windows::core::Result<(u32, u32)> {
+    let stream = InMemoryRandomAccessStream::new()?;
+    let writer = DataWriter::CreateDataWriter(&stream)?;
+    writer.WriteBytes(bytes)?;
+    writer.StoreAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+    writer.DetachStream()?;
+    stream.Seek(0)?;
+
+    let decoder = BitmapDecoder::CreateAsync(&stream)?.join_timeout(LOCAL_TIMEOUT)?;
+    Ok((decoder.PixelWidth()?, decoder.PixelHeight()?))
+}
+
+/// 等比缩小到最长边不超过 `max_size`，已经足够小的图片原样返回
+fn resize(bytes: &[u8], max_size: u32) -> windows::core::Result<Vec<u8>> {
+    let source = InMemoryRandomAccessStream::new()?;
+    let writer = DataWriter::CreateDataWriter(&source)?;
+    writer.WriteBytes(bytes)?;
+    writer.StoreAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+    writer.DetachStream()?;
+    source.Seek(0)?;
+
+    let decoder = BitmapDecoder::CreateAsync(&source)?.join_timeout(LOCAL_TIMEOUT)?;
+    let (width, height) = (decoder.PixelWidth()?, decoder.PixelHeight()?);
+    let longest = width.max(height);
+    if max_size == 0 || longest <= max_size {
+        return Ok(bytes.to_vec());
+    }
+
+    let scale = f64::from(max_size) / f64::from(longest);
+    let output = InMemoryRandomAccessStream::new()?;
+    // 转码会保留原图的编码格式
+    let encoder =
+        BitmapEncoder::CreateForTranscodingAsync(&output, &decoder)?.join_timeout(LOCAL_TIMEOUT)?;
+    let transform = encoder.BitmapTransform()?;
+    transform.SetScaledWidth(scaled(width, scale))?;
+    transform.SetScaledHeight(scaled(height, scale))?;
+    transform.SetInterpolationMode(BitmapInterpolationMode::Fant)?;
+    encoder.FlushAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+    debug!(width, height, max_size, "已缩小封面");
+
+    let size = output.Size()?;
+    let reader = DataReader::CreateDataReader(&output.GetInputStreamAt(0)?)?;
+    reader.LoadAsync(size as u32)?.join_timeout(LOCAL_TIMEOUT)?;
+    let mut resized = vec![0; size as usize];
+    reader.ReadBytes(&mut resized)?;
+    Ok(resized)
+}
+
+fn scaled(length: u32, scale: f64) -> u32 {
+    ((f64::from(length) * scale).round() as u32).max(1)
+}
+
+/// 每首歌只保留一个临时文件，同一首歌的新请求会覆盖旧文件
+fn write_temp_file(bytes: &[u8], ncm_id: Option<u64>, extension: &str) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join("InfLink-rs");
+    fs::create_dir_all(&dir).map_err(|e| format!("创建临时目录失败: {e}"))?;
+    let name = ncm_id.map_or_else(|| "cover-current".to_string(), |id| format!("cover-{id}"));
+    let path = dir.join(format!("{name}.{extension}"));
+    fs::write(&path, bytes).map_err(|e| format!("写入封面文件失败: {e}"))?;
+    Ok(path)
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{
+    audio_session,
+    config::{
+        self,
+        DataDirSource,
+    },
+    discord::{
+        self,
+        ConnectionStatus,
+    },
+    dispatcher,
+    ffi::{
+        self,
+        LoaderInfo,
+    },
+    logger::{
+        self,
+        SpanTiming,
+    },
+    maintenance::{
+        self,
+        MaintenanceReport,
+    },
+    platform::{
+        self,
+        Capabilities,
+    },
+    sinks::{
+        self,
+        WorkerHealth,
+    },
+    smtc_core::{
+        self,
+        EventCallbackStatus,
+    },
+    timeline,
+    watchdog::{
+        self,
+        ThreadCpuUsage,
+    },
+};
+
+/// 用于排查问题的后端运行状态
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    version: &'static str,
+    /// 加载插件的 `BetterNCM` 和网易云版本
+    loader: Option<LoaderInfo>,
+    /// 当前系统支持的可选功能
+    platform: &'static Capabilities,
+    data_dir: Option<PathBuf>,
+    /// 数据目录是如何确定的
+    data_dir_source: Option<DataDirSource>,
+    /// 各个 span 的耗时统计，未开启 `logging.span_timing` 时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_timings: Option<Vec<SpanTiming>>,
+    event_callback: EventCallbackStatus,
+    discord: ConnectionStatus,
+    /// 是否被 `Quiesce` 命令暂停
+    quiesced: bool,
+    /// 本次启动以来检测到的播放次数
+    new_plays: u64,
+    /// 暂停很久后继续播放同一首歌，没有计为新播放的次数
+    resumed_plays: u64,
+    /// 队列积压时丢弃的过时时间线更新数量
+    superseded_timeline_updates: u64,
+    /// 根据音频输出修正播放状态的次数
+    play_state_corrections: u64,
+    /// 默认输出设备的名称
+    output_device: Option<String>,
+    /// 当前歌曲实际播放的时长 (毫秒)，不包括暂停和跳转的部分
+    track_played_ms: f64,
+    /// 最近一次采样中插件各线程的 CPU 占用
+    thread_cpu: Vec<ThreadCpuUsage>,
+    /// 受监督的输出端工作线程
+    sink_workers: Vec<WorkerHealth>,
+    /// 最近一次数据目录清理的结果，启动后还没有清理过时为空
+    maintenance: Option<MaintenanceReport>,
+}
+
+pub fn collect() -> Diagnostics {
+    Diagnostics {
+        version: env!("CARGO_PKG_VERSION"),
+        loader: ffi::loader_info(),
+        platform: platform::capabilities(),
+        data_dir: config::data_dir(),
+        data_dir_source: config::data_dir_source(),
+        span_timings: logger::span_timings(),
+        event_callback: smtc_core::event_callback_status(),
+        discord: discord::connection_status(),
+        quiesced: dispatcher::is_quiesced(),
+        new_plays: dispatcher::new_play_count(),
+        resumed_plays: dispatcher::resumed_play_count(),
+        superseded_timeline_updates: dispatcher::superseded_timeline_count(),
+        play_state_corrections: audio_session::correction_count(),
+        output_device: audio_session::output_device_name(),
+        track_played_ms: timeline::played_ms(),
+        thread_cpu: watchdog::last_sample(),
+        sink_workers: sinks::worker_health(),
+        maintenance: maintenance::last_report(),
+    }
+}
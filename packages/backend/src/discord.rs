@@ -1,67 +1,224 @@
 use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    hash::{
+        BuildHasher,
+        RandomState,
+    },
+    io,
     sync::{
+        Arc,
         LazyLock,
         Mutex,
         mpsc::{
             self,
             Receiver,
             Sender,
+            SyncSender,
+            TrySendError,
         },
     },
-    thread,
     time::{
         Duration,
-        SystemTime,
-        UNIX_EPOCH,
+        Instant,
     },
 };
 
 use discord_rich_presence::{
     DiscordIpc,
-    DiscordIpcClient,
     activity::{
         Activity,
         ActivityType,
         Assets,
         Button,
+        Party,
         StatusDisplayType,
         Timestamps,
     },
 };
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use tracing::{
     debug,
     info,
+    info_span,
     warn,
 };
+use ureq::{
+    Agent,
+    tls::{
+        TlsConfig,
+        TlsProvider,
+    },
+};
 
-use crate::model::{
-    DiscordAppNameMode,
-    DiscordConfigPayload,
-    DiscordDisplayMode,
-    MetadataPayload,
-    PlayStatePayload,
-    PlaybackStatus,
-    SharedMetadata,
-    TimelinePayload,
+use crate::{
+    audio_session,
+    clock,
+    config::{
+        self,
+        DiscordCoverConfig,
+        DiscordExternalAssets,
+        DiscordPausePolicy,
+        DiscordReconnectConfig,
+        DiscordSecondaryButton,
+        TextFormat,
+    },
+    discord_pipe::{
+        ClientPreference,
+        ConnectedClient,
+        DiscordPipeClient,
+    },
+    formatting,
+    instance,
+    model::{
+        DiscordAppNameMode,
+        DiscordConfigPayload,
+        DiscordDisplayMode,
+        ListenTogetherRoom,
+        MetadataPayload,
+        PlayStatePayload,
+        PlaybackContext,
+        PlaybackStatus,
+        PreviewPresencePayload,
+        SharedMetadata,
+        SinkId,
+    },
+    presence_lock::PresenceLock,
+    sinks::{
+        self,
+        Supervisor,
+    },
+    tasks,
+    timeline::Timeline,
 };
 
 const APP_ID: &str = "1427186361827594375";
 const NCM_ICON_ASSET_KEY: &str = "ncm_icon";
+const NCM_ICON_TEXT: &str = "NetEase CloudMusic";
+const PRIVATE_FM_APP_NAME: &str = "Private FM";
+/// 改写后的封面地址的缓存时间
+const COVER_URL_TTL: Duration = Duration::from_mins(10);
+/// Discord 会长期保留代理后的图片，同一个地址不需要反复申请
+const EXTERNAL_ASSET_TTL: Duration = Duration::from_hours(6);
+const EXTERNAL_ASSET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 测试 Activity 的显示时间，之后恢复正常的状态
+const TEST_ACTIVITY_DURATION: Duration = Duration::from_secs(10);
+/// 等待 RPC 线程返回测试结果的最长时间
+const TEST_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
 
 // 主要用来应对跳转进度的更新
 const TIMESTAMP_UPDATE_THRESHOLD_MS: i64 = 100;
-const RECONNECT_COOLDOWN_SECONDS: u8 = 5;
 
 enum RpcMessage {
     Metadata(SharedMetadata),
     PlayState(PlayStatePayload),
-    Timeline(TimelinePayload),
+    Timeline(Timeline),
     Enable,
     Disable,
     Config(DiscordConfigPayload),
+    ListenTogether(Option<ListenTogetherRoom>),
+    Suppress(Option<String>),
+    InstanceRoleChanged,
+    /// 配置文件被修改后重新读取 Discord 相关的配置
+    ReloadConfig,
+    /// 后台线程申请 external asset 的结果
+    ExternalAsset {
+        url: String,
+        application_id: String,
+        result: Result<String, String>,
+    },
+    /// 发送一个测试用的 Activity，并通过 Sender 返回结果
+    Test(Sender<Result<(), String>>),
+    /// 只检查能否连接 Discord，不改变当前的 Activity
+    Probe(Sender<Result<(), String>>),
+    Preview(
+        Box<PreviewPresencePayload>,
+        Sender<Result<serde_json::Value, String>>,
+    ),
+}
+
+static SENDER: LazyLock<Mutex<Option<SyncSender<RpcMessage>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Discord IPC 的连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    /// 连接失败，等到 `retry_at` 之后再重试
+    Backoff {
+        attempts: u32,
+        retry_at: Instant,
+    },
+    /// 连续失败的次数达到上限，直到重新启用前都不再尝试连接
+    Idle,
 }
 
-static SENDER: LazyLock<Mutex<Option<Sender<RpcMessage>>>> = LazyLock::new(|| Mutex::new(None));
+/// 连接状态的快照，用于诊断信息
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStatus {
+    state: &'static str,
+    /// 连续连接失败的次数
+    failed_attempts: u32,
+    /// 处于退避状态时距离下次重试的时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_in_ms: Option<u64>,
+    /// 已连接时实际连接到的 Discord 客户端
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client: Option<ConnectedClient>,
+}
+
+static CONNECTION_STATE: Mutex<ConnectionState> = Mutex::new(ConnectionState::Disconnected);
+static CONNECTED_CLIENT: Mutex<Option<ConnectedClient>> = Mutex::new(None);
+
+pub fn connection_status() -> ConnectionStatus {
+    let state = CONNECTION_STATE
+        .lock()
+        .map_or(ConnectionState::Disconnected, |state| *state);
+
+    let (name, failed_attempts, retry_in_ms) = match state {
+        ConnectionState::Disconnected => ("disconnected", 0, None),
+        ConnectionState::Connecting => ("connecting", 0, None),
+        ConnectionState::Connected => ("connected", 0, None),
+        ConnectionState::Backoff { attempts, retry_at } => (
+            "backoff",
+            attempts,
+            Some(
+                retry_at
+                    .saturating_duration_since(Instant::now())
+                    .as_millis() as u64,
+            ),
+        ),
+        ConnectionState::Idle => ("idle", 0, None),
+    };
+
+    ConnectionStatus {
+        state: name,
+        failed_attempts,
+        retry_in_ms,
+        client: CONNECTED_CLIENT.lock().ok().and_then(|client| *client),
+    }
+}
+
+/// 指数退避，并加上 ±20% 的随机抖动，避免多个客户端同时重试
+fn backoff_delay(attempts: u32, config: &DiscordReconnectConfig) -> Duration {
+    let base = Duration::from_secs(config.base_delay_secs.max(1));
+    let max = Duration::from_secs(config.max_delay_secs.max(config.base_delay_secs));
+    let delay = base
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(max);
+
+    let random = RandomState::new().hash_one(Instant::now());
+    let jitter = (random % 401) as f64 / 1000.0 - 0.2;
+    delay.mul_f64(1.0 + jitter)
+}
 
 #[derive(Debug, Clone, PartialEq)]
 struct ActivityData {
@@ -70,15 +227,32 @@ struct ActivityData {
     current_time: f64,
     cached_cover_url: String,
     cached_song_url: String,
+    /// 第二个按钮的文字和地址，没有对应的 ID 时不显示
+    cached_secondary_button: Option<(&'static str, String)>,
     cached_app_name: Option<String>,
+    cached_large_text: String,
+    cached_details: String,
+    cached_state: String,
+    /// 小图标的提示文字，开启 `discord.show_output_device` 时带有输出设备
+    cached_small_text: String,
 }
 
 impl ActivityData {
-    fn from_metadata(metadata: SharedMetadata, mode: &DiscordAppNameMode) -> Self {
-        let cached_cover_url =
-            Self::process_cover_url(metadata.cover.as_ref().and_then(|c| c.url.as_deref()));
+    fn from_metadata(
+        metadata: SharedMetadata,
+        mode: &DiscordAppNameMode,
+        format: &TextFormat,
+        secondary_button: DiscordSecondaryButton,
+        cached_cover_url: String,
+    ) -> Self {
         let cached_song_url = Self::process_song_url(metadata.ncm_id);
+        let cached_secondary_button = Self::process_secondary_button(secondary_button, &metadata);
         let cached_app_name = Self::compute_app_name(mode, &metadata);
+        let cached_large_text = format
+            .text(&Self::compute_large_text(&metadata))
+            .into_owned();
+        let cached_details = format.text(&metadata.song_name).into_owned();
+        let cached_state = format.text(&metadata.author_name).into_owned();
 
         Self {
             metadata,
@@ -86,20 +260,42 @@ impl ActivityData {
             current_time: 0.0,
             cached_cover_url,
             cached_song_url,
+            cached_secondary_button,
             cached_app_name,
+            cached_large_text,
+            cached_details,
+            cached_state,
+            cached_small_text: NCM_ICON_TEXT.to_string(),
         }
     }
 
-    fn update_metadata(&mut self, metadata: SharedMetadata, mode: &DiscordAppNameMode) {
-        self.cached_cover_url =
-            Self::process_cover_url(metadata.cover.as_ref().and_then(|c| c.url.as_deref()));
+    fn update_metadata(
+        &mut self,
+        metadata: SharedMetadata,
+        mode: &DiscordAppNameMode,
+        format: &TextFormat,
+        secondary_button: DiscordSecondaryButton,
+        cover_url: String,
+    ) {
+        self.cached_cover_url = cover_url;
         self.cached_song_url = Self::process_song_url(metadata.ncm_id);
+        self.cached_secondary_button = Self::process_secondary_button(secondary_button, &metadata);
         self.cached_app_name = Self::compute_app_name(mode, &metadata);
+        self.cached_large_text = format
+            .text(&Self::compute_large_text(&metadata))
+            .into_owned();
+        self.cached_details = format.text(&metadata.song_name).into_owned();
+        self.cached_state = format.text(&metadata.author_name).into_owned();
         self.metadata = metadata;
         self.current_time = 0.0;
     }
 
     fn compute_app_name(mode: &DiscordAppNameMode, metadata: &MetadataPayload) -> Option<String> {
+        // 与网易云一致，显示为 "Listening to Private FM"
+        if metadata.context == PlaybackContext::Radio {
+            return Some(PRIVATE_FM_APP_NAME.to_string());
+        }
+
         match mode {
             DiscordAppNameMode::Default => None,
             DiscordAppNameMode::Song => Some(metadata.song_name.clone()),
@@ -109,67 +305,511 @@ impl ActivityData {
         }
     }
 
-    fn process_cover_url(original_url: Option<&str>) -> String {
+    fn compute_large_text(metadata: &MetadataPayload) -> String {
+        metadata.playlist.as_ref().map_or_else(
+            || metadata.album_name.clone(),
+            |playlist| {
+                format!(
+                    "{} · from {} ({}/{})",
+                    metadata.album_name, playlist.name, playlist.position, playlist.length
+                )
+            },
+        )
+    }
+
+    fn process_cover_url(original_url: Option<&str>, cover_config: &DiscordCoverConfig) -> String {
         original_url.map_or_else(
             || NCM_ICON_ASSET_KEY.to_string(),
             |url| {
-                let url = url.replace("http://", "https://");
+                // 只升级地址本身的协议，查询参数中的地址保持不变
+                let url = url
+                    .strip_prefix("http://")
+                    .map_or_else(|| url.to_string(), |rest| format!("https://{rest}"));
                 let base_url = url.split('?').next().unwrap_or(&url);
-                format!("{base_url}?imageView&enlarge=1&type=jpeg&quality=90&thumbnail=150y150")
+
+                // 缩略图参数只对网易云的图片 CDN 有效，其他 CDN 的地址原样使用
+                if !cover_config.rewrite_url || !is_ncm_image_cdn(base_url) {
+                    return url;
+                }
+
+                let DiscordCoverConfig { size, quality, .. } = cover_config;
+                format!(
+                    "{base_url}?imageView&enlarge=1&type=jpeg&quality={quality}&thumbnail={size}y{size}"
+                )
             },
         )
     }
 
     fn process_song_url(ncm_id: Option<u64>) -> String {
-        ncm_id.map_or_else(
-            || "https://music.163.com/".to_string(),
-            |id| format!("https://music.163.com/song?id={id}"),
-        )
+        formatting::song_url(ncm_id)
     }
+
+    fn process_secondary_button(
+        button: DiscordSecondaryButton,
+        metadata: &MetadataPayload,
+    ) -> Option<(&'static str, String)> {
+        match button {
+            DiscordSecondaryButton::None => None,
+            DiscordSecondaryButton::Album => metadata
+                .album_id
+                .filter(|&id| id > 0)
+                .map(|id| ("💿 Album", format!("https://music.163.com/album?id={id}"))),
+            DiscordSecondaryButton::Artist => metadata
+                .artist_ids
+                .first()
+                .filter(|&&id| id > 0)
+                .map(|id| ("🎤 Artist", format!("https://music.163.com/artist?id={id}"))),
+        }
+    }
+}
+
+/// 按歌曲 ID 缓存改写后的封面地址，重复收到同一首歌的元数据时不再重新拼接
+#[derive(Debug, Default)]
+struct CoverUrlCache {
+    entries: HashMap<u64, CachedCoverUrl>,
+    /// 图片地址对应的 `mp:external/` 资源和过期时刻
+    external_assets: HashMap<String, (String, Instant)>,
+    /// 正在后台申请 external asset 的图片地址
+    pending_assets: HashSet<String>,
 }
 
+#[derive(Debug)]
+struct CachedCoverUrl {
+    original: Option<String>,
+    processed: String,
+    expires_at: Instant,
+}
+
+impl CoverUrlCache {
+    fn resolve(&mut self, metadata: &MetadataPayload, cover_config: &DiscordCoverConfig) -> String {
+        self.resolve_at(metadata, cover_config, Instant::now())
+    }
+
+    /// 与 [`Self::resolve`] 相同，但使用给定的时刻判断缓存是否过期
+    fn resolve_at(
+        &mut self,
+        metadata: &MetadataPayload,
+        cover_config: &DiscordCoverConfig,
+        now: Instant,
+    ) -> String {
+        let original = metadata.cover.as_ref().and_then(|c| c.url.as_deref());
+        let Some(ncm_id) = metadata.ncm_id else {
+            let processed = ActivityData::process_cover_url(original, cover_config);
+            return self.external_asset(processed, cover_config);
+        };
+
+        self.entries.retain(|_, entry| entry.expires_at > now);
+
+        // 同一首歌的封面地址也可能改变，例如前端换用了更高清的图片
+        if let Some(entry) = self.entries.get(&ncm_id)
+            && entry.original.as_deref() == original
+        {
+            return entry.processed.clone();
+        }
+
+        let processed = ActivityData::process_cover_url(original, cover_config);
+        let processed = self.external_asset(processed, cover_config);
+        self.entries.insert(
+            ncm_id,
+            CachedCoverUrl {
+                original: original.map(str::to_owned),
+                processed: processed.clone(),
+                expires_at: now + COVER_URL_TTL,
+            },
+        );
+        processed
+    }
+}
+
+impl CoverUrlCache {
+    /// 没有缓存的资源时原样返回图片地址，并在后台申请 external asset，
+    /// 申请成功后通过 [`Self::insert_external_asset`] 换上代理后的地址
+    fn external_asset(&mut self, url: String, cover_config: &DiscordCoverConfig) -> String {
+        let Some(assets) = &cover_config.external_assets else {
+            return url;
+        };
+        if !url.starts_with("https://") {
+            return url;
+        }
+
+        let now = Instant::now();
+        self.external_assets
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        if let Some((key, _)) = self.external_assets.get(&url) {
+            return key.clone();
+        }
+
+        if self.pending_assets.insert(url.clone())
+            && let Err(e) = spawn_external_asset_request(url.clone(), assets.clone())
+        {
+            warn!("无法启动 Discord 封面代理线程: {e}");
+            self.pending_assets.remove(&url);
+        }
+        url
+    }
+
+    /// 记录申请到的资源，并替换缓存中仍在使用原始地址的封面
+    fn insert_external_asset(&mut self, url: String, key: &str) {
+        self.pending_assets.remove(&url);
+        for entry in self.entries.values_mut() {
+            if entry.processed == url {
+                entry.processed = key.to_string();
+            }
+        }
+        self.external_assets
+            .insert(url, (key.to_string(), Instant::now() + EXTERNAL_ASSET_TTL));
+    }
+}
+
+/// 申请请求最多需要 [`EXTERNAL_ASSET_TIMEOUT`]，放在工作线程上会让测试和预览等待超时
+fn spawn_external_asset_request(url: String, assets: DiscordExternalAssets) -> io::Result<()> {
+    tasks::spawn("discord-asset-thread", move |token| {
+        let result = request_external_asset(&url, &assets);
+        if !token.is_cancelled() {
+            send(RpcMessage::ExternalAsset {
+                url,
+                application_id: assets.application_id,
+                result,
+            });
+        }
+    })
+}
+
+#[derive(Deserialize)]
+struct ExternalAsset {
+    external_asset_path: String,
+}
+
+static HTTP_AGENT: LazyLock<Agent> = LazyLock::new(|| {
+    Agent::config_builder()
+        .tls_config(
+            TlsConfig::builder()
+                .provider(TlsProvider::NativeTls)
+                .build(),
+        )
+        .timeout_global(Some(EXTERNAL_ASSET_TIMEOUT))
+        .build()
+        .into()
+});
+
+/// 让 Discord 的媒体代理抓取图片，返回可以直接作为 `large_image` 的资源键
+fn request_external_asset(url: &str, assets: &DiscordExternalAssets) -> Result<String, String> {
+    let endpoint = format!(
+        "https://discord.com/api/v10/applications/{}/external-assets",
+        assets.application_id
+    );
+    let response: Vec<ExternalAsset> = HTTP_AGENT
+        .post(&endpoint)
+        .header("Authorization", &format!("Bot {}", assets.token))
+        .send_json(serde_json::json!({ "urls": [url] }))
+        .map_err(|e| e.to_string())?
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("解析 Discord 响应失败: {e}"))?;
+
+    response
+        .into_iter()
+        .next()
+        .map(|asset| format!("mp:{}", asset.external_asset_path))
+        .ok_or_else(|| "Discord 没有返回资源".to_string())
+}
+
+fn is_ncm_image_cdn(url: &str) -> bool {
+    url.strip_prefix("https://")
+        .and_then(|rest| rest.split('/').next())
+        .is_some_and(|host| host.ends_with(".music.126.net"))
+}
+
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug)]
 struct RpcWorker {
-    client: Option<DiscordIpcClient>,
+    client: Option<DiscordPipeClient>,
     data: Option<ActivityData>,
     is_enabled: bool,
-    connect_retry_count: u8,
+    state: ConnectionState,
     // 上次发送的结束时间戳
     // 用于防抖，也用于判断是否要清除 Activity
     last_sent_end_timestamp: Option<i64>,
     show_when_paused: bool,
+    /// 配置文件中设置的暂停行为，优先于前端的 `show_when_paused`
+    pause_policy: Option<DiscordPausePolicy>,
     display_mode: DiscordDisplayMode,
     app_name_mode: DiscordAppNameMode,
+    text_format: TextFormat,
+    cover_config: DiscordCoverConfig,
+    cover_urls: CoverUrlCache,
+    reconnect_config: DiscordReconnectConfig,
+    /// 当前所在的一起听房间
+    party: Option<ListenTogetherRoom>,
+    show_party: bool,
+    secondary_button: DiscordSecondaryButton,
+    /// 被 Discord 拒绝的房间，在进入新房间前不再发送 party 字段
+    rejected_party_id: Option<String>,
+    /// 正在占据系统媒体会话的其他播放器，存在时隐藏 Activity
+    suppressed_by: Option<String>,
+    /// 正在显示测试 Activity 时，在这个时刻之前不同步正常的状态
+    test_activity_until: Option<Instant>,
+    transition_grace: Duration,
+    /// 暂停后等待切歌的截止时刻，在此之前保持当前的 Activity 不变
+    transition_until: Option<Instant>,
+    client_preference: ClientPreference,
+    /// 开启 `discord.cooperative` 时与其他插件协调发布权
+    presence_lock: Option<PresenceLock>,
+    show_output_device: bool,
 }
 
 impl Default for RpcWorker {
     fn default() -> Self {
+        let config = config::get();
         Self {
             client: None,
             data: None,
             is_enabled: false,
-            connect_retry_count: 0,
+            state: ConnectionState::Disconnected,
             last_sent_end_timestamp: None,
             show_when_paused: false,
+            pause_policy: config.pause_policy.discord,
             display_mode: DiscordDisplayMode::Name,
             app_name_mode: DiscordAppNameMode::Default,
+            text_format: config.formatting.discord,
+            cover_config: config.discord.cover,
+            cover_urls: CoverUrlCache::default(),
+            reconnect_config: config.discord.reconnect,
+            party: None,
+            show_party: config.discord.show_party,
+            secondary_button: config.discord.secondary_button,
+            rejected_party_id: None,
+            suppressed_by: None,
+            test_activity_until: None,
+            transition_grace: Duration::from_millis(config.discord.transition_grace_ms),
+            transition_until: None,
+            client_preference: ClientPreference {
+                flavor: config.discord.client,
+                pipe: config.discord.pipe,
+            },
+            presence_lock: open_presence_lock(config.discord.cooperative),
+            show_output_device: config.discord.show_output_device,
         }
     }
 }
 
 impl RpcWorker {
+    fn pause_policy(&self) -> DiscordPausePolicy {
+        self.pause_policy.unwrap_or(if self.show_when_paused {
+            DiscordPausePolicy::Badge
+        } else {
+            DiscordPausePolicy::Clear
+        })
+    }
+
+    /// 设置了 `external_assets` 时使用其中的应用
+    fn application_id(&self) -> String {
+        self.cover_config.external_assets.as_ref().map_or_else(
+            || APP_ID.to_string(),
+            |assets| assets.application_id.clone(),
+        )
+    }
+
+    /// 没有开启协作模式时总是返回 `true`
+    ///
+    /// 暂停且配置为隐藏时不显示 Activity，此时让出发布权
+    fn acquire_presence(&mut self) -> bool {
+        let showing = self.data.as_ref().is_some_and(|data| {
+            data.status == PlaybackStatus::Playing
+                || self.pause_policy() != DiscordPausePolicy::Clear
+        });
+        if !showing {
+            self.release_presence();
+            return true;
+        }
+        self.presence_lock
+            .as_mut()
+            .is_none_or(PresenceLock::try_acquire)
+    }
+
+    fn release_presence(&mut self) {
+        if let Some(lock) = &mut self.presence_lock {
+            lock.release();
+        }
+    }
+
+    /// 让出发布权后需要定期检查其他插件是否已经释放
+    const fn waiting_for_presence(&self) -> bool {
+        self.is_enabled && self.presence_lock.is_some() && self.data.is_some()
+    }
+
+    fn small_text(&self) -> String {
+        let device = self
+            .show_output_device
+            .then(audio_session::output_device_name)
+            .flatten();
+        device.map_or_else(
+            || NCM_ICON_TEXT.to_string(),
+            |device| format!("{NCM_ICON_TEXT} · on {device}"),
+        )
+    }
+
+    fn new_client(&self) -> DiscordPipeClient {
+        DiscordPipeClient::new(self.application_id(), self.client_preference)
+    }
+
+    fn reload_config(&mut self) {
+        let config = config::get();
+        let previous_application_id = self.application_id();
+        self.text_format = config.formatting.discord;
+        self.cover_config = config.discord.cover;
+        self.cover_urls = CoverUrlCache::default();
+        self.reconnect_config = config.discord.reconnect;
+        self.show_party = config.discord.show_party;
+        self.secondary_button = config.discord.secondary_button;
+        self.show_output_device = config.discord.show_output_device;
+        self.pause_policy = config.pause_policy.discord;
+        self.transition_grace = Duration::from_millis(config.discord.transition_grace_ms);
+        if config.discord.cooperative != self.presence_lock.is_some() {
+            self.presence_lock = open_presence_lock(config.discord.cooperative);
+        }
+        let previous_preference = self.client_preference;
+        self.client_preference = ClientPreference {
+            flavor: config.discord.client,
+            pipe: config.discord.pipe,
+        };
+
+        // 用新的格式重新生成当前歌曲的文本和封面地址，保留播放进度
+        if let Some(data) = &mut self.data {
+            let metadata = data.metadata.clone();
+            let cover_url = self.cover_urls.resolve(&metadata, &self.cover_config);
+            let current_time = data.current_time;
+            data.update_metadata(
+                metadata,
+                &self.app_name_mode,
+                &self.text_format,
+                self.secondary_button,
+                cover_url,
+            );
+            data.current_time = current_time;
+        }
+        self.last_sent_end_timestamp = None;
+        if self.application_id() != previous_application_id {
+            info!("Discord 应用已改变，重新连接");
+            self.disconnect();
+        } else if self.client_preference != previous_preference {
+            info!("Discord 客户端选择已改变，重新连接");
+            self.disconnect();
+        }
+        info!("已重新加载 Discord 配置");
+    }
+
+    fn set_external_asset(
+        &mut self,
+        url: String,
+        application_id: &str,
+        result: Result<String, String>,
+    ) {
+        // 申请期间配置被修改过，资源属于之前的应用
+        if self
+            .cover_config
+            .external_assets
+            .as_ref()
+            .is_none_or(|assets| assets.application_id != application_id)
+        {
+            self.cover_urls.pending_assets.remove(&url);
+            return;
+        }
+
+        let key = match result {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("通过 Discord 代理封面失败，继续使用原始地址: {e}");
+                self.cover_urls.pending_assets.remove(&url);
+                return;
+            }
+        };
+        debug!(key, "已通过 Discord 代理封面");
+
+        if let Some(data) = &mut self.data
+            && data.cached_cover_url == url
+        {
+            data.cached_cover_url.clone_from(&key);
+            self.last_sent_end_timestamp = None;
+        }
+        self.cover_urls.insert_external_asset(url, &key);
+    }
+
+    fn set_metadata(&mut self, payload: SharedMetadata) {
+        let cover_url = self.cover_urls.resolve(&payload, &self.cover_config);
+        let new_data = match self.data.take() {
+            Some(mut d) => {
+                d.update_metadata(
+                    payload,
+                    &self.app_name_mode,
+                    &self.text_format,
+                    self.secondary_button,
+                    cover_url,
+                );
+                d
+            }
+            None => ActivityData::from_metadata(
+                payload,
+                &self.app_name_mode,
+                &self.text_format,
+                self.secondary_button,
+                cover_url,
+            ),
+        };
+        self.data = Some(new_data);
+        self.last_sent_end_timestamp = None;
+        self.transition_until = None;
+    }
+
     fn handle_message(&mut self, msg: RpcMessage) {
         match msg {
             RpcMessage::Enable => {
                 info!("启用 Discord RPC");
                 self.is_enabled = true;
-                self.connect_retry_count = 0;
+                // 重新启用时从头开始计算重试次数
+                if matches!(
+                    self.state,
+                    ConnectionState::Backoff { .. } | ConnectionState::Idle
+                ) {
+                    self.transition(ConnectionState::Disconnected);
+                }
             }
             RpcMessage::Disable => {
                 info!("禁用 Discord RPC");
                 self.is_enabled = false;
                 self.disconnect();
             }
+            RpcMessage::Test(reply) => {
+                let result = self.show_test_activity();
+                if let Err(e) = &result {
+                    warn!("发送测试 Activity 失败: {e}");
+                }
+                let _ = reply.send(result);
+            }
+            RpcMessage::Probe(reply) => {
+                let _ = reply.send(self.probe());
+            }
+            RpcMessage::Preview(payload, reply) => {
+                let _ = reply.send(self.preview(payload));
+            }
+            RpcMessage::Suppress(app) => {
+                self.suppressed_by = app;
+                self.last_sent_end_timestamp = None;
+            }
+            RpcMessage::InstanceRoleChanged => {
+                self.last_sent_end_timestamp = None;
+            }
+            RpcMessage::ReloadConfig => self.reload_config(),
+            RpcMessage::ExternalAsset {
+                url,
+                application_id,
+                result,
+            } => self.set_external_asset(url, &application_id, result),
+            RpcMessage::ListenTogether(room) => {
+                debug!(?room, "更新一起听房间");
+                self.party = room;
+                self.last_sent_end_timestamp = None;
+            }
             RpcMessage::Config(payload) => {
                 info!(
                     show_when_paused = ?payload.show_when_paused,
@@ -191,17 +831,7 @@ impl RpcWorker {
 
                 self.last_sent_end_timestamp = None;
             }
-            RpcMessage::Metadata(payload) => {
-                let new_data = match self.data.take() {
-                    Some(mut d) => {
-                        d.update_metadata(payload, &self.app_name_mode);
-                        d
-                    }
-                    None => ActivityData::from_metadata(payload, &self.app_name_mode),
-                };
-                self.data = Some(new_data);
-                self.last_sent_end_timestamp = None;
-            }
+            RpcMessage::Metadata(payload) => self.set_metadata(payload),
             RpcMessage::PlayState(payload) => {
                 if let Some(data) = &mut self.data {
                     if payload.status == PlaybackStatus::Playing
@@ -209,54 +839,182 @@ impl RpcWorker {
                     {
                         self.last_sent_end_timestamp = None;
                     }
+                    // 切歌时前端会依次报告暂停、播放和新歌的元数据，
+                    // 等待一小段时间，把这个过程当作一次切歌处理
+                    if payload.status == PlaybackStatus::Paused
+                        && data.status == PlaybackStatus::Playing
+                        && !self.transition_grace.is_zero()
+                    {
+                        self.transition_until = Some(Instant::now() + self.transition_grace);
+                    }
                     data.status = payload.status;
                 }
             }
-            RpcMessage::Timeline(payload) => {
+            RpcMessage::Timeline(timeline) => {
                 if let Some(data) = &mut self.data {
-                    data.current_time = payload.current_time;
+                    data.current_time = timeline.position_ms();
                 }
             }
         }
     }
 
+    fn transition(&mut self, next: ConnectionState) {
+        if self.state != next {
+            info!(from = ?self.state, to = ?next, "Discord 连接状态变更");
+        }
+        self.state = next;
+
+        if let Ok(mut state) = CONNECTION_STATE.lock() {
+            *state = next;
+        }
+        if let Ok(mut connected) = CONNECTED_CLIENT.lock() {
+            *connected = self
+                .client
+                .as_ref()
+                .filter(|_| next == ConnectionState::Connected)
+                .and_then(DiscordPipeClient::connected_client);
+        }
+    }
+
     fn disconnect(&mut self) {
         if let Some(mut client) = self.client.take() {
             let _ = client.clear_activity();
             let _ = client.close();
         }
         self.last_sent_end_timestamp = None;
+
+        // 退避和空闲状态需要保留，否则会立即重试
+        if matches!(
+            self.state,
+            ConnectionState::Connecting | ConnectionState::Connected
+        ) {
+            self.transition(ConnectionState::Disconnected);
+        }
     }
 
     fn connect(&mut self) {
-        if self.connect_retry_count > 0 {
-            self.connect_retry_count -= 1;
-            return;
-        }
+        let previous_attempts = match self.state {
+            ConnectionState::Disconnected => 0,
+            ConnectionState::Backoff { attempts, retry_at } if Instant::now() >= retry_at => {
+                attempts
+            }
+            _ => return,
+        };
+
+        self.transition(ConnectionState::Connecting);
 
-        let mut client = DiscordIpcClient::new(APP_ID);
+        let mut client = self.new_client();
         match client.connect() {
             Ok(()) => {
-                info!("Discord IPC 已连接");
+                info!(client = ?client.connected_client(), "Discord IPC 已连接");
                 self.client = Some(client);
                 self.last_sent_end_timestamp = None;
+                self.transition(ConnectionState::Connected);
             }
             Err(e) => {
-                info!("连接 Discord IPC 失败: {e:?}. Discord 可能未运行");
-                self.connect_retry_count = RECONNECT_COOLDOWN_SECONDS;
+                let attempts = previous_attempts + 1;
+                info!(attempts, "连接 Discord IPC 失败: {e:?}. Discord 可能未运行");
+
+                if self
+                    .reconnect_config
+                    .max_attempts
+                    .is_some_and(|max| attempts >= max)
+                {
+                    warn!(attempts, "连接 Discord IPC 的失败次数已达上限，停止重试");
+                    self.transition(ConnectionState::Idle);
+                } else {
+                    let delay = backoff_delay(attempts, &self.reconnect_config);
+                    debug!(delay_ms = delay.as_millis(), "稍后重试连接 Discord IPC");
+                    self.transition(ConnectionState::Backoff {
+                        attempts,
+                        retry_at: Instant::now() + delay,
+                    });
+                }
             }
         }
     }
 
+    /// 已经连接时直接返回，否则用临时的客户端连接后立即断开
+    fn probe(&self) -> Result<(), String> {
+        if self.client.is_some() {
+            return Ok(());
+        }
+        let mut client = self.new_client();
+        client
+            .connect()
+            .map_err(|e| format!("连接 Discord IPC 失败，Discord 可能未运行: {e}"))?;
+        let _ = client.close();
+        Ok(())
+    }
+
+    /// 不需要播放音乐就能验证 Discord 连接是否正常
+    fn show_test_activity(&mut self) -> Result<(), String> {
+        if self.client.is_none() {
+            let mut client = self.new_client();
+            client
+                .connect()
+                .map_err(|e| format!("连接 Discord IPC 失败，Discord 可能未运行: {e}"))?;
+            self.client = Some(client);
+            self.transition(ConnectionState::Connected);
+        }
+
+        let client = self.client.as_mut().ok_or("Discord IPC 未连接")?;
+        let activity = Activity::new()
+            .details("Test Track")
+            .state("InfLink-rs")
+            .activity_type(ActivityType::Listening)
+            .assets(
+                Assets::new()
+                    .large_image(NCM_ICON_ASSET_KEY)
+                    .large_text("InfLink-rs"),
+            );
+
+        if let Err(e) = client.set_activity(activity) {
+            self.disconnect();
+            return Err(format!("设置测试 Activity 失败: {e}"));
+        }
+
+        info!("已发送测试 Activity");
+        self.test_activity_until = Some(Instant::now() + TEST_ACTIVITY_DURATION);
+        Ok(())
+    }
+
     fn sync_discord(&mut self) {
+        if let Some(until) = self.test_activity_until {
+            if Instant::now() < until {
+                return;
+            }
+            self.test_activity_until = None;
+            self.last_sent_end_timestamp = None;
+        }
+
+        if let Some(until) = self.transition_until {
+            if Instant::now() < until {
+                return;
+            }
+            // 等待期间没有切歌，按普通的暂停处理
+            self.transition_until = None;
+            self.last_sent_end_timestamp = None;
+        }
+
         if !self.is_enabled {
+            self.release_presence();
             if self.client.is_some() {
                 self.disconnect();
             }
             return;
         }
 
-        if self.data.is_none() {
+        if self.data.is_none() || self.suppressed_by.is_some() || !instance::is_active() {
+            self.release_presence();
+            if let Some(client) = &mut self.client {
+                let _ = client.clear_activity();
+                self.last_sent_end_timestamp = None;
+            }
+            return;
+        }
+
+        if !self.acquire_presence() {
             if let Some(client) = &mut self.client {
                 let _ = client.clear_activity();
                 self.last_sent_end_timestamp = None;
@@ -268,14 +1026,44 @@ impl RpcWorker {
             self.connect();
         }
 
+        let small_text = self.small_text();
+        if let Some(data) = &mut self.data {
+            data.cached_small_text = small_text;
+        }
+
+        let party = self.party.as_ref().filter(|room| {
+            self.show_party && self.rejected_party_id.as_ref() != Some(&room.room_id)
+        });
+
+        let pause_policy = self.pause_policy();
         if let (Some(client), Some(data)) = (&mut self.client, &self.data) {
-            let success = Self::perform_update(
+            let mut success = Self::perform_update(
                 client,
                 data,
                 &mut self.last_sent_end_timestamp,
-                self.show_when_paused,
+                pause_policy,
                 &self.display_mode,
+                &self.text_format,
+                party,
             );
+
+            // Discord 可能会拒绝 party 字段，此时不带 party 重试一次
+            if !success && party.is_some() {
+                success = Self::perform_update(
+                    client,
+                    data,
+                    &mut self.last_sent_end_timestamp,
+                    pause_policy,
+                    &self.display_mode,
+                    &self.text_format,
+                    None,
+                );
+                if success {
+                    warn!("Discord 拒绝了一起听房间信息，在进入新房间前不再发送");
+                    self.rejected_party_id = party.map(|room| room.room_id.clone());
+                }
+            }
+
             if !success {
                 self.disconnect();
             }
@@ -285,14 +1073,18 @@ impl RpcWorker {
     fn build_base_activity<'a>(
         data: &'a ActivityData,
         display_mode: &DiscordDisplayMode,
+        party: Option<&'a ListenTogetherRoom>,
     ) -> Activity<'a> {
         let assets = Assets::new()
             .large_image(&data.cached_cover_url)
-            .large_text(&data.metadata.album_name)
+            .large_text(&data.cached_large_text)
             .small_image(NCM_ICON_ASSET_KEY)
-            .small_text("NetEase CloudMusic");
+            .small_text(&data.cached_small_text);
 
-        let buttons = vec![Button::new("🎧 Listen", &data.cached_song_url)];
+        let mut buttons = vec![Button::new("🎧 Listen", &data.cached_song_url)];
+        if let Some((label, url)) = &data.cached_secondary_button {
+            buttons.push(Button::new(*label, url));
+        }
 
         let status_type = match display_mode {
             DiscordDisplayMode::Name => StatusDisplayType::Name,
@@ -301,8 +1093,8 @@ impl RpcWorker {
         };
 
         let mut activity = Activity::new()
-            .details(&data.metadata.song_name)
-            .state(&data.metadata.author_name)
+            .details(&data.cached_details)
+            .state(&data.cached_state)
             .activity_type(ActivityType::Listening)
             .assets(assets)
             .buttons(buttons)
@@ -312,6 +1104,18 @@ impl RpcWorker {
             activity = activity.name(name);
         }
 
+        // 显示为 "2 of 5"，人数不合法时 Discord 会拒绝整个 Activity
+        if let Some(room) = party
+            && room.size > 0
+            && room.size <= room.max_size
+        {
+            activity = activity.party(
+                Party::new()
+                    .id(&room.room_id)
+                    .size([room.size as i32, room.max_size as i32]),
+            );
+        }
+
         activity
     }
 
@@ -320,10 +1124,7 @@ impl RpcWorker {
         // 开始和结束时间戳向后平移一年以实现在暂停时进度静止的效果
         const ONE_YEAR_MS: i64 = 365 * 24 * 60 * 60 * 1000;
 
-        let now_ms = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as i64;
+        let now_ms = clock::unix_now_ms();
 
         let current_progress_ms = current_time as i64;
         let future_start = (now_ms - current_progress_ms) + ONE_YEAR_MS;
@@ -333,10 +1134,7 @@ impl RpcWorker {
     }
 
     fn calc_playing_timestamps(current_time: f64, duration: f64) -> (i64, i64) {
-        let now_ms = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as i64;
+        let now_ms = clock::unix_now_ms();
 
         let duration_ms = duration as i64;
         let current_time_ms = current_time as i64;
@@ -348,80 +1146,115 @@ impl RpcWorker {
         (start, end)
     }
 
-    fn perform_update(
-        client: &mut DiscordIpcClient,
-        data: &ActivityData,
-        last_sent_end_timestamp: &mut Option<i64>,
-        show_when_paused: bool,
+    fn paused_text(data: &ActivityData, text_format: &TextFormat) -> String {
+        data.metadata.duration.map_or_else(
+            || "Paused".to_string(),
+            |duration| {
+                format!(
+                    "Paused · {} / {}",
+                    text_format.duration(data.current_time),
+                    text_format.duration(duration)
+                )
+            },
+        )
+    }
+
+    /// 生成要发送的 Activity 和其中的结束时间戳，暂停且配置为隐藏时返回 `None`
+    fn compose_activity<'a>(
+        data: &'a ActivityData,
+        pause_policy: DiscordPausePolicy,
         display_mode: &DiscordDisplayMode,
-    ) -> bool {
-        let mut activity = Self::build_base_activity(data, display_mode);
-        let mut new_end_timestamp = None;
-        let should_send;
+        party: Option<&'a ListenTogetherRoom>,
+        paused_text: &'a str,
+    ) -> Option<(Activity<'a>, Option<i64>)> {
+        let mut activity = Self::build_base_activity(data, display_mode, party);
+        let duration = data.metadata.duration.filter(|&duration| duration > 0.0);
 
         match data.status {
             PlaybackStatus::Paused => {
-                if !show_when_paused {
-                    debug!("播放暂停且配置为隐藏，清除 Activity");
-                    if let Err(e) = client.clear_activity() {
-                        warn!("清除 Discord Activity 失败: {e:?}");
-                        return false;
-                    }
-                    *last_sent_end_timestamp = None;
-                    return true;
+                if pause_policy == DiscordPausePolicy::Clear {
+                    return None;
                 }
 
-                if let Some(duration) = data.metadata.duration
-                    && duration > 0.0
-                {
+                if let Some(duration) = duration {
                     let (start, end) = Self::calc_paused_timestamps(data.current_time, duration);
 
                     debug!(future_start = start, future_end = end, "应用 hack 时间戳");
 
-                    activity = activity
-                        .timestamps(Timestamps::new().start(start).end(end))
-                        .assets(
+                    activity = activity.timestamps(Timestamps::new().start(start).end(end));
+                    if pause_policy == DiscordPausePolicy::Badge {
+                        activity = activity.assets(
                             Assets::new()
                                 .large_image(&data.cached_cover_url)
-                                .large_text(&data.metadata.album_name)
+                                .large_text(&data.cached_large_text)
                                 .small_image(NCM_ICON_ASSET_KEY)
-                                .small_text("Paused"),
+                                .small_text(paused_text),
                         );
+                    }
                 }
-
-                should_send = true;
-                *last_sent_end_timestamp = None;
+                Some((activity, None))
             }
             PlaybackStatus::Playing => {
-                if let Some(duration) = data.metadata.duration
-                    && duration > 0.0
-                {
-                    let (start, end) = Self::calc_playing_timestamps(data.current_time, duration);
-
-                    // 频繁调用 Discord RPC 接口会导致限流，所以在跳转发生时再更新时间戳
-                    if let Some(last_end) = last_sent_end_timestamp {
-                        let diff = (*last_end - end).abs();
-                        if diff < TIMESTAMP_UPDATE_THRESHOLD_MS {
-                            return true;
-                        }
-                        debug!(
-                            diff_ms = diff,
-                            threshold_ms = TIMESTAMP_UPDATE_THRESHOLD_MS,
-                            "进度变更超过阈值，触发更新"
-                        );
-                    }
+                let Some(duration) = duration else {
+                    return Some((activity, None));
+                };
+                let (start, end) = Self::calc_playing_timestamps(data.current_time, duration);
+                activity = activity.timestamps(Timestamps::new().start(start).end(end));
+                Some((activity, Some(end)))
+            }
+        }
+    }
 
-                    activity = activity.timestamps(Timestamps::new().start(start).end(end));
-                    new_end_timestamp = Some(end);
-                    should_send = true;
-                } else {
-                    should_send = last_sent_end_timestamp.is_some();
-                    if should_send {
-                        warn!("没有时长，清除时间戳");
+    fn perform_update(
+        client: &mut DiscordPipeClient,
+        data: &ActivityData,
+        last_sent_end_timestamp: &mut Option<i64>,
+        pause_policy: DiscordPausePolicy,
+        display_mode: &DiscordDisplayMode,
+        text_format: &TextFormat,
+        party: Option<&ListenTogetherRoom>,
+    ) -> bool {
+        let paused_text = Self::paused_text(data, text_format);
+        let Some((activity, new_end_timestamp)) =
+            Self::compose_activity(data, pause_policy, display_mode, party, &paused_text)
+        else {
+            debug!("播放暂停且配置为隐藏，清除 Activity");
+            if let Err(e) = client.clear_activity() {
+                warn!("清除 Discord Activity 失败: {e:?}");
+                return false;
+            }
+            *last_sent_end_timestamp = None;
+            return true;
+        };
+
+        let should_send = match (data.status, new_end_timestamp) {
+            (PlaybackStatus::Paused, _) => {
+                *last_sent_end_timestamp = None;
+                true
+            }
+            (PlaybackStatus::Playing, Some(end)) => {
+                // 频繁调用 Discord RPC 接口会导致限流，所以在跳转发生时再更新时间戳
+                if let Some(last_end) = last_sent_end_timestamp {
+                    let diff = (*last_end - end).abs();
+                    if diff < TIMESTAMP_UPDATE_THRESHOLD_MS {
+                        return true;
                     }
+                    debug!(
+                        diff_ms = diff,
+                        threshold_ms = TIMESTAMP_UPDATE_THRESHOLD_MS,
+                        "进度变更超过阈值，触发更新"
+                    );
                 }
+                true
             }
-        }
+            (PlaybackStatus::Playing, None) => {
+                let should_send = last_sent_end_timestamp.is_some();
+                if should_send {
+                    warn!("没有时长，清除时间戳");
+                }
+                should_send
+            }
+        };
 
         if should_send {
             debug!(
@@ -430,7 +1263,9 @@ impl RpcWorker {
                 "更新 Discord Activity"
             );
 
-            if let Err(e) = client.set_activity(activity) {
+            let result =
+                info_span!("discord_set_activity").in_scope(|| client.set_activity(activity));
+            if let Err(e) = result {
                 warn!("设置 Discord Activity 失败: {e:?}, 尝试重连");
                 return false;
             }
@@ -445,6 +1280,39 @@ impl RpcWorker {
 
         true
     }
+
+    /// 用当前的配置为示例元数据生成 Activity，不会发送给 Discord
+    fn preview(&self, payload: Box<PreviewPresencePayload>) -> Result<serde_json::Value, String> {
+        let original = payload
+            .metadata
+            .cover
+            .as_ref()
+            .and_then(|c| c.url.as_deref());
+        // 不申请 external asset，设置界面需要能直接显示的图片地址
+        let cover_url = ActivityData::process_cover_url(original, &self.cover_config);
+        let mut data = ActivityData::from_metadata(
+            SharedMetadata(Arc::new(payload.metadata)),
+            &self.app_name_mode,
+            &self.text_format,
+            self.secondary_button,
+            cover_url,
+        );
+        data.status = payload.status;
+        data.current_time = payload.current_time;
+        data.cached_small_text = self.small_text();
+
+        let paused_text = Self::paused_text(&data, &self.text_format);
+        match Self::compose_activity(
+            &data,
+            self.pause_policy(),
+            &self.display_mode,
+            None,
+            &paused_text,
+        ) {
+            Some((activity, _)) => serde_json::to_value(activity).map_err(|e| e.to_string()),
+            None => Ok(serde_json::Value::Null),
+        }
+    }
 }
 
 impl Drop for RpcWorker {
@@ -456,6 +1324,15 @@ impl Drop for RpcWorker {
     }
 }
 
+fn open_presence_lock(cooperative: bool) -> Option<PresenceLock> {
+    if !cooperative {
+        return None;
+    }
+    PresenceLock::open()
+        .inspect_err(|e| warn!("无法打开 Discord 状态锁，不与其他插件协调: {e:?}"))
+        .ok()
+}
+
 fn background_loop(rx: &Receiver<RpcMessage>) {
     let mut worker = RpcWorker::default();
 
@@ -466,7 +1343,11 @@ fn background_loop(rx: &Receiver<RpcMessage>) {
                 worker.sync_discord();
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {
-                if worker.client.is_none() {
+                if worker.client.is_none()
+                    || worker.test_activity_until.is_some()
+                    || worker.transition_until.is_some()
+                    || worker.waiting_for_presence()
+                {
                     worker.sync_discord();
                 }
             }
@@ -476,21 +1357,32 @@ fn background_loop(rx: &Receiver<RpcMessage>) {
 }
 
 pub fn init() {
-    let (tx, rx) = mpsc::channel();
+    let (tx, rx) = sinks::worker_queue();
     if let Ok(mut guard) = SENDER.lock() {
         *guard = Some(tx);
     }
-    thread::spawn(move || {
+    let supervisor = Supervisor::new(SinkId::Discord, "discord-rpc-thread");
+    let spawn_result = supervisor.spawn(move |restarted| {
+        // 重启后的工作线程从禁用状态开始，需要恢复之前的启用状态
+        if restarted && sinks::is_enabled(SinkId::Discord) {
+            enable();
+        }
         background_loop(&rx);
     });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动 Discord RPC 线程: {e}");
+    }
 }
 
 fn send(msg: RpcMessage) {
-    if let Ok(guard) = SENDER.lock()
-        && let Some(tx) = guard.as_ref()
-        && let Err(e) = tx.send(msg)
-    {
-        warn!("向 Discord RPC 线程发送消息失败: {e}");
+    let Ok(guard) = SENDER.lock() else {
+        return;
+    };
+    match guard.as_ref().map(|tx| tx.try_send(msg)) {
+        Some(Err(TrySendError::Full(_))) => warn!("Discord RPC 线程消息队列已满，丢弃消息"),
+        Some(Err(e)) => warn!("向 Discord RPC 线程发送消息失败: {e}"),
+        Some(Ok(())) | None => {}
     }
 }
 
@@ -509,6 +1401,218 @@ pub fn update_metadata(payload: SharedMetadata) {
 pub fn update_play_state(payload: PlayStatePayload) {
     send(RpcMessage::PlayState(payload));
 }
-pub fn update_timeline(payload: TimelinePayload) {
-    send(RpcMessage::Timeline(payload));
+pub fn update_timeline(timeline: Timeline) {
+    send(RpcMessage::Timeline(timeline));
+}
+/// 让 Discord 显示一个测试用的 Activity，用于在设置界面中验证配置
+pub fn test_activity() -> Result<(), String> {
+    request(RpcMessage::Test)
+}
+
+pub fn probe() -> Result<(), String> {
+    request(RpcMessage::Probe)
+}
+
+/// 返回示例元数据对应的 Activity，暂停且配置为隐藏时为 `null`
+pub fn preview(payload: PreviewPresencePayload) -> Result<serde_json::Value, String> {
+    request(|reply| RpcMessage::Preview(Box::new(payload), reply))
+}
+
+/// 向工作线程发送需要回复的消息并等待结果
+fn request<T>(message: impl FnOnce(Sender<Result<T, String>>) -> RpcMessage) -> Result<T, String> {
+    let (tx, rx) = mpsc::channel();
+    let sender = SENDER
+        .lock()
+        .map_err(|e| format!("Discord RPC 发送端锁中毒: {e}"))?
+        .clone()
+        .ok_or("Discord RPC 线程未启动")?;
+    sender.try_send(message(tx)).map_err(|e| match e {
+        TrySendError::Full(_) => "Discord RPC 线程消息队列已满".to_string(),
+        TrySendError::Disconnected(_) => "向 Discord RPC 线程发送消息失败: 线程已退出".to_string(),
+    })?;
+
+    rx.recv_timeout(TEST_REPLY_TIMEOUT)
+        .map_err(|_| "等待 Discord RPC 线程响应超时".to_string())?
+}
+
+pub fn set_suppressed_by(app: Option<String>) {
+    send(RpcMessage::Suppress(app));
+}
+/// 多实例仲裁的结果改变后立即重新同步，其他实例发布状态时清除本实例的 Activity
+pub fn instance_role_changed() {
+    send(RpcMessage::InstanceRoleChanged);
+}
+pub fn reload_config() {
+    send(RpcMessage::ReloadConfig);
+}
+
+pub fn update_listen_together(room: Option<ListenTogetherRoom>) {
+    send(RpcMessage::ListenTogether(room));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::CoverPayload;
+
+    const NCM_COVER: &str = "https://p1.music.126.net/abc/109951.jpg";
+
+    fn metadata(ncm_id: Option<u64>, cover_url: &str) -> MetadataPayload {
+        MetadataPayload {
+            song_name: String::new(),
+            author_name: String::new(),
+            album_name: String::new(),
+            cover: Some(CoverPayload {
+                base64: None,
+                url: Some(cover_url.to_string()),
+            }),
+            ncm_id,
+            duration: None,
+            playlist: None,
+            album_id: None,
+            artist_ids: Vec::new(),
+            context: PlaybackContext::Normal,
+        }
+    }
+
+    fn thumbnail(base_url: &str) -> String {
+        format!("{base_url}?imageView&enlarge=1&type=jpeg&quality=90&thumbnail=150y150")
+    }
+
+    #[test]
+    fn missing_cover_uses_ncm_icon() {
+        let url = ActivityData::process_cover_url(None, &DiscordCoverConfig::default());
+        assert_eq!(url, NCM_ICON_ASSET_KEY);
+    }
+
+    #[test]
+    fn rewrites_https_ncm_cover() {
+        let url = ActivityData::process_cover_url(Some(NCM_COVER), &DiscordCoverConfig::default());
+        assert_eq!(url, thumbnail(NCM_COVER));
+    }
+
+    #[test]
+    fn upgrades_http_ncm_cover() {
+        let url = ActivityData::process_cover_url(
+            Some("http://p1.music.126.net/abc/109951.jpg"),
+            &DiscordCoverConfig::default(),
+        );
+        assert_eq!(url, thumbnail(NCM_COVER));
+    }
+
+    #[test]
+    fn replaces_existing_query_on_ncm_cover() {
+        let url = ActivityData::process_cover_url(
+            Some("https://p1.music.126.net/abc/109951.jpg?param=300y300"),
+            &DiscordCoverConfig::default(),
+        );
+        assert_eq!(url, thumbnail(NCM_COVER));
+    }
+
+    #[test]
+    fn keeps_other_hosts_unchanged() {
+        let config = DiscordCoverConfig::default();
+        for url in [
+            "https://example.com/cover.jpg?size=large",
+            // 只是路径中包含网易云 CDN 的域名
+            "https://example.com/p1.music.126.net/cover.jpg",
+            "https://music.126.net.example.com/cover.jpg",
+        ] {
+            assert_eq!(ActivityData::process_cover_url(Some(url), &config), url);
+        }
+    }
+
+    #[test]
+    fn upgrades_only_the_scheme_of_other_hosts() {
+        let url = ActivityData::process_cover_url(
+            Some("http://example.com/cover?src=http://example.org/a.jpg"),
+            &DiscordCoverConfig::default(),
+        );
+        assert_eq!(
+            url,
+            "https://example.com/cover?src=http://example.org/a.jpg"
+        );
+    }
+
+    #[test]
+    fn rewrite_can_be_disabled() {
+        let config = DiscordCoverConfig {
+            rewrite_url: false,
+            ..DiscordCoverConfig::default()
+        };
+        let url = "https://p1.music.126.net/abc/109951.jpg?param=300y300";
+        assert_eq!(ActivityData::process_cover_url(Some(url), &config), url);
+    }
+
+    #[test]
+    fn cache_hit_reuses_processed_url() {
+        let now = Instant::now();
+        let mut cache = CoverUrlCache::default();
+        let metadata = metadata(Some(1), NCM_COVER);
+        let first = cache.resolve_at(&metadata, &DiscordCoverConfig::default(), now);
+
+        // 命中缓存时不会按新的配置重新改写
+        let larger = DiscordCoverConfig {
+            size: 600,
+            ..DiscordCoverConfig::default()
+        };
+        let second = cache.resolve_at(&metadata, &larger, now + Duration::from_secs(1));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn cache_invalidated_when_cover_changes() {
+        let now = Instant::now();
+        let config = DiscordCoverConfig::default();
+        let mut cache = CoverUrlCache::default();
+        cache.resolve_at(&metadata(Some(1), NCM_COVER), &config, now);
+
+        let other = "https://p2.music.126.net/def/109952.jpg";
+        let url = cache.resolve_at(&metadata(Some(1), other), &config, now);
+        assert_eq!(url, thumbnail(other));
+    }
+
+    #[test]
+    fn cache_expires_after_ttl() {
+        let now = Instant::now();
+        let mut cache = CoverUrlCache::default();
+        let metadata = metadata(Some(1), NCM_COVER);
+        cache.resolve_at(&metadata, &DiscordCoverConfig::default(), now);
+
+        let larger = DiscordCoverConfig {
+            size: 600,
+            ..DiscordCoverConfig::default()
+        };
+        let url = cache.resolve_at(&metadata, &larger, now + COVER_URL_TTL);
+        assert!(url.ends_with("thumbnail=600y600"), "{url}");
+    }
+
+    #[test]
+    fn external_asset_replaces_cached_url() {
+        let now = Instant::now();
+        let mut cache = CoverUrlCache::default();
+        let metadata = metadata(Some(1), NCM_COVER);
+        let config = DiscordCoverConfig::default();
+        let original = cache.resolve_at(&metadata, &config, now);
+
+        cache.insert_external_asset(original, "mp:external/abc");
+        let url = cache.resolve_at(&metadata, &config, now);
+        assert_eq!(url, "mp:external/abc");
+    }
+
+    #[test]
+    fn songs_without_id_are_not_cached() {
+        let now = Instant::now();
+        let mut cache = CoverUrlCache::default();
+        let metadata = metadata(None, NCM_COVER);
+        cache.resolve_at(&metadata, &DiscordCoverConfig::default(), now);
+        assert!(cache.entries.is_empty());
+
+        let larger = DiscordCoverConfig {
+            size: 600,
+            ..DiscordCoverConfig::default()
+        };
+        let url = cache.resolve_at(&metadata, &larger, now);
+        assert!(url.ends_with("thumbnail=600y600"), "{url}");
+    }
 }
@@ -0,0 +1,199 @@
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::{
+        Read,
+        Write,
+    },
+    os::windows::fs::OpenOptionsExt,
+};
+
+use discord_rich_presence::{
+    DiscordIpc,
+    error::Error,
+};
+use serde::Serialize;
+use serde_json::{
+    Value,
+    json,
+};
+use tracing::debug;
+
+use crate::config::DiscordClientFlavor;
+
+/// Discord 依次尝试 `discord-ipc-0` 到 `discord-ipc-9`，第一个空闲的管道归先启动的客户端
+const PIPE_COUNT: u8 = 10;
+
+/// `GENERIC_READ | GENERIC_WRITE`
+const PIPE_ACCESS: u32 = 0x3;
+
+const OP_HANDSHAKE: u8 = 0;
+const OP_CLOSE: u8 = 2;
+
+/// 选择连接哪一个 Discord 客户端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientPreference {
+    pub flavor: DiscordClientFlavor,
+    /// 指定管道编号时不再按客户端类型选择
+    pub pipe: Option<u8>,
+}
+
+/// 实际连接到的客户端，用于诊断信息
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectedClient {
+    pub pipe: u8,
+    /// 无法从握手响应中判断时为空
+    pub flavor: Option<DiscordClientFlavor>,
+}
+
+/// `discord_rich_presence` 自带的客户端总是连接第一个可用的管道，
+/// 同时运行多个 Discord 客户端 (正式版、PTB、Canary) 时无法选择
+#[derive(Debug)]
+pub struct DiscordPipeClient {
+    client_id: String,
+    preference: ClientPreference,
+    socket: Option<File>,
+    connected: Option<ConnectedClient>,
+}
+
+impl DiscordPipeClient {
+    pub fn new(client_id: impl Into<String>, preference: ClientPreference) -> Self {
+        Self {
+            client_id: client_id.into(),
+            preference,
+            socket: None,
+            connected: None,
+        }
+    }
+
+    pub const fn connected_client(&self) -> Option<ConnectedClient> {
+        self.connected
+    }
+
+    /// 打开管道并握手，成功时返回对方的客户端类型
+    fn open(&mut self, pipe: u8) -> Result<Option<DiscordClientFlavor>, Error> {
+        let path = format!(r"\\?\pipe\discord-ipc-{pipe}");
+        let socket = OpenOptions::new()
+            .access_mode(PIPE_ACCESS)
+            .open(path)
+            .map_err(|_| Error::IPCConnectionFailed)?;
+        self.socket = Some(socket);
+
+        match self.handshake() {
+            Ok(flavor) => Ok(flavor),
+            Err(e) => {
+                self.socket = None;
+                Err(e)
+            }
+        }
+    }
+
+    fn handshake(&mut self) -> Result<Option<DiscordClientFlavor>, Error> {
+        self.send(
+            json!({
+                "v": 1,
+                "client_id": self.client_id,
+            }),
+            OP_HANDSHAKE,
+        )?;
+
+        // 握手被拒绝时 Discord 会返回关闭帧
+        let (op, response) = self.recv()?;
+        if u8::try_from(op) == Ok(OP_CLOSE) {
+            debug!(?response, "Discord 拒绝了握手");
+            return Err(Error::IPCConnectionFailed);
+        }
+        Ok(flavor_of(&response))
+    }
+
+    /// 按客户端类型选择时需要先和每个管道握手，确认类型后再重新连接
+    fn probe_pipes(&mut self) -> Vec<(u8, Option<DiscordClientFlavor>)> {
+        let mut found = Vec::new();
+        for pipe in 0..PIPE_COUNT {
+            if let Ok(flavor) = self.open(pipe) {
+                found.push((pipe, flavor));
+                let _ = self.close();
+                self.socket = None;
+            }
+        }
+        debug!(?found, "已找到的 Discord 客户端");
+        found
+    }
+
+    fn connect_to(&mut self, pipe: u8) -> Result<(), Error> {
+        let flavor = self.open(pipe)?;
+        self.connected = Some(ConnectedClient { pipe, flavor });
+        Ok(())
+    }
+}
+
+/// 根据 READY 事件中的 API 地址判断客户端类型
+fn flavor_of(ready: &Value) -> Option<DiscordClientFlavor> {
+    let endpoint = ready
+        .pointer("/data/config/api_endpoint")
+        .and_then(Value::as_str)?;
+    Some(if endpoint.contains("ptb.") {
+        DiscordClientFlavor::Ptb
+    } else if endpoint.contains("canary.") {
+        DiscordClientFlavor::Canary
+    } else {
+        DiscordClientFlavor::Stable
+    })
+}
+
+impl DiscordIpc for DiscordPipeClient {
+    fn connect(&mut self) -> Result<(), Error> {
+        self.connected = None;
+
+        if let Some(pipe) = self.preference.pipe {
+            return self.connect_to(pipe);
+        }
+
+        if self.preference.flavor == DiscordClientFlavor::Any {
+            return (0..PIPE_COUNT)
+                .find_map(|pipe| self.connect_to(pipe).ok())
+                .ok_or(Error::IPCConnectionFailed);
+        }
+
+        let found = self.probe_pipes();
+        let (pipe, _) = found
+            .iter()
+            .find(|&&(_, flavor)| flavor == Some(self.preference.flavor))
+            .or_else(|| found.first())
+            .copied()
+            .ok_or(Error::IPCConnectionFailed)?;
+        self.connect_to(pipe)
+    }
+
+    fn get_client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    fn connect_ipc(&mut self) -> Result<(), Error> {
+        self.connect()
+    }
+
+    fn send_handshake(&mut self) -> Result<(), Error> {
+        // 握手已经在 connect 中完成
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        let socket = self.socket.as_mut().ok_or(Error::NotConnected)?;
+        socket.write_all(data).map_err(Error::WriteError)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let socket = self.socket.as_mut().ok_or(Error::NotConnected)?;
+        socket.read_exact(buffer).map_err(Error::ReadError)
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        let _ = self.send(json!({}), OP_CLOSE);
+        let socket = self.socket.as_mut().ok_or(Error::NotConnected)?;
+        socket.flush().map_err(Error::FlushError)
+    }
+}
@@ -1,43 +1,132 @@
 use std::{
+    iter,
     sync::{
         Arc,
         LazyLock,
         Mutex,
+        Once,
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            Ordering,
+        },
         mpsc::{
             self,
             Receiver,
+            RecvTimeoutError,
             Sender,
         },
     },
     thread,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+use serde::Serialize;
 use tracing::{
+    debug,
+    debug_span,
     error,
+    info,
     warn,
 };
 
 use crate::{
+    audio_session,
+    clipboard,
+    clock,
+    command_worker,
+    config,
+    config_watch,
+    cover,
+    diagnostics,
     discord,
+    enrichment,
+    event_sink,
+    gsmtc,
+    http_server,
+    instance,
+    listening_session,
+    logger,
+    maintenance,
     model::{
         AppMessage,
+        CommandEnvelope,
         CommandResult,
         CommandStatus,
+        MetadataPayload,
+        PlayModePayload,
+        PlayStatePayload,
+        PlaybackStatus,
         SharedMetadata,
+        SinkId,
+    },
+    palette,
+    recorder,
+    request_id::{
+        self,
+        RequestId,
     },
+    schema,
+    self_test,
+    sinks,
     smtc_core::{
         self,
+        DisplayUpdate,
         SmtcContext,
+        SmtcEvent,
     },
+    tasks,
+    timeline::{
+        self,
+        Timeline,
+    },
+    updater,
+    watchdog,
 };
 
-static GLOBAL_SENDER: LazyLock<Mutex<Option<Sender<AppMessage>>>> =
+static GLOBAL_SENDER: LazyLock<Mutex<Option<Sender<QueuedCommand>>>> =
     LazyLock::new(|| Mutex::new(None));
 
+/// 排队等待 Dispatcher 处理的命令
+struct QueuedCommand {
+    msg: AppMessage,
+    request_id: Option<RequestId>,
+}
+
+impl From<AppMessage> for QueuedCommand {
+    fn from(msg: AppMessage) -> Self {
+        Self {
+            msg,
+            request_id: None,
+        }
+    }
+}
+
+/// V8 回调接收方只转发给当前注册的回调，本身没有状态，所以整个进程只注册一次
+static V8_SINK: Once = Once::new();
+
 pub fn init() {
     let (tx, rx) = mpsc::channel();
 
+    // 关闭是异步的，旧的 Dispatcher 线程可能在重新初始化之后才退出，
+    // 所以不在关闭时移除接收方，而是只注册一次
+    V8_SINK.call_once(|| event_sink::register(Arc::new(smtc_core::V8CallbackSink)));
+    clock::init();
     discord::init();
+    gsmtc::init();
+    audio_session::init();
+    audio_session::watch_devices();
+    http_server::init();
+    updater::init();
+    watchdog::init();
+    config_watch::init();
+    recorder::init();
+    listening_session::init();
+    maintenance::init();
+    command_worker::init();
 
     thread::Builder::new()
         .name("dispatcher-thread".into())
@@ -49,12 +138,25 @@ pub fn init() {
     if let Ok(mut guard) = GLOBAL_SENDER.lock() {
         *guard = Some(tx);
     }
+
+    // 仲裁结果通过 Dispatcher 队列应用，所以要在发送端就绪后再启动
+    instance::init();
+}
+
+/// 从后端内部向 Dispatcher 发送命令
+pub fn post(msg: AppMessage) {
+    if let Ok(guard) = GLOBAL_SENDER.lock()
+        && let Some(tx) = guard.as_ref()
+        && let Err(e) = tx.send(msg.into())
+    {
+        warn!("向 Dispatcher 发送命令失败: {e}");
+    }
 }
 
 pub fn shutdown() {
     if let Ok(guard) = GLOBAL_SENDER.lock() {
         if let Some(tx) = guard.as_ref() {
-            if let Err(e) = tx.send(AppMessage::Shutdown) {
+            if let Err(e) = tx.send(AppMessage::Shutdown.into()) {
                 error!("发送关闭信号失败: {e}");
             }
         } else {
@@ -65,11 +167,41 @@ pub fn shutdown() {
 
 struct SmtcManager {
     ctx: Option<SmtcContext>,
+    /// 当前批次中尚未提交的显示信息修改
+    display_update: Option<DisplayUpdate>,
+    /// 最近一次收到的播放模式，重新启用会话时再次应用
+    play_mode: Option<PlayModePayload>,
+    /// 其他网易云实例正在发布或插件被暂停
+    standby: bool,
+    /// 从媒体浮窗中隐藏的原因
+    hidden: Option<HideReason>,
+    /// 提交本批次的显示信息后再显示会话，避免浮窗中出现空白条目
+    reveal_after_commit: bool,
+    paused_since: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HideReason {
+    /// 启用了 `smtc.defer_until_metadata`，还没有收到元数据
+    AwaitingMetadata,
+    /// 暂停超过了 `smtc.hide_after_paused_mins`
+    Idle,
 }
 
 impl SmtcManager {
-    const fn new() -> Self {
-        Self { ctx: None }
+    fn new() -> Self {
+        Self {
+            ctx: None,
+            display_update: None,
+            play_mode: None,
+            standby: false,
+            hidden: config::get()
+                .smtc
+                .defer_until_metadata
+                .then_some(HideReason::AwaitingMetadata),
+            reveal_after_commit: false,
+            paused_since: None,
+        }
     }
 
     fn get_or_init(&mut self) -> Option<&mut SmtcContext> {
@@ -86,114 +218,845 @@ impl SmtcManager {
         self.ctx.as_mut()
     }
 
+    fn display_update(&mut self) -> Option<(&SmtcContext, &DisplayUpdate)> {
+        self.get_or_init()?;
+        let ctx = self.ctx.as_ref()?;
+
+        if self.display_update.is_none() {
+            match smtc_core::begin_update(ctx) {
+                Ok(update) => self.display_update = Some(update),
+                Err(e) => {
+                    error!("获取 SMTC 显示信息更新器失败: {e:?}");
+                    return None;
+                }
+            }
+        }
+
+        Some((ctx, self.display_update.as_ref()?))
+    }
+
+    fn commit_display_update(&mut self) {
+        if let Some(update) = self.display_update.take()
+            && let Err(e) = update.commit()
+        {
+            error!("提交 SMTC 显示信息失败: {e:?}");
+        }
+        if std::mem::take(&mut self.reveal_after_commit) {
+            self.set_hidden(None);
+        }
+    }
+
+    fn note_metadata(&mut self, metadata: &MetadataPayload) {
+        if metadata.song_name.trim().is_empty() {
+            return;
+        }
+        self.reveal_after_commit = true;
+    }
+
+    fn note_play_state(&mut self, status: PlaybackStatus) {
+        match status {
+            PlaybackStatus::Playing => {
+                self.paused_since = None;
+                if self.hidden == Some(HideReason::Idle) {
+                    self.set_hidden(None);
+                }
+            }
+            PlaybackStatus::Paused => {
+                self.paused_since.get_or_insert_with(Instant::now);
+            }
+        }
+    }
+
+    /// 暂停太久后需要隐藏会话的时刻
+    fn idle_deadline(&self) -> Option<Instant> {
+        let minutes = config::get().smtc.hide_after_paused_mins;
+        if minutes == 0 || self.hidden.is_some() {
+            return None;
+        }
+        self.paused_since
+            .map(|since| since + Duration::from_mins(minutes))
+    }
+
+    fn hide_if_idle(&mut self) {
+        if self
+            .idle_deadline()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            info!("暂停时间过长，从媒体浮窗中隐藏 SMTC");
+            self.paused_since = None;
+            self.set_hidden(Some(HideReason::Idle));
+        }
+    }
+
+    fn set_hidden(&mut self, hidden: Option<HideReason>) {
+        if self.hidden != hidden {
+            self.hidden = hidden;
+            debug!(?hidden, "SMTC 隐藏状态已改变");
+            self.refresh_visibility();
+        }
+    }
+
+    fn update_play_mode(&mut self, payload: PlayModePayload) {
+        self.play_mode = Some(payload);
+        self.apply_play_mode();
+    }
+
+    fn apply_play_mode(&mut self) {
+        let Some(payload) = self.play_mode.clone() else {
+            return;
+        };
+        if let Some(ctx) = self.get_or_init()
+            && let Err(e) =
+                smtc_core::update_play_mode(ctx, payload.is_shuffling, &payload.repeat_mode)
+        {
+            error!("更新 SMTC 播放模式失败: {e:?}");
+        }
+    }
+
+    fn rebind_handlers_if_poisoned(&mut self) {
+        if let Some(ctx) = self.ctx.as_mut()
+            && let Err(e) = smtc_core::rebind_handlers_if_poisoned(ctx)
+        {
+            error!("重新绑定 SMTC 事件处理器失败: {e:?}");
+        }
+    }
+
+    fn set_standby(&mut self, standby: bool) {
+        self.standby = standby;
+        self.refresh_visibility();
+    }
+
+    fn refresh_visibility(&mut self) {
+        let hide = self.standby || !instance::is_active() || self.hidden.is_some();
+        if let Some(ctx) = self.get_or_init()
+            && let Err(e) = smtc_core::set_standby(ctx, hide)
+        {
+            error!("切换 SMTC 待机状态失败: {e:?}");
+        }
+    }
+
     fn shutdown(&mut self) {
+        self.display_update = None;
         if let Some(mut ctx) = self.ctx.take() {
             let _ = smtc_core::set_enabled(&mut ctx, false);
+        } else {
+            smtc_core::close_session();
         }
     }
 }
 
-fn run_dispatcher_loop(rx: &Receiver<AppMessage>) {
-    let mut smtc_manager = SmtcManager::new();
+/// 同一首歌在这个时间内重复收到元数据时不视为新的播放
+const NEW_PLAY_MIN_INTERVAL: Duration = Duration::from_secs(30);
 
-    while let Ok(msg) = rx.recv() {
-        match msg {
-            AppMessage::UpdateMetadata(payload) => {
-                let shared_meta = SharedMetadata(Arc::new(payload));
+/// 检测到的播放次数，不包括前端重连等原因重复发送的元数据
+static NEW_PLAY_COUNT: AtomicU64 = AtomicU64::new(0);
 
-                discord::update_metadata(shared_meta.clone());
+pub fn new_play_count() -> u64 {
+    NEW_PLAY_COUNT.load(Ordering::Relaxed)
+}
 
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) = smtc_core::update_metadata(ctx, &shared_meta)
-                {
-                    error!("更新 SMTC 元数据失败: {e:?}");
-                }
+/// 因为被同一批中更新的时间线取代而丢弃的时间线更新数量
+static SUPERSEDED_TIMELINE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+pub fn superseded_timeline_count() -> u64 {
+    SUPERSEDED_TIMELINE_COUNT.load(Ordering::Relaxed)
+}
+
+/// 队列积压时 (例如 `WinRT` 调用较慢) 时间线更新会大量堆积，
+/// 只保留最后一次并放在其他命令之后处理，让播放状态和元数据尽快生效
+///
+/// 相对跳转需要基于它之前的最新进度计算，所以遇到 `SeekRelative` 时先处理在它之前收到的时间线
+fn prioritize_batch(batch: Vec<QueuedCommand>) -> Vec<QueuedCommand> {
+    let last_metadata = batch
+        .iter()
+        .rposition(|cmd| matches!(cmd.msg, AppMessage::UpdateMetadata(_)));
+
+    let mut commands = Vec::with_capacity(batch.len());
+    let mut timeline = None;
+    let mut superseded = 0;
+
+    for (index, cmd) in batch.into_iter().enumerate() {
+        if matches!(cmd.msg, AppMessage::SeekRelative(_)) {
+            commands.extend(timeline.take());
+        }
+        if !matches!(cmd.msg, AppMessage::UpdateTimeline(_)) {
+            commands.push(cmd);
+            continue;
+        }
+
+        // 新元数据之前的时间线属于上一首歌曲
+        if last_metadata.is_some_and(|last| index < last) || timeline.replace(cmd).is_some() {
+            superseded += 1;
+        }
+    }
+
+    if superseded > 0 {
+        debug!(superseded, "丢弃了被取代的时间线更新");
+        SUPERSEDED_TIMELINE_COUNT.fetch_add(superseded, Ordering::Relaxed);
+    }
+
+    commands.extend(timeline);
+    commands
+}
+
+/// 判断收到的元数据是否代表一次新的播放
+///
+/// 前端反复重载时会重新发送当前歌曲的元数据，只有通知类的下游 (例如提示和记录播放历史)
+/// 需要据此去重，Discord 和 SMTC 这类状态同步的下游仍然需要每次都更新
+#[derive(Default)]
+struct NewPlayDetector {
+    last_play: Option<(PlayKey, Instant)>,
+    /// 最近一次收到的播放进度 (毫秒)
+    last_position_ms: Option<f64>,
+    /// 同一首歌隔了很久再次收到元数据，要等到第一次时间线才能判断是否是继续播放
+    pending_resume: Option<PendingResume>,
+}
+
+struct PendingResume {
+    /// 暂停前的进度 (毫秒)
+    position_ms: f64,
+    song: String,
+    ncm_id: Option<u64>,
+}
+
+#[derive(PartialEq, Eq)]
+enum PlayKey {
+    NcmId(u64),
+    /// 本地歌曲等没有 ID 的情况
+    Title {
+        song: String,
+        artist: String,
+    },
+}
+
+enum PlayCheck {
+    New,
+    Duplicate,
+    /// 可能是暂停很久之后继续播放，等待时间线确认
+    Pending,
+}
+
+/// 暂停前的进度超过这个值才可能是继续播放，刚开头的歌曲重新播放也没有区别
+const RESUME_MIN_POSITION_MS: f64 = 5_000.0;
+
+/// 继续播放时第一次时间线的进度与暂停前进度的最大差值
+const RESUME_POSITION_TOLERANCE_MS: f64 = 5_000.0;
+
+/// 被识别为暂停后继续播放而没有计为新播放的次数
+static RESUMED_PLAY_COUNT: AtomicU64 = AtomicU64::new(0);
+
+pub fn resumed_play_count() -> u64 {
+    RESUMED_PLAY_COUNT.load(Ordering::Relaxed)
+}
+
+impl NewPlayDetector {
+    fn check(&mut self, metadata: &MetadataPayload) -> PlayCheck {
+        let key = match metadata.ncm_id {
+            Some(id) if id > 0 => PlayKey::NcmId(id),
+            _ => PlayKey::Title {
+                song: metadata.song_name.clone(),
+                artist: metadata.author_name.clone(),
+            },
+        };
+
+        let same_song = self
+            .last_play
+            .as_ref()
+            .is_some_and(|(last_key, _)| *last_key == key);
+        let recent = self
+            .last_play
+            .as_ref()
+            .is_some_and(|(_, at)| at.elapsed() < NEW_PLAY_MIN_INTERVAL);
+
+        if same_song && recent {
+            return PlayCheck::Duplicate;
+        }
+
+        let last_position_ms = self.last_position_ms.take();
+        self.last_play = Some((key, Instant::now()));
+        self.pending_resume = None;
+
+        match last_position_ms {
+            Some(position_ms) if same_song && position_ms >= RESUME_MIN_POSITION_MS => {
+                self.pending_resume = Some(PendingResume {
+                    position_ms,
+                    song: metadata.song_name.clone(),
+                    ncm_id: metadata.ncm_id,
+                });
+                PlayCheck::Pending
             }
-            AppMessage::UpdatePlayState(payload) => {
-                discord::update_play_state(payload.clone());
+            _ => PlayCheck::New,
+        }
+    }
 
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) = smtc_core::update_play_state(ctx, payload.status)
-                {
-                    error!("更新 SMTC 播放状态失败: {e:?}");
-                }
+    /// 记录播放进度，进度与暂停前不连续时说明待确认的是一次新的播放
+    fn observe_timeline(&mut self, position_ms: f64) {
+        self.last_position_ms = Some(position_ms);
+        let Some(pending) = self.pending_resume.take() else {
+            return;
+        };
+
+        if (position_ms - pending.position_ms).abs() > RESUME_POSITION_TOLERANCE_MS {
+            record_new_play(&pending.song, pending.ncm_id);
+        } else {
+            RESUMED_PLAY_COUNT.fetch_add(1, Ordering::Relaxed);
+            info!(
+                song = pending.song,
+                position_ms, "继续播放暂停前的歌曲，不计为新的播放"
+            );
+        }
+    }
+}
+
+/// 前端报告的时长与时间线总长相差超过这个值时才修正 (毫秒)
+const DURATION_MISMATCH_TOLERANCE_MS: f64 = 2_000.0;
+
+/// 修正前端报告的错误时长
+///
+/// 试听片段和 VIP 预览的元数据中是完整歌曲的时长，实际能播放的只有时间线中的总长。
+/// 切歌后的第一次时间线可能还是上一首歌的总长，所以连续两次得到相同的总长才修正
+#[derive(Default)]
+struct DurationReconciler {
+    /// 与元数据不一致、等待下一次时间线确认的总长
+    candidate_ms: Option<f64>,
+    /// 当前歌曲已经修正过，直到下一次元数据都不再检查
+    settled: bool,
+}
+
+impl DurationReconciler {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 返回确认后的实际时长
+    fn observe(&mut self, reported_ms: f64, total_ms: f64) -> Option<f64> {
+        if self.settled
+            || total_ms <= 0.0
+            || (reported_ms - total_ms).abs() <= DURATION_MISMATCH_TOLERANCE_MS
+        {
+            self.candidate_ms = None;
+            return None;
+        }
+
+        match self.candidate_ms.replace(total_ms) {
+            Some(candidate) if (candidate - total_ms).abs() < 1.0 => {
+                self.settled = true;
+                Some(total_ms)
             }
-            AppMessage::UpdateTimeline(payload) => {
-                discord::update_timeline(payload.clone());
+            _ => None,
+        }
+    }
+}
 
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) =
-                        smtc_core::update_timeline(ctx, payload.current_time, payload.total_time)
-                {
-                    error!("更新 SMTC 时间线失败: {e:?}");
-                }
+/// 时间线总长与元数据中的时长不一致时，以时间线为准更新各个输出端
+fn reconcile_duration(total_ms: f64, reconciler: &mut DurationReconciler) {
+    let Some(metadata) = cover::current_metadata() else {
+        return;
+    };
+    let Some(reported_ms) = metadata.duration.filter(|&duration| duration > 0.0) else {
+        return;
+    };
+    let Some(actual_ms) = reconciler.observe(reported_ms, total_ms) else {
+        return;
+    };
+
+    info!(
+        song = %metadata.song_name,
+        reported_ms, actual_ms, "歌曲时长与时间线不一致，以时间线为准"
+    );
+    let corrected = SharedMetadata(Arc::new(MetadataPayload {
+        duration: Some(actual_ms),
+        ..MetadataPayload::clone(&metadata)
+    }));
+    cover::set_current(corrected.clone());
+    discord::update_metadata(corrected.clone());
+    http_server::update_metadata(corrected.clone());
+
+    smtc_core::dispatch_event(&SmtcEvent::DurationCorrected {
+        ncm_id: corrected.ncm_id,
+        reported_ms,
+        actual_ms,
+    });
+}
+
+fn record_new_play(song: &str, ncm_id: Option<u64>) {
+    NEW_PLAY_COUNT.fetch_add(1, Ordering::Relaxed);
+    listening_session::note_new_play();
+    info!(song, ?ncm_id, "开始播放新的歌曲");
+}
+
+fn handle_update_metadata(
+    payload: MetadataPayload,
+    smtc_manager: &mut SmtcManager,
+    new_play_detector: &mut NewPlayDetector,
+    duration_reconciler: &mut DurationReconciler,
+) {
+    duration_reconciler.reset();
+    match new_play_detector.check(&payload) {
+        PlayCheck::New => record_new_play(&payload.song_name, payload.ncm_id),
+        PlayCheck::Duplicate => debug!(song = %payload.song_name, "重复收到当前歌曲的元数据"),
+        PlayCheck::Pending => {
+            debug!(song = %payload.song_name, "再次收到暂停前歌曲的元数据，等待进度确认是否继续播放");
+        }
+    }
+
+    enrichment::spawn(&payload);
+    smtc_manager.note_metadata(&payload);
+    let shared_meta = SharedMetadata(Arc::new(payload));
+
+    timeline::reset();
+    cover::set_current(shared_meta.clone());
+    if !is_quiesced() {
+        palette::extract(shared_meta.clone());
+    }
+    discord::update_metadata(shared_meta.clone());
+    http_server::update_metadata(shared_meta.clone());
+
+    if let Some(ctx) = smtc_manager.get_or_init()
+        && let Err(e) = smtc_core::set_playback_context(ctx, shared_meta.context)
+    {
+        error!("更新 SMTC 播放来源失败: {e:?}");
+    }
+
+    if let Some((ctx, update)) = smtc_manager.display_update()
+        && let Err(e) = smtc_core::update_metadata(ctx, update, &shared_meta)
+    {
+        error!("更新 SMTC 元数据失败: {e:?}");
+    }
+}
+
+/// 后台补全的元数据只在仍是当前歌曲时应用，不重新判断新的播放，也不重置进度
+fn handle_enriched_metadata(payload: MetadataPayload, smtc_manager: &mut SmtcManager) {
+    let is_current = cover::current_metadata().is_some_and(|current| {
+        current.ncm_id == payload.ncm_id && current.song_name == payload.song_name
+    });
+    if !is_current {
+        debug!(ncm_id = ?payload.ncm_id, "歌曲已切换，丢弃补全的元数据");
+        return;
+    }
+
+    let shared_meta = SharedMetadata(Arc::new(payload));
+    cover::set_current(shared_meta.clone());
+    if !is_quiesced() {
+        palette::extract(shared_meta.clone());
+    }
+    discord::update_metadata(shared_meta.clone());
+    http_server::update_metadata(shared_meta.clone());
+
+    if let Some((ctx, update)) = smtc_manager.display_update()
+        && let Err(e) = smtc_core::update_metadata(ctx, update, &shared_meta)
+    {
+        error!("更新 SMTC 元数据失败: {e:?}");
+    }
+}
+
+fn set_sink_enabled(sink: SinkId, enabled: bool, smtc_manager: &mut SmtcManager) {
+    sinks::set_enabled(sink, enabled);
+    if is_quiesced() {
+        debug!(?sink, enabled, "插件已暂停，输出端状态将在恢复后生效");
+        return;
+    }
+    apply_sink_enabled(sink, enabled, smtc_manager);
+}
+
+fn apply_sink_enabled(sink: SinkId, enabled: bool, smtc_manager: &mut SmtcManager) {
+    match sink {
+        SinkId::Smtc => {
+            if let Some(ctx) = smtc_manager.get_or_init()
+                && let Err(e) = smtc_core::set_enabled(ctx, enabled)
+            {
+                let action = if enabled { "启用" } else { "禁用" };
+                error!("{action} SMTC 失败: {e:?}");
             }
-            AppMessage::UpdatePlayMode(payload) => {
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) =
-                        smtc_core::update_play_mode(ctx, payload.is_shuffling, &payload.repeat_mode)
-                {
-                    error!("更新 SMTC 播放模式失败: {e:?}");
+            // 重新打开的会话不一定保留之前的随机和循环状态
+            if enabled {
+                if smtc_manager.hidden.is_some() {
+                    smtc_manager.refresh_visibility();
                 }
+                smtc_manager.apply_play_mode();
             }
-            AppMessage::EnableSmtc => {
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) = smtc_core::set_enabled(ctx, true)
-                {
-                    error!("启用 SMTC 失败: {e:?}");
+        }
+        SinkId::Discord if enabled => discord::enable(),
+        SinkId::Discord => discord::disable(),
+    }
+}
+
+/// 插件是否被 `Quiesce` 命令暂停
+static QUIESCED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_quiesced() -> bool {
+    QUIESCED.load(Ordering::Relaxed)
+}
+
+/// 上下文检查线程负责在页面跳转后清除失效的回调，命令工作线程执行自检等排查用的命令，
+/// 暂停期间都要保持运行
+const QUIESCE_KEEP_TASKS: &[&str] = &["context-watch-thread", "command-worker-thread"];
+
+/// 停止所有输出端、后台任务、HTTP 服务和系统通知，但不卸载插件，用于排查网易云的性能问题
+fn quiesce(smtc_manager: &mut SmtcManager) {
+    if QUIESCED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    info!("正在暂停插件的所有活动");
+
+    // SMTC 只是隐藏而不关闭会话，这样恢复后事件回调仍然有效
+    smtc_manager.set_standby(true);
+    discord::disable();
+    instance::release();
+    http_server::shutdown();
+    recorder::shutdown();
+    tasks::cancel_except(QUIESCE_KEEP_TASKS);
+    clock::shutdown();
+    audio_session::unwatch_devices();
+
+    smtc_core::dispatch_event(&SmtcEvent::QuiesceStateChanged { quiesced: true });
+    info!("插件已暂停");
+}
+
+/// 依次关闭所有子系统，之后 Dispatcher 线程退出
+fn shutdown_all(smtc_manager: &mut SmtcManager) {
+    listening_session::finish();
+    instance::release();
+    discord::disable();
+    smtc_core::dispatch_event(&SmtcEvent::DiscordStatus { connected: false });
+    http_server::shutdown();
+    tasks::shutdown();
+    clock::shutdown();
+    audio_session::unwatch_devices();
+    smtc_manager.shutdown();
+}
+
+fn resume(smtc_manager: &mut SmtcManager) {
+    if !QUIESCED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    info!("正在恢复插件");
+
+    clock::init();
+    gsmtc::init();
+    audio_session::init();
+    audio_session::watch_devices();
+    http_server::init();
+    updater::init();
+    watchdog::init();
+    config_watch::init();
+    recorder::init();
+    listening_session::init();
+    maintenance::init();
+    instance::init();
+
+    for sink in SinkId::ALL {
+        apply_sink_enabled(sink, sinks::is_enabled(sink), smtc_manager);
+    }
+    smtc_manager.set_standby(!instance::is_active());
+
+    smtc_core::dispatch_event(&SmtcEvent::QuiesceStateChanged { quiesced: false });
+    info!("插件已恢复");
+}
+
+fn handle_update_play_state(payload: &PlayStatePayload, smtc_manager: &mut SmtcManager) {
+    if payload.status == PlaybackStatus::Playing {
+        instance::note_playing();
+    }
+    timeline::set_status(payload.status);
+    smtc_manager.note_play_state(payload.status);
+    listening_session::update_play_state(payload.status);
+    discord::update_play_state(payload.clone());
+    audio_session::update_reported_status(payload.status);
+    http_server::update_play_state(payload.status);
+
+    if let Some(ctx) = smtc_manager.get_or_init()
+        && let Err(e) = smtc_core::update_play_state(ctx, payload.status)
+    {
+        error!("更新 SMTC 播放状态失败: {e:?}");
+    }
+}
+
+fn handle_update_timeline(
+    timeline: Timeline,
+    smtc_manager: &mut SmtcManager,
+    new_play_detector: &mut NewPlayDetector,
+    duration_reconciler: &mut DurationReconciler,
+) {
+    new_play_detector.observe_timeline(timeline.position_ms());
+    // 需要在转发时间线之前完成，重新发送元数据会把 Discord 和 HTTP 服务器的进度归零
+    reconcile_duration(timeline.duration_ms(), duration_reconciler);
+
+    timeline::update(timeline);
+    discord::update_timeline(timeline);
+    http_server::update_timeline(timeline);
+
+    if let Some(ctx) = smtc_manager.get_or_init()
+        && let Err(e) = smtc_core::update_timeline(ctx, timeline)
+    {
+        error!("更新 SMTC 时间线失败: {e:?}");
+    }
+}
+
+/// 等待下一条命令，期间到了隐藏 SMTC 的时刻就先隐藏
+fn next_command(
+    rx: &Receiver<QueuedCommand>,
+    smtc_manager: &mut SmtcManager,
+) -> Option<QueuedCommand> {
+    loop {
+        let Some(deadline) = smtc_manager.idle_deadline() else {
+            return rx.recv().ok();
+        };
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(command) => return Some(command),
+            Err(RecvTimeoutError::Timeout) => smtc_manager.hide_if_idle(),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+fn run_dispatcher_loop(rx: &Receiver<QueuedCommand>) {
+    let mut smtc_manager = SmtcManager::new();
+    let mut new_play_detector = NewPlayDetector::default();
+    let mut duration_reconciler = DurationReconciler::default();
+
+    'dispatch: while let Some(first) = next_command(rx, &mut smtc_manager) {
+        // 已经排队的命令作为一批处理，同一批中对 SMTC 显示信息的修改只提交一次
+        let batch = iter::once(first).chain(rx.try_iter()).collect();
+        for QueuedCommand { msg, request_id } in prioritize_batch(batch) {
+            let _span =
+                debug_span!("dispatch_command", request_id = request_id.as_deref()).entered();
+            let _request_id = request_id::enter(request_id);
+
+            match msg {
+                AppMessage::UpdateMetadata(payload) => {
+                    handle_update_metadata(
+                        payload,
+                        &mut smtc_manager,
+                        &mut new_play_detector,
+                        &mut duration_reconciler,
+                    );
                 }
-            }
-            AppMessage::DisableSmtc => {
-                if let Some(ctx) = smtc_manager.get_or_init()
-                    && let Err(e) = smtc_core::set_enabled(ctx, false)
-                {
-                    error!("禁用 SMTC 失败: {e:?}");
+                AppMessage::UpdatePlayState(payload) => {
+                    handle_update_play_state(&payload, &mut smtc_manager);
+                }
+                AppMessage::UpdateTimeline(timeline) => {
+                    handle_update_timeline(
+                        timeline,
+                        &mut smtc_manager,
+                        &mut new_play_detector,
+                        &mut duration_reconciler,
+                    );
+                }
+                AppMessage::UpdatePlayMode(payload) => smtc_manager.update_play_mode(payload),
+                AppMessage::PrefetchCover(_) if is_quiesced() => {}
+                AppMessage::PrefetchCover(payload) => smtc_core::prefetch_cover(payload),
+                AppMessage::SetControls(payload) => {
+                    if let Some(ctx) = smtc_manager.get_or_init()
+                        && let Err(e) = smtc_core::set_controls(ctx, payload)
+                    {
+                        error!("更新 SMTC 控制按钮失败: {e:?}");
+                    }
+                }
+                AppMessage::TogglePlayPause => {
+                    smtc_core::dispatch_event(&SmtcEvent::TogglePlayPause);
+                }
+                AppMessage::VolumeUp => smtc_core::dispatch_event(&SmtcEvent::VolumeUp),
+                AppMessage::VolumeDown => smtc_core::dispatch_event(&SmtcEvent::VolumeDown),
+                AppMessage::Mute => smtc_core::dispatch_event(&SmtcEvent::ToggleMute),
+                AppMessage::SeekRelative(payload) => {
+                    if let Some(position_ms) = timeline::seek_relative(payload.delta_ms) {
+                        smtc_core::dispatch_event(&SmtcEvent::Seek { position_ms });
+                    } else {
+                        debug!("还没有收到时间线，忽略相对跳转");
+                    }
+                }
+                AppMessage::EnableSink(payload) => {
+                    set_sink_enabled(payload.sink, true, &mut smtc_manager);
+                }
+                AppMessage::DisableSink(payload) => {
+                    set_sink_enabled(payload.sink, false, &mut smtc_manager);
+                }
+                AppMessage::DiscordConfig(cfg) => discord::update_config(cfg),
+                AppMessage::UpdateListenTogether(payload) => {
+                    discord::update_listen_together(payload.room);
+                }
+                AppMessage::GetRecentLogs(_)
+                | AppMessage::GetLogPath
+                | AppMessage::OpenLogFolder
+                | AppMessage::GetDiagnostics
+                | AppMessage::GetSchema
+                | AppMessage::TestSink(_)
+                | AppMessage::ListSinks
+                | AppMessage::SelfTest
+                | AppMessage::GetCurrentCover(_)
+                | AppMessage::CopyShareText(_)
+                | AppMessage::PreviewPresence(_)
+                | AppMessage::SetLogRetention(_)
+                | AppMessage::AckEvent(_)
+                | AppMessage::ApplyUpdate => {
+                    warn!("同步命令应在 send_command 中直接处理，不应进入 Dispatcher 队列");
+                }
+                AppMessage::EnrichedMetadata(payload) => {
+                    handle_enriched_metadata(payload, &mut smtc_manager);
+                }
+                AppMessage::InstanceStandby(standby) => {
+                    discord::instance_role_changed();
+                    smtc_manager.set_standby(standby);
+                }
+                AppMessage::Quiesce => quiesce(&mut smtc_manager),
+                AppMessage::Resume => resume(&mut smtc_manager),
+                AppMessage::Shutdown => {
+                    shutdown_all(&mut smtc_manager);
+                    break 'dispatch;
                 }
-            }
-            AppMessage::EnableDiscord => discord::enable(),
-            AppMessage::DisableDiscord => discord::disable(),
-            AppMessage::DiscordConfig(cfg) => discord::update_config(cfg),
-            AppMessage::Shutdown => {
-                discord::disable();
-                smtc_manager.shutdown();
-                break;
             }
         }
+
+        smtc_manager.commit_display_update();
+        smtc_manager.rebind_handlers_if_poisoned();
     }
 }
 
 pub fn send_command(json: &str) -> String {
+    recorder::record(json);
+
+    let request_id: Option<RequestId> = serde_json::from_str::<CommandEnvelope>(json)
+        .unwrap_or_default()
+        .request_id
+        .map(Into::into);
+    let _span = debug_span!("command", request_id = request_id.as_deref()).entered();
+    let _request_id = request_id::enter(request_id.clone());
+
     let command: AppMessage = match serde_json::from_str(json) {
         Ok(cmd) => cmd,
         Err(e) => {
-            return serde_json::to_string(&CommandResult {
-                status: CommandStatus::Error,
-                message: Some(format!("JSON 解析失败: {e}")),
-            })
-            .expect("序列化错误响应时出错");
+            return error_result(format!("JSON 解析失败: {e}"));
         }
     };
 
+    if is_async_command(&command) {
+        let Some(request_id) = request_id else {
+            return error_result("该命令需要 request_id 来接收结果".into());
+        };
+        return command_worker::submit(command, request_id)
+            .map_or_else(error_result, |()| success_result());
+    }
+
+    if let Some(result) = handle_sync_command(&command) {
+        return result;
+    }
+
     if let Ok(guard) = GLOBAL_SENDER.lock()
         && let Some(tx) = guard.as_ref()
     {
-        if let Err(e) = tx.send(command) {
+        let queued = QueuedCommand {
+            msg: command,
+            request_id,
+        };
+        if let Err(e) = tx.send(queued) {
             return error_result(format!("发送消息到 Actor 失败: {e}"));
         }
-        return serde_json::to_string(&CommandResult {
+        return success_result();
+    }
+
+    error_result("Dispatcher 未初始化".into())
+}
+
+/// 处理不需要经过 Dispatcher 线程的命令
+///
+/// 这类命令只读取全局状态或执行很快的操作，直接在调用线程上同步返回结果
+fn handle_sync_command(command: &AppMessage) -> Option<String> {
+    let result = match command {
+        AppMessage::GetRecentLogs(payload) => {
+            logger::recent_logs(payload.level.as_deref(), payload.limit)
+                .map_or_else(error_result, |logs| data_result(&logs))
+        }
+        AppMessage::GetLogPath => {
+            logger::log_location().map_or_else(error_result, |location| data_result(&location))
+        }
+        AppMessage::OpenLogFolder => {
+            logger::open_log_folder().map_or_else(error_result, |()| success_result())
+        }
+        AppMessage::GetDiagnostics => data_result(&diagnostics::collect()),
+        AppMessage::GetSchema => data_result(&schema::collect()),
+        AppMessage::ListSinks => data_result(&sinks::list()),
+        AppMessage::CopyShareText(payload) => {
+            clipboard::copy_share_text(payload).map_or_else(error_result, |text| data_result(&text))
+        }
+        AppMessage::SetLogRetention(payload) => {
+            logger::set_log_retention(payload).map_or_else(error_result, |()| success_result())
+        }
+        AppMessage::AckEvent(payload) => {
+            smtc_core::ack_event(payload.seq);
+            success_result()
+        }
+        AppMessage::ApplyUpdate => {
+            updater::apply_update().map_or_else(error_result, |()| success_result())
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// 执行时会等待其他线程或 `WinRT` 操作的命令，在渲染线程上同步执行会卡住界面
+///
+/// 这类命令交给 [`command_worker`] 执行，`send_command` 立即返回，结果通过 `CommandCompleted`
+/// 事件发送
+const fn is_async_command(command: &AppMessage) -> bool {
+    matches!(
+        command,
+        AppMessage::SelfTest
+            | AppMessage::TestSink(_)
+            | AppMessage::PreviewPresence(_)
+            | AppMessage::GetCurrentCover(_)
+    )
+}
+
+/// 在命令工作线程上执行 [`is_async_command`] 中的命令
+pub fn run_async_command(command: &AppMessage) -> Result<serde_json::Value, String> {
+    match command {
+        AppMessage::SelfTest => to_value(&self_test::run()),
+        AppMessage::TestSink(payload) => match payload.sink {
+            SinkId::Smtc => Err("SMTC 不支持测试".to_string()),
+            SinkId::Discord => discord::test_activity().map(|()| serde_json::Value::Null),
+        },
+        AppMessage::PreviewPresence(payload) => discord::preview(payload.clone()),
+        AppMessage::GetCurrentCover(payload) => {
+            cover::current_cover(*payload).and_then(|cover| to_value(&cover))
+        }
+        _ => Err("该命令不能异步执行".into()),
+    }
+}
+
+fn to_value<T: Serialize>(data: &T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(data).map_err(|e| format!("序列化返回数据失败: {e}"))
+}
+
+fn success_result() -> String {
+    serde_json::to_string(&CommandResult {
+        request_id: request_id::current().as_deref().map(str::to_owned),
+        status: CommandStatus::Success,
+        message: None,
+        data: None,
+    })
+    .expect("序列化成功响应时出错")
+}
+
+fn data_result<T: Serialize>(data: &T) -> String {
+    match serde_json::to_value(data) {
+        Ok(value) => serde_json::to_string(&CommandResult {
+            request_id: request_id::current().as_deref().map(str::to_owned),
             status: CommandStatus::Success,
             message: None,
+            data: Some(value),
         })
-        .expect("序列化成功响应时出错");
+        .expect("序列化成功响应时出错"),
+        Err(e) => error_result(format!("序列化返回数据失败: {e}")),
     }
-
-    error_result("Dispatcher 未初始化".into())
 }
 
 fn error_result(msg: String) -> String {
     serde_json::to_string(&CommandResult {
+        request_id: request_id::current().as_deref().map(str::to_owned),
         status: CommandStatus::Error,
         message: Some(msg),
+        data: None,
     })
     .expect("序列化错误结果时出错")
 }
@@ -0,0 +1,230 @@
+use std::{
+    sync::{
+        LazyLock,
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use serde::Deserialize;
+use tracing::{
+    debug,
+    warn,
+};
+use ureq::{
+    Agent,
+    tls::{
+        TlsConfig,
+        TlsProvider,
+    },
+};
+
+use crate::{
+    config::{
+        self,
+        EnrichmentConfig,
+        ResolverConfig,
+    },
+    dispatcher,
+    model::{
+        AppMessage,
+        CoverPayload,
+        MetadataPayload,
+    },
+    tasks,
+};
+
+const SONG_DETAIL_URL: &str = "https://music.163.com/api/song/detail/";
+const USER_AGENT: &str = concat!("InfLink-rs/", env!("CARGO_PKG_VERSION"));
+
+/// 补全元数据中缺失字段的解析器
+///
+/// 解析器在后台线程中运行，补全的结果在之后作为 `EnrichedMetadata` 交给 Dispatcher
+trait Resolver: Sync {
+    fn name(&self) -> &'static str;
+
+    fn config<'a>(&self, config: &'a EnrichmentConfig) -> &'a ResolverConfig;
+
+    /// 需要补全的字段已经有值时返回 `false`
+    fn needed(&self, metadata: &MetadataPayload) -> bool;
+
+    fn resolve(&self, metadata: &mut MetadataPayload, timeout: Duration) -> Result<()>;
+}
+
+static RESOLVERS: &[&dyn Resolver] = &[&CoverResolver, &DurationResolver];
+
+/// 有已启用的解析器需要补全时，在后台线程中补全元数据
+///
+/// 查询网络期间不阻塞 Dispatcher，各个输出端先显示原始的元数据，
+/// 有字段被补全后再通过 `EnrichedMetadata` 更新
+pub fn spawn(metadata: &MetadataPayload) {
+    let config = config::get().enrichment;
+    let needed = RESOLVERS
+        .iter()
+        .any(|resolver| resolver.config(&config).enabled && resolver.needed(metadata));
+    if !needed {
+        return;
+    }
+
+    let mut metadata = metadata.clone();
+    let spawn_result = tasks::spawn("enrichment-thread", move |token| {
+        if enrich(&mut metadata, &config) && !token.is_cancelled() {
+            dispatcher::post(AppMessage::EnrichedMetadata(metadata));
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动元数据补全线程: {e}");
+    }
+}
+
+/// 依次运行已启用的解析器，失败时只记录日志，返回是否有字段被补全
+fn enrich(metadata: &mut MetadataPayload, config: &EnrichmentConfig) -> bool {
+    let mut enriched = false;
+    for resolver in RESOLVERS {
+        let resolver_config = resolver.config(config);
+        if !resolver_config.enabled || !resolver.needed(metadata) {
+            continue;
+        }
+
+        let timeout = Duration::from_millis(resolver_config.timeout_ms.max(1));
+        let name = resolver.name();
+        match resolver.resolve(metadata, timeout) {
+            Ok(()) => {
+                debug!(resolver = name, ncm_id = ?metadata.ncm_id, "已补全元数据");
+                enriched = true;
+            }
+            Err(e) => warn!(resolver = name, ncm_id = ?metadata.ncm_id, "补全元数据失败: {e:?}"),
+        }
+    }
+    enriched
+}
+
+/// 前端没有提供封面时，根据歌曲 ID 从网易云 API 获取专辑封面 URL
+struct CoverResolver;
+
+impl Resolver for CoverResolver {
+    fn name(&self) -> &'static str {
+        "cover"
+    }
+
+    fn config<'a>(&self, config: &'a EnrichmentConfig) -> &'a ResolverConfig {
+        &config.cover
+    }
+
+    fn needed(&self, metadata: &MetadataPayload) -> bool {
+        metadata.ncm_id.is_some()
+            && metadata
+                .cover
+                .as_ref()
+                .is_none_or(|cover| cover.base64.is_none() && cover.url.is_none())
+    }
+
+    fn resolve(&self, metadata: &mut MetadataPayload, timeout: Duration) -> Result<()> {
+        let ncm_id = metadata.ncm_id.context("缺少歌曲 ID")?;
+        let url = song_detail(ncm_id, timeout)?
+            .album
+            .and_then(|album| album.pic_url)
+            .context("网易云 API 没有返回封面")?;
+        metadata.cover = Some(CoverPayload {
+            base64: None,
+            url: Some(url),
+        });
+        Ok(())
+    }
+}
+
+/// 前端没有提供时长时，根据歌曲 ID 从网易云 API 获取
+struct DurationResolver;
+
+impl Resolver for DurationResolver {
+    fn name(&self) -> &'static str {
+        "duration"
+    }
+
+    fn config<'a>(&self, config: &'a EnrichmentConfig) -> &'a ResolverConfig {
+        &config.duration
+    }
+
+    fn needed(&self, metadata: &MetadataPayload) -> bool {
+        metadata.ncm_id.is_some() && metadata.duration.is_none_or(|duration| duration <= 0.0)
+    }
+
+    fn resolve(&self, metadata: &mut MetadataPayload, timeout: Duration) -> Result<()> {
+        let ncm_id = metadata.ncm_id.context("缺少歌曲 ID")?;
+        let duration = song_detail(ncm_id, timeout)?
+            .duration
+            .filter(|&duration| duration > 0)
+            .context("网易云 API 没有返回时长")?;
+        metadata.duration = Some(duration as f64);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct SongDetailResponse {
+    #[serde(default)]
+    songs: Vec<SongDetail>,
+}
+
+#[derive(Deserialize, Clone)]
+struct SongDetail {
+    id: u64,
+    album: Option<AlbumDetail>,
+    /// 毫秒
+    duration: Option<u64>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AlbumDetail {
+    pic_url: Option<String>,
+}
+
+static AGENT: LazyLock<Agent> = LazyLock::new(|| {
+    Agent::config_builder()
+        .tls_config(
+            TlsConfig::builder()
+                .provider(TlsProvider::NativeTls)
+                .build(),
+        )
+        .build()
+        .into()
+});
+
+/// 多个解析器通常查询同一首歌，只缓存最近一次的结果
+static LAST_DETAIL: Mutex<Option<SongDetail>> = Mutex::new(None);
+
+fn song_detail(ncm_id: u64, timeout: Duration) -> Result<SongDetail> {
+    if let Ok(last) = LAST_DETAIL.lock()
+        && let Some(detail) = last.as_ref().filter(|detail| detail.id == ncm_id)
+    {
+        return Ok(detail.clone());
+    }
+
+    let response: SongDetailResponse = AGENT
+        .get(SONG_DETAIL_URL)
+        .query("ids", format!("[{ncm_id}]"))
+        .header("User-Agent", USER_AGENT)
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .call()?
+        .body_mut()
+        .read_json()
+        .context("解析歌曲详情失败")?;
+
+    let detail = response
+        .songs
+        .into_iter()
+        .find(|song| song.id == ncm_id)
+        .context("网易云 API 没有返回这首歌")?;
+    if let Ok(mut last) = LAST_DETAIL.lock() {
+        *last = Some(detail.clone());
+    }
+    Ok(detail)
+}
@@ -0,0 +1,43 @@
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use tracing::debug;
+
+/// 后端事件的接收方
+///
+/// V8 回调是默认的接收方，没有前端回调的环境 (例如只使用 HTTP 服务的小组件)
+/// 也可以通过其他接收方收到 SMTC 按钮事件
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `event_json` 是带序号的事件 JSON，与传给 V8 回调的内容相同
+    fn send(&self, event_json: &str);
+}
+
+static SINKS: Mutex<Vec<Arc<dyn EventSink>>> = Mutex::new(Vec::new());
+
+/// 同名的接收方只保留一个，重复初始化时新的接收方会替换旧的
+pub fn register(sink: Arc<dyn EventSink>) {
+    debug!(sink = sink.name(), "已注册事件接收方");
+    if let Ok(mut sinks) = SINKS.lock() {
+        sinks.retain(|existing| existing.name() != sink.name());
+        sinks.push(sink);
+    }
+}
+
+/// 移除指定名称的接收方，停止的服务之后不会再收到事件
+pub fn unregister(name: &str) {
+    if let Ok(mut sinks) = SINKS.lock() {
+        sinks.retain(|sink| sink.name() != name);
+    }
+}
+
+pub fn broadcast(event_json: &str) {
+    // 不在持有锁时调用接收方，避免接收方内部再分发事件时死锁
+    let sinks = SINKS.lock().map(|sinks| sinks.clone()).unwrap_or_default();
+    for sink in sinks {
+        sink.send(event_json);
+    }
+}
@@ -4,17 +4,24 @@ use std::{
         CString,
         c_char,
         c_int,
-        c_void,
     },
     panic,
+    path::PathBuf,
     ptr,
     sync::{
         LazyLock,
         Mutex,
         Once,
+        OnceLock,
     },
 };
 
+use betterncm_api::{
+    NCMProcessType,
+    PluginAPI,
+    RawV8Value,
+};
+use serde::Serialize;
 use tracing::{
     debug,
     error,
@@ -23,6 +30,7 @@ use tracing::{
 };
 
 use crate::{
+    context_watch,
     dispatcher,
     logger,
     smtc_core,
@@ -36,77 +44,29 @@ where
     match panic::catch_unwind(func) {
         Ok(result) => result,
         Err(e) => {
-            let message = e.downcast_ref::<&'static str>().map_or_else(
-                || {
-                    e.downcast_ref::<String>()
-                        .map_or("未知类型的 Panic", |s| s.as_str())
-                },
-                |s| *s,
-            );
-            error!("一个 FFI 调用发生了 Panic: {message}");
+            error!("一个 FFI 调用发生了 Panic: {}", logger::panic_message(&*e));
             T::default()
         }
     }
 }
 
-const DISPATCH_ARGS: [NativeAPIType; 1] = [NativeAPIType::String];
-const CALLBACK_ARGS: [NativeAPIType; 1] = [NativeAPIType::V8Value];
-
-#[repr(i32)]
-#[derive(Debug, PartialEq, Eq)]
-#[allow(dead_code)]
-pub enum NCMProcessType {
-    Undetected = 0x0,
-    Main = 0x0001,
-    Renderer = 0x10,
-    GpuProcess = 0x100,
-    Utility = 0x1000,
+/// 加载插件的 `BetterNCM` 和网易云版本，用于区分不同代的插件加载器
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoaderInfo {
+    pub betterncm_version: String,
+    pub ncm_version: Option<[u16; 3]>,
 }
 
-#[repr(i32)]
-#[allow(dead_code)]
-pub enum NativeAPIType {
-    Int,
-    Boolean,
-    Double,
-    String,
-    V8Value,
-}
+static LOADER_INFO: OnceLock<LoaderInfo> = OnceLock::new();
 
-pub type NativeFunction = unsafe extern "C" fn(args: *mut *mut c_void) -> *mut c_char;
-pub type AddNativeApiFn = extern "C" fn(
-    args: *const NativeAPIType,
-    args_num: c_int,
-    identifier: *const c_char,
-    function: NativeFunction,
-) -> c_int;
-
-#[repr(C)]
-pub struct PluginAPI {
-    pub add_native_api: AddNativeApiFn,
-    pub betterncm_version: *const c_char,
-    pub process_type: NCMProcessType,
-    pub ncm_version: *const [u16; 3],
+pub fn loader_info() -> Option<LoaderInfo> {
+    LOADER_INFO.get().cloned()
 }
 
-unsafe fn register_api(
-    add_api_fn: AddNativeApiFn,
-    identifier_str: &str,
-    args: Option<&[NativeAPIType]>,
-    function: NativeFunction,
-) -> Result<(), c_int> {
-    let identifier = match CString::new(identifier_str) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("无法创建 CString '{identifier_str}': {e}");
-            return Err(-1);
-        }
-    };
-
-    let (args_ptr, args_len) = args.map_or((ptr::null(), 0), |a| (a.as_ptr(), a.len() as c_int));
-
-    add_api_fn(args_ptr, args_len, identifier.as_ptr(), function);
-    Ok(())
+/// 前端传入的参数为空指针，`BetterNCM` 正常情况下不会这样调用
+fn invalid_arg(identifier: &str, index: usize) {
+    error!("{identifier} 的第 {index} 个参数为空指针");
 }
 
 unsafe fn c_char_to_string(s: *const c_char) -> String {
@@ -116,18 +76,16 @@ unsafe fn c_char_to_string(s: *const c_char) -> String {
     unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() }
 }
 
-#[instrument(skip(_args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn initialize(_args: *mut *mut c_void) -> *mut c_char {
+#[instrument]
+fn initialize() -> *mut c_char {
     safe_call(|| {
         dispatcher::init();
         ptr::null_mut()
     })
 }
 
-#[instrument(skip(_args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn terminate(_args: *mut *mut c_void) -> *mut c_char {
+#[instrument]
+fn terminate() -> *mut c_char {
     safe_call(|| {
         logger::clear_callback();
         dispatcher::shutdown();
@@ -135,19 +93,16 @@ pub unsafe extern "C" fn terminate(_args: *mut *mut c_void) -> *mut c_char {
     })
 }
 
-#[instrument(skip(args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn registerEventCallback(args: *mut *mut c_void) -> *mut c_char {
+#[instrument]
+fn register_event_callback(callback: RawV8Value) -> *mut c_char {
     safe_call(|| {
-        let v8_func_ptr = unsafe { *args.cast::<*mut cef_safe::cef_sys::_cef_v8value_t>() };
-        if !v8_func_ptr.is_null() {
-            match unsafe { cef_safe::CefV8Value::from_raw(v8_func_ptr) } {
-                Ok(v8_func) => {
-                    debug!("已注册事件回调");
-                    smtc_core::register_event_callback(v8_func);
-                }
-                Err(e) => error!("无法转换 V8 指针 {e:?}"),
+        match unsafe { cef_safe::CefV8Value::from_raw(callback.as_ptr().cast()) } {
+            Ok(v8_func) => {
+                debug!("已注册事件回调");
+                smtc_core::register_event_callback(v8_func);
+                context_watch::start();
             }
+            Err(e) => error!("无法转换 V8 指针 {e:?}"),
         }
         ptr::null_mut()
     })
@@ -163,21 +118,9 @@ pub unsafe extern "C" fn registerEventCallback(args: *mut *mut c_void) -> *mut c
 /// 参见 <https://github.com/std-microblock/chromatic/blob/1b7eb7fdaa08de15e579c86dadb6ef848a72b6f1/src/v8NativeCalls.cpp#L585-L590>
 static RETURN_BUFFER: LazyLock<Mutex<CString>> = LazyLock::new(|| Mutex::new(CString::default()));
 
-#[instrument(skip(args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn dispatch(args: *mut *mut c_void) -> *mut c_char {
-    safe_call(|| {
-        if args.is_null() {
-            error!("dispatch 收到了空指针");
-            return ptr::null_mut();
-        }
-        let command_ptr = unsafe { *args.add(0) };
-        if command_ptr.is_null() {
-            error!("dispatch 收到了空命令指针");
-            return ptr::null_mut();
-        }
-
-        let command_json = unsafe { c_char_to_string(command_ptr.cast::<c_char>()) };
+#[instrument(skip(command_json))]
+fn dispatch(command_json: String) -> *mut c_char {
+    safe_call(move || {
         // trace!(command = %command_json, "收到前端命令");
 
         let result_json = dispatcher::send_command(&command_json);
@@ -202,39 +145,24 @@ pub unsafe extern "C" fn dispatch(args: *mut *mut c_void) -> *mut c_char {
     })
 }
 
-#[instrument(skip(args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn registerLogger(args: *mut *mut c_void) -> *mut c_char {
+#[instrument]
+fn register_logger(callback: RawV8Value) -> *mut c_char {
     safe_call(|| {
-        let v8_func_ptr = unsafe { *args.cast::<*mut cef_safe::cef_sys::_cef_v8value_t>() };
-        if !v8_func_ptr.is_null() {
-            match unsafe { cef_safe::CefV8Value::from_raw(v8_func_ptr) } {
-                Ok(v8_func) => {
-                    debug!("已注册日志回调");
-                    logger::register_callback(v8_func);
-                }
-                Err(e) => error!("无法转换 V8 指针: {e:?}"),
+        match unsafe { cef_safe::CefV8Value::from_raw(callback.as_ptr().cast()) } {
+            Ok(v8_func) => {
+                debug!("已注册日志回调");
+                logger::register_callback(v8_func);
+                context_watch::start();
             }
+            Err(e) => error!("无法转换 V8 指针: {e:?}"),
         }
         ptr::null_mut()
     })
 }
 
-#[instrument(skip(args))]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn setLogLevel(args: *mut *mut c_void) -> *mut c_char {
-    safe_call(|| {
-        if args.is_null() {
-            error!("setLogLevel 收到了空指针");
-            return ptr::null_mut();
-        }
-        let level_pointer = unsafe { *args.add(0) };
-        if level_pointer.is_null() {
-            error!("setLogLevel 收到了空日志级别指针");
-            return ptr::null_mut();
-        }
-
-        let level_string = unsafe { c_char_to_string(level_pointer.cast::<c_char>()) };
+#[instrument]
+fn set_log_level(level_string: String) -> *mut c_char {
+    safe_call(move || {
         if let Err(e) = logger::set_frontend_log_level(&level_string) {
             error!("设置日志级别失败: {e}");
         }
@@ -243,6 +171,26 @@ pub unsafe extern "C" fn setLogLevel(args: *mut *mut c_void) -> *mut c_char {
     })
 }
 
+/// 在后台线程中回放命令录制文件，只在调试构建中注册，供开发者在 `DevTools` 中调用
+#[cfg(debug_assertions)]
+#[instrument]
+fn replay_commands(path: String) -> *mut c_char {
+    safe_call(move || {
+        let path = PathBuf::from(path);
+        let spawn_result = std::thread::Builder::new()
+            .name("command-replay-thread".into())
+            .spawn(move || {
+                if let Err(e) = crate::recorder::replay(&path) {
+                    error!("回放命令失败: {e}");
+                }
+            });
+        if let Err(e) = spawn_result {
+            error!("无法启动命令回放线程: {e}");
+        }
+        ptr::null_mut()
+    })
+}
+
 static LOGGER_INIT: Once = Once::new();
 
 #[instrument(skip(api))]
@@ -262,37 +210,33 @@ pub unsafe extern "C" fn BetterNCMPluginMain(api: *mut PluginAPI) -> c_int {
 
         unsafe {
             let api_ref = &*api;
-            if api_ref.process_type == NCMProcessType::Renderer {
-                trace!(process_type = ?api_ref.process_type, "正在注册 API");
-                let add_api = api_ref.add_native_api;
 
-                macro_rules! reg {
-                    ($func:ident, $args:expr) => {
-                        register_api(
-                            add_api,
-                            concat!("inflink.", stringify!($func)),
-                            $args,
-                            $func,
-                        )
-                    };
-                    ($func:ident) => {
-                        reg!($func, None)
-                    };
-                }
-
-                let registrations = [
-                    reg!(initialize),
-                    reg!(registerLogger, Some(&CALLBACK_ARGS)),
-                    reg!(setLogLevel, Some(&DISPATCH_ARGS)),
-                    reg!(terminate),
-                    reg!(registerEventCallback, Some(&CALLBACK_ARGS)),
-                    reg!(dispatch, Some(&DISPATCH_ARGS)),
-                ];
+            let loader_info = LoaderInfo {
+                betterncm_version: c_char_to_string(api_ref.betterncm_version),
+                ncm_version: api_ref.ncm_version.as_ref().copied(),
+            };
+            debug!(?loader_info, "插件加载器信息");
+            let _ = LOADER_INFO.set(loader_info);
 
-                for result in registrations {
-                    if let Err(code) = result {
-                        return code;
-                    }
+            if api_ref.process_type == NCMProcessType::Renderer {
+                trace!(process_type = ?api_ref.process_type, "正在注册 API");
+                let apis = betterncm_api::native_apis! {
+                    prefix: "inflink",
+                    on_invalid_arg: invalid_arg,
+                    apis: [
+                        initialize => initialize(),
+                        registerLogger => register_logger(RawV8Value),
+                        setLogLevel => set_log_level(String),
+                        terminate => terminate(),
+                        registerEventCallback => register_event_callback(RawV8Value),
+                        dispatch => dispatch(String),
+                        #[cfg(debug_assertions)]
+                        replayCommands => replay_commands(String),
+                    ],
+                };
+
+                for api in &apis {
+                    api_ref.register(api);
                 }
             } else {
                 debug!(process_type = ?api_ref.process_type, "插件在非渲染进程中加载, 跳过注册API");
@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use unicode_width::{
+    UnicodeWidthChar,
+    UnicodeWidthStr,
+};
+
+use crate::{
+    config::{
+        DurationStyle,
+        TextFormat,
+    },
+    model::MetadataPayload,
+};
+
+const ELLIPSIS: char = '…';
+
+/// 把毫秒格式化为 `m:ss` 或 `h:mm:ss`
+pub fn duration(ms: f64, style: DurationStyle) -> String {
+    let total_secs = (ms.max(0.0) / 1000.0) as u64;
+    let (hours, minutes, seconds) = (total_secs / 3600, total_secs / 60 % 60, total_secs % 60);
+
+    match style {
+        DurationStyle::Compact if hours == 0 => format!("{minutes}:{seconds:02}"),
+        DurationStyle::Padded if hours == 0 => format!("{minutes:02}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+/// 网易云的歌曲页面，没有 ID 时为网易云首页
+pub fn song_url(ncm_id: Option<u64>) -> String {
+    ncm_id.map_or_else(
+        || "https://music.163.com/".to_string(),
+        |id| format!("https://music.163.com/song?id={id}"),
+    )
+}
+
+/// 替换模板中的 `{title}` `{artist}` `{album}` `{url}` `{id}` `{duration}`，
+/// 不认识的占位符原样保留
+pub fn render_template(template: &str, metadata: &MetadataPayload) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find('}') else {
+            break;
+        };
+
+        let name = &placeholder[1..end];
+        match placeholder_value(name, metadata) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&placeholder[..=end]),
+        }
+        rest = &placeholder[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn placeholder_value(name: &str, metadata: &MetadataPayload) -> Option<String> {
+    let value = match name {
+        "title" => metadata.song_name.clone(),
+        "artist" => metadata.author_name.clone(),
+        "album" => metadata.album_name.clone(),
+        "url" => song_url(metadata.ncm_id),
+        "id" => metadata.ncm_id.map(|id| id.to_string()).unwrap_or_default(),
+        "duration" => metadata
+            .duration
+            .map(|ms| duration(ms, DurationStyle::Compact))
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 按显示宽度截断文本，中日韩字符按两个宽度计算，超出时以省略号结尾
+pub fn truncate(text: &str, max_width: usize) -> Cow<'_, str> {
+    if text.width() <= max_width {
+        return Cow::Borrowed(text);
+    }
+
+    let budget = max_width.saturating_sub(ELLIPSIS.width().unwrap_or(1));
+    let mut width = 0;
+    let mut end = 0;
+    for (index, ch) in text.char_indices() {
+        width += ch.width().unwrap_or(0);
+        if width > budget {
+            break;
+        }
+        end = index + ch.len_utf8();
+    }
+
+    Cow::Owned(format!("{}{ELLIPSIS}", text[..end].trim_end()))
+}
+
+impl TextFormat {
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.max_width
+            .map_or(Cow::Borrowed(text), |max_width| truncate(text, max_width))
+    }
+
+    pub fn duration(&self, ms: f64) -> String {
+        duration(ms, self.duration_style)
+    }
+}
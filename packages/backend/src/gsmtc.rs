@@ -0,0 +1,119 @@
+use std::{
+    sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+use windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSessionManager,
+    GlobalSystemMediaTransportControlsSessionPlaybackStatus,
+};
+
+use crate::{
+    config,
+    discord,
+    platform,
+    tasks,
+    winrt::{
+        JoinTimeout,
+        LOCAL_TIMEOUT,
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// 网易云自己的媒体会话，插件的 SMTC 也注册在这个进程下
+const NCM_APP_ID: &str = "cloudmusic";
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// 通过系统的全局媒体会话 (GSMTC) 监视其他播放器，
+/// 在其他应用 (例如游戏或 Spotify) 占据当前媒体会话并正在播放时暂时隐藏 Discord 状态
+///
+/// 可以重复调用，配置文件中启用该功能后会再次调用以启动监视线程
+pub fn init() {
+    if !config::get().discord.suppress_when_other_player_active {
+        return;
+    }
+
+    if !platform::capabilities().global_media_sessions {
+        warn!("当前系统不支持全局媒体会话，无法在其他播放器播放时隐藏 Discord 状态");
+        return;
+    }
+
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("gsmtc-monitor-thread", |token| {
+        let manager = match GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
+            .and_then(|operation| operation.join_timeout(LOCAL_TIMEOUT))
+        {
+            Ok(manager) => manager,
+            Err(e) => {
+                warn!("获取全局媒体会话管理器失败: {e:?}");
+                return;
+            }
+        };
+
+        let mut last_active_app = None;
+        while !token.is_cancelled() {
+            // 配置文件中关闭该功能后线程继续运行，只是不再隐藏状态
+            let active_app = if config::get().discord.suppress_when_other_player_active {
+                other_active_app(&manager).unwrap_or_else(|e| {
+                    debug!("读取当前媒体会话失败: {e:?}");
+                    None
+                })
+            } else {
+                None
+            };
+
+            if active_app != last_active_app {
+                if let Some(app) = &active_app {
+                    info!(app, "其他播放器正在播放，暂时隐藏 Discord 状态");
+                } else {
+                    info!("其他播放器已停止，恢复 Discord 状态");
+                }
+                discord::set_suppressed_by(active_app.clone());
+                last_active_app = active_app;
+            }
+
+            token.sleep(POLL_INTERVAL);
+        }
+
+        // 任务被取消 (例如暂停插件) 后允许再次启动
+        STARTED.store(false, Ordering::SeqCst);
+    });
+
+    if let Err(e) = spawn_result {
+        STARTED.store(false, Ordering::SeqCst);
+        warn!("无法启动媒体会话监视线程: {e}");
+    }
+}
+
+/// 当前媒体会话属于其他应用且正在播放时返回它的 AUMID
+fn other_active_app(
+    manager: &GlobalSystemMediaTransportControlsSessionManager,
+) -> Result<Option<String>> {
+    let Ok(session) = manager.GetCurrentSession() else {
+        return Ok(None);
+    };
+
+    let app_id = session.SourceAppUserModelId()?.to_string();
+    if app_id.to_ascii_lowercase().contains(NCM_APP_ID) {
+        return Ok(None);
+    }
+
+    let status = session.GetPlaybackInfo()?.PlaybackStatus()?;
+    Ok(
+        (status == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing)
+            .then_some(app_id),
+    )
+}
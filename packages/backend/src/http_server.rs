@@ -0,0 +1,428 @@
+use std::{
+    borrow::Cow,
+    io::Write,
+    net::{
+        Ipv4Addr,
+        SocketAddr,
+    },
+    sync::{
+        Arc,
+        LazyLock,
+        Mutex,
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        mpsc::{
+            self,
+            SyncSender,
+            TrySendError,
+        },
+    },
+    thread::{
+        self,
+        JoinHandle,
+    },
+    time::Instant,
+};
+
+use base64::{
+    Engine,
+    engine::general_purpose,
+};
+use serde::Serialize;
+use tiny_http::{
+    Header,
+    Method,
+    Request,
+    Response,
+    Server,
+};
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+
+use crate::{
+    config::{
+        self,
+        PausePolicy,
+    },
+    event_sink::{
+        self,
+        EventSink,
+    },
+    model::{
+        PlaybackStatus,
+        SharedMetadata,
+    },
+    timeline::Timeline,
+};
+
+/// 当前播放的歌曲，供本地网页叠加层和小组件读取
+#[derive(Default)]
+struct NowPlayingState {
+    metadata: Option<SharedMetadata>,
+    status: Option<PlaybackStatus>,
+    current_time: f64,
+    total_time: f64,
+    /// 最近一次收到时间线更新的时刻，用于推算播放中的进度
+    timeline_updated_at: Option<Instant>,
+}
+
+static STATE: LazyLock<Mutex<NowPlayingState>> =
+    LazyLock::new(|| Mutex::new(NowPlayingState::default()));
+
+struct RunningServer {
+    server: Arc<Server>,
+    thread: JoinHandle<()>,
+}
+
+/// 正在运行的服务，停止后可以再次调用 [`init`] 启动
+static SERVER: Mutex<Option<RunningServer>> = Mutex::new(None);
+
+const SSE_SINK_NAME: &str = "http-sse";
+
+/// 同时订阅 `/events` 的客户端上限
+const MAX_EVENT_CLIENTS: usize = 8;
+/// 每个客户端最多积压的事件数，客户端停止读取导致积压超过这个数量时断开它
+const CLIENT_QUEUE_LEN: usize = 64;
+
+/// 订阅了 `/events` 的客户端的事件队列，由每个客户端各自的写入线程写入连接
+static EVENT_CLIENTS: Mutex<Vec<SyncSender<Arc<str>>>> = Mutex::new(Vec::new());
+/// 仍在运行的写入线程数，已断开但仍卡在写入中的线程也计入客户端上限
+static EVENT_WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// 通过 Server-Sent Events 把后端事件转发给本机的客户端，
+/// 不依赖前端的 V8 回调也能收到 SMTC 按钮事件
+struct SseSink;
+
+impl EventSink for SseSink {
+    fn name(&self) -> &'static str {
+        SSE_SINK_NAME
+    }
+
+    /// 只把事件放入各个客户端的队列，不在这里写入连接，
+    /// 否则一个停止读取的客户端会卡住分发事件的线程 (例如 Dispatcher 和 SMTC 按钮回调)
+    fn send(&self, event_json: &str) {
+        let Ok(mut clients) = EVENT_CLIENTS.lock() else {
+            return;
+        };
+        if clients.is_empty() {
+            return;
+        }
+
+        let message: Arc<str> = format!("data: {event_json}\n\n").into();
+        clients.retain(|client| match client.try_send(Arc::clone(&message)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                debug!("事件订阅客户端积压过多，已断开");
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NowPlayingResponse<'a> {
+    title: Cow<'a, str>,
+    artist: Cow<'a, str>,
+    album: Cow<'a, str>,
+    is_playing: bool,
+    progress_ms: u64,
+    duration_ms: u64,
+    /// 按 `formatting.http_server` 格式化后的进度，例如 `1:23`
+    progress_text: String,
+    duration_text: String,
+    ncm_id: Option<u64>,
+    /// 可以用来打开专辑页面，前端没有提供时为空
+    album_id: Option<u64>,
+    artist_ids: &'a [u64],
+    /// 指向本服务的 `/cover.jpg`，没有封面时为空
+    cover_url: Option<String>,
+}
+
+pub fn is_running() -> bool {
+    SERVER.lock().is_ok_and(|running| running.is_some())
+}
+
+pub fn init() {
+    let server_config = config::get().http_server;
+    if !server_config.enabled {
+        return;
+    }
+
+    let Ok(mut running) = SERVER.lock() else {
+        return;
+    };
+    if running.is_some() {
+        warn!("正在播放 HTTP 服务已经启动，不应重复调用 init()");
+        return;
+    }
+
+    // 只监听本机地址，避免把播放信息暴露到局域网
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, server_config.port));
+    let server = match Server::http(addr) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            warn!("启动正在播放 HTTP 服务失败 ({addr}): {e}");
+            return;
+        }
+    };
+
+    let thread_server = Arc::clone(&server);
+    let spawn_result = thread::Builder::new()
+        .name("http-server-thread".into())
+        .spawn(move || {
+            for request in thread_server.incoming_requests() {
+                handle_request(request, addr.port());
+            }
+            debug!("正在播放 HTTP 服务已停止");
+        });
+
+    match spawn_result {
+        Ok(thread) => {
+            event_sink::register(Arc::new(SseSink));
+            *running = Some(RunningServer { server, thread });
+            info!("正在播放 HTTP 服务已启动: http://{addr}/now-playing");
+        }
+        Err(e) => warn!("无法启动 HTTP 服务线程: {e}"),
+    }
+}
+
+/// 停止服务并断开所有 `/events` 客户端，插件暂停后恢复时会重新调用 [`init`]
+pub fn shutdown() {
+    let running = SERVER.lock().ok().and_then(|mut running| running.take());
+    event_sink::unregister(SSE_SINK_NAME);
+    if let Ok(mut clients) = EVENT_CLIENTS.lock() {
+        clients.clear();
+    }
+
+    let Some(running) = running else {
+        return;
+    };
+    running.server.unblock();
+    // 等待线程释放服务，否则马上重新启动时端口可能仍被占用
+    if running.thread.join().is_err() {
+        warn!("HTTP 服务线程异常退出");
+    }
+}
+
+pub fn update_metadata(metadata: SharedMetadata) {
+    if let Ok(mut state) = STATE.lock() {
+        state.total_time = metadata.duration.unwrap_or_default();
+        state.current_time = 0.0;
+        state.timeline_updated_at = None;
+        state.metadata = Some(metadata);
+    }
+}
+
+pub fn update_play_state(status: PlaybackStatus) {
+    if let Ok(mut state) = STATE.lock() {
+        // 先把进度推算到当前时刻，避免暂停后进度回退
+        state.current_time = state.progress_ms();
+        state.timeline_updated_at = Some(Instant::now());
+        state.status = Some(status);
+    }
+}
+
+pub fn update_timeline(timeline: Timeline) {
+    if let Ok(mut state) = STATE.lock() {
+        state.current_time = timeline.position_ms();
+        state.total_time = timeline.duration_ms();
+        state.timeline_updated_at = Some(Instant::now());
+    }
+}
+
+impl NowPlayingState {
+    fn progress_ms(&self) -> f64 {
+        let mut progress = self.current_time;
+
+        if self.status == Some(PlaybackStatus::Playing)
+            && let Some(updated_at) = self.timeline_updated_at
+        {
+            progress += updated_at.elapsed().as_secs_f64() * 1000.0;
+        }
+
+        if self.total_time > 0.0 {
+            progress.min(self.total_time)
+        } else {
+            progress
+        }
+    }
+}
+
+fn handle_request(request: Request, port: u16) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    debug!(method = %request.method(), path, "收到 HTTP 请求");
+
+    if request.method() == &Method::Get && path == "/events" {
+        subscribe_events(request);
+        return;
+    }
+
+    let response = match (request.method(), path.as_str()) {
+        (Method::Options, _) => Response::empty(204).boxed(),
+        (Method::Get, "/" | "/now-playing") => now_playing_response(port),
+        (Method::Get, "/cover.jpg") => cover_response(),
+        _ => Response::empty(404).boxed(),
+    };
+
+    let response = response
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .with_header(header("Access-Control-Allow-Methods", "GET, OPTIONS"))
+        .with_header(header("Cache-Control", "no-store"));
+
+    if let Err(e) = request.respond(response) {
+        debug!("发送 HTTP 响应失败: {e}");
+    }
+}
+
+/// 接管连接并保持打开，之后由单独的写入线程把 [`SseSink`] 放入队列的事件写入连接
+fn subscribe_events(request: Request) {
+    const RESPONSE_HEAD: &str = "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/event-stream\r\n\
+        Cache-Control: no-store\r\n\
+        Access-Control-Allow-Origin: *\r\n\r\n";
+
+    if EVENT_WRITERS.load(Ordering::Relaxed) >= MAX_EVENT_CLIENTS {
+        debug!("事件订阅数量已达到上限");
+        let _ = request.respond(Response::empty(503));
+        return;
+    }
+
+    let mut writer = request.into_writer();
+    if writer
+        .write_all(RESPONSE_HEAD.as_bytes())
+        .and_then(|()| writer.flush())
+        .is_err()
+    {
+        return;
+    }
+
+    let (tx, rx) = mpsc::sync_channel::<Arc<str>>(CLIENT_QUEUE_LEN);
+    EVENT_WRITERS.fetch_add(1, Ordering::Relaxed);
+    let spawn_result = thread::Builder::new()
+        .name("http-sse-thread".into())
+        .spawn(move || {
+            // 客户端被移除后队列断开，循环结束并关闭连接
+            for message in rx {
+                if writer
+                    .write_all(message.as_bytes())
+                    .and_then(|()| writer.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            EVENT_WRITERS.fetch_sub(1, Ordering::Relaxed);
+        });
+
+    match spawn_result {
+        Ok(_) => {
+            if let Ok(mut clients) = EVENT_CLIENTS.lock() {
+                clients.push(tx);
+                debug!(clients = clients.len(), "新的客户端订阅了事件");
+            }
+        }
+        Err(e) => {
+            EVENT_WRITERS.fetch_sub(1, Ordering::Relaxed);
+            warn!("无法启动事件订阅的写入线程: {e}");
+        }
+    }
+}
+
+fn now_playing_response(port: u16) -> tiny_http::ResponseBox {
+    let Ok(state) = STATE.lock() else {
+        return Response::empty(500).boxed();
+    };
+
+    let Some(metadata) = state.metadata.as_ref() else {
+        return Response::empty(204).boxed();
+    };
+
+    let config = config::get();
+    let is_playing = state.status == Some(PlaybackStatus::Playing);
+    if !is_playing && config.pause_policy.http_server == PausePolicy::Clear {
+        return Response::empty(204).boxed();
+    }
+
+    let format = config.formatting.http_server;
+    let progress_ms = state.progress_ms();
+    let body = NowPlayingResponse {
+        title: format.text(&metadata.song_name),
+        artist: format.text(&metadata.author_name),
+        album: format.text(&metadata.album_name),
+        is_playing,
+        progress_ms: progress_ms as u64,
+        duration_ms: state.total_time as u64,
+        progress_text: format.duration(progress_ms),
+        duration_text: format.duration(state.total_time),
+        ncm_id: metadata.ncm_id,
+        album_id: metadata.album_id,
+        artist_ids: &metadata.artist_ids,
+        cover_url: metadata
+            .cover
+            .as_ref()
+            .map(|_| format!("http://127.0.0.1:{port}/cover.jpg")),
+    };
+
+    match serde_json::to_vec(&body) {
+        Ok(json) => Response::from_data(json)
+            .with_header(header("Content-Type", "application/json; charset=utf-8"))
+            .boxed(),
+        Err(e) => {
+            warn!("序列化正在播放信息失败: {e}");
+            Response::empty(500).boxed()
+        }
+    }
+}
+
+fn cover_response() -> tiny_http::ResponseBox {
+    let cover = STATE
+        .lock()
+        .ok()
+        .and_then(|state| state.metadata.as_ref().and_then(|m| m.cover.clone()));
+
+    let Some(cover) = cover else {
+        return Response::empty(404).boxed();
+    };
+
+    if let Some(base64_data) = &cover.base64 {
+        match general_purpose::STANDARD.decode(base64_data) {
+            Ok(bytes) => {
+                let content_type = if bytes.starts_with(b"\x89PNG") {
+                    "image/png"
+                } else {
+                    "image/jpeg"
+                };
+                return Response::from_data(bytes)
+                    .with_header(header("Content-Type", content_type))
+                    .boxed();
+            }
+            Err(e) => warn!("解码封面 Base64 失败: {e}"),
+        }
+    }
+
+    // 只有封面 URL 时让客户端直接从网易云的服务器获取
+    cover
+        .url
+        .and_then(|url| Header::from_bytes(&b"Location"[..], url.as_bytes()).ok())
+        .map_or_else(
+            || Response::empty(404).boxed(),
+            |location| Response::empty(302).with_header(location).boxed(),
+        )
+}
+
+fn header(name: &'static str, value: &'static str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .expect("硬编码的 HTTP 头无效，这不应该发生")
+}
@@ -0,0 +1,233 @@
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{
+            AtomicBool,
+            AtomicU32,
+            Ordering,
+        },
+    },
+    time::Duration,
+};
+
+use anyhow::{
+    Result,
+    bail,
+};
+use tracing::{
+    info,
+    warn,
+};
+use windows::{
+    Win32::{
+        Foundation::{
+            CloseHandle,
+            HANDLE,
+            INVALID_HANDLE_VALUE,
+            WAIT_ABANDONED,
+            WAIT_OBJECT_0,
+        },
+        System::{
+            Memory::{
+                CreateFileMappingW,
+                FILE_MAP_ALL_ACCESS,
+                MapViewOfFile,
+                PAGE_READWRITE,
+            },
+            Threading::{
+                CreateMutexW,
+                GetCurrentProcessId,
+                GetExitCodeProcess,
+                OpenProcess,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+                ReleaseMutex,
+                WaitForSingleObject,
+            },
+        },
+    },
+    core::w,
+};
+
+use crate::{
+    dispatcher,
+    model::AppMessage,
+    tasks,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 等待其他实例释放仲裁锁的最长时间 (毫秒)
+const LOCK_TIMEOUT_MS: u32 = 1000;
+/// `GetExitCodeProcess` 对仍在运行的进程返回的值
+const STILL_ACTIVE: u32 = 259;
+
+/// 同一用户会话中所有网易云实例共享的仲裁状态
+struct Arbiter {
+    /// 共享内存中正在发布状态的实例的进程 ID，0 表示没有
+    active_pid: &'static AtomicU32,
+    mutex: HANDLE,
+}
+
+// Safety: 共享内存在进程的整个生命周期内保持映射，互斥量句柄可以在任意线程上使用
+unsafe impl Send for Arbiter {}
+unsafe impl Sync for Arbiter {}
+
+static ARBITER: OnceLock<Arbiter> = OnceLock::new();
+
+/// 本实例是否负责发布 SMTC 和 Discord 状态，没有仲裁器时总是发布
+static IS_ACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// 多个网易云实例同时加载插件时，只让最近开始播放的实例发布状态，
+/// 该实例退出后其他实例自动接管
+///
+/// 暂停插件后恢复时会再次调用，此时复用已经打开的仲裁器
+pub fn init() {
+    if ARBITER.get().is_none() {
+        match Arbiter::open() {
+            Ok(arbiter) => {
+                let _ = ARBITER.set(arbiter);
+            }
+            Err(e) => {
+                warn!("无法创建多实例仲裁器，将始终发布播放状态: {e:?}");
+                return;
+            }
+        }
+    }
+
+    refresh();
+
+    let spawn_result = tasks::spawn("instance-arbiter-thread", |token| {
+        while token.sleep(POLL_INTERVAL) {
+            refresh();
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动多实例仲裁线程: {e}");
+    }
+}
+
+pub fn is_active() -> bool {
+    IS_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// 本实例开始播放时取得发布权
+pub fn note_playing() {
+    let Some(arbiter) = ARBITER.get() else {
+        return;
+    };
+
+    let pid = current_pid();
+    arbiter.with_lock(|| arbiter.active_pid.store(pid, Ordering::SeqCst));
+    refresh();
+}
+
+/// 退出时交出发布权，让其他实例不必等到下次轮询就能接管
+pub fn release() {
+    let Some(arbiter) = ARBITER.get() else {
+        return;
+    };
+
+    let pid = current_pid();
+    arbiter.with_lock(|| {
+        let _ = arbiter
+            .active_pid
+            .compare_exchange(pid, 0, Ordering::SeqCst, Ordering::SeqCst);
+    });
+}
+
+fn refresh() {
+    let Some(arbiter) = ARBITER.get() else {
+        return;
+    };
+
+    let pid = current_pid();
+    let Some((active, active_pid)) = arbiter.with_lock(|| {
+        let active_pid = arbiter.active_pid.load(Ordering::SeqCst);
+        if active_pid == pid {
+            return (true, pid);
+        }
+        if active_pid == 0 || !process_alive(active_pid) {
+            arbiter.active_pid.store(pid, Ordering::SeqCst);
+            return (true, pid);
+        }
+        (false, active_pid)
+    }) else {
+        return;
+    };
+
+    if IS_ACTIVE.swap(active, Ordering::Relaxed) == active {
+        return;
+    }
+
+    if active {
+        info!("本实例开始发布播放状态");
+    } else {
+        info!(
+            active_pid,
+            "另一个网易云实例正在播放，本实例暂停发布播放状态"
+        );
+    }
+    dispatcher::post(AppMessage::InstanceStandby(!active));
+}
+
+impl Arbiter {
+    fn open() -> Result<Self> {
+        // 新建的共享内存会被清零，也就是没有活动实例
+        let mapping = unsafe {
+            CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                None,
+                PAGE_READWRITE,
+                0,
+                size_of::<AtomicU32>() as u32,
+                w!("Local\\InfLinkRs.ActiveInstance"),
+            )?
+        };
+        let view =
+            unsafe { MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, size_of::<AtomicU32>()) };
+        // 映射视图会保持共享内存对象存活
+        let _ = unsafe { CloseHandle(mapping) };
+        if view.Value.is_null() {
+            bail!(
+                "映射共享内存失败: {:?}",
+                windows::core::Error::from_thread()
+            );
+        }
+
+        let mutex = unsafe { CreateMutexW(None, false, w!("Local\\InfLinkRs.InstanceLock"))? };
+
+        Ok(Self {
+            active_pid: unsafe { &*view.Value.cast::<AtomicU32>() },
+            mutex,
+        })
+    }
+
+    /// 持有跨进程的仲裁锁执行 `f`，等待超时时返回 `None`
+    fn with_lock<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let wait = unsafe { WaitForSingleObject(self.mutex, LOCK_TIMEOUT_MS) };
+        // 持有锁的实例崩溃时会得到 WAIT_ABANDONED，此时共享状态仍然只是一个进程 ID，可以继续使用
+        if wait != WAIT_OBJECT_0 && wait != WAIT_ABANDONED {
+            return None;
+        }
+
+        let result = f();
+        let _ = unsafe { ReleaseMutex(self.mutex) };
+        Some(result)
+    }
+}
+
+fn current_pid() -> u32 {
+    unsafe { GetCurrentProcessId() }
+}
+
+fn process_alive(pid: u32) -> bool {
+    let Ok(handle) = (unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }) else {
+        return false;
+    };
+
+    let mut exit_code = 0;
+    let result = unsafe { GetExitCodeProcess(handle, &raw mut exit_code) };
+    let _ = unsafe { CloseHandle(handle) };
+
+    result.is_ok() && exit_code == STILL_ACTIVE
+}
@@ -1,6 +1,38 @@
+mod audio_session;
+mod clipboard;
+mod clock;
+mod command_worker;
+mod config;
+mod config_watch;
+mod context_watch;
+mod cover;
+mod diagnostics;
 mod discord;
+mod discord_pipe;
 mod dispatcher;
+mod enrichment;
+mod event_sink;
 mod ffi;
+mod formatting;
+mod gsmtc;
+mod http_server;
+mod instance;
+mod listening_session;
 mod logger;
+mod maintenance;
 mod model;
+mod palette;
+mod platform;
+mod presence_lock;
+mod recorder;
+mod redact;
+mod request_id;
+mod schema;
+mod self_test;
+mod sinks;
 mod smtc_core;
+mod tasks;
+mod timeline;
+mod updater;
+mod watchdog;
+mod winrt;
@@ -0,0 +1,176 @@
+use std::{
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+
+use crate::{
+    config,
+    model::PlaybackStatus,
+    smtc_core::{
+        self,
+        SmtcEvent,
+    },
+    tasks,
+};
+
+/// 检查暂停时长的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 短于这个时长的会话不发送总结，例如只是打开网易云试听了几秒
+const MIN_LISTENED: Duration = Duration::from_mins(1);
+
+/// 一段连续的收听，暂停超过 `listening_session.end_after_pause_mins` 后结束
+struct ListeningSession {
+    started_at: SystemTime,
+    /// 最近一次开始播放的时刻，暂停时为空
+    playing_since: Option<Instant>,
+    /// 暂停的时刻，播放时为空
+    paused_since: Option<Instant>,
+    /// 最近一次仍在播放的时刻，作为会话的结束时间
+    last_played_at: SystemTime,
+    listened: Duration,
+    tracks_played: u32,
+}
+
+impl ListeningSession {
+    fn new() -> Self {
+        let now = SystemTime::now();
+        Self {
+            started_at: now,
+            playing_since: None,
+            paused_since: None,
+            last_played_at: now,
+            listened: Duration::ZERO,
+            tracks_played: 0,
+        }
+    }
+
+    fn pause(&mut self) {
+        if let Some(since) = self.playing_since.take() {
+            self.listened += since.elapsed();
+            self.last_played_at = SystemTime::now();
+        }
+        self.paused_since.get_or_insert_with(Instant::now);
+    }
+
+    fn idle_too_long(&self, end_after: Duration) -> bool {
+        self.paused_since
+            .is_some_and(|since| since.elapsed() >= end_after)
+    }
+}
+
+static CURRENT: Mutex<Option<ListeningSession>> = Mutex::new(None);
+
+fn end_after_pause() -> Option<Duration> {
+    let minutes = config::get().listening_session.end_after_pause_mins;
+    (minutes > 0).then(|| Duration::from_mins(minutes))
+}
+
+pub fn init() {
+    if end_after_pause().is_none() {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("listening-session-thread", |token| {
+        while token.sleep(CHECK_INTERVAL) {
+            let Some(end_after) = end_after_pause() else {
+                continue;
+            };
+            let ended = CURRENT.lock().ok().and_then(|mut current| {
+                current.take_if(|session| session.idle_too_long(end_after))
+            });
+            if let Some(session) = ended {
+                report(session);
+            }
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动收听会话线程: {e}");
+    }
+}
+
+pub fn update_play_state(status: PlaybackStatus) {
+    let Some(end_after) = end_after_pause() else {
+        return;
+    };
+    let Ok(mut current) = CURRENT.lock() else {
+        return;
+    };
+
+    match status {
+        PlaybackStatus::Playing => {
+            // 检查线程可能还没来得及结束上一个会话
+            if let Some(session) = current.take_if(|session| session.idle_too_long(end_after)) {
+                report(session);
+            }
+            let session = current.get_or_insert_with(|| {
+                debug!("开始新的收听会话");
+                ListeningSession::new()
+            });
+            session.paused_since = None;
+            session.playing_since.get_or_insert_with(Instant::now);
+        }
+        PlaybackStatus::Paused => {
+            if let Some(session) = current.as_mut() {
+                session.pause();
+            }
+        }
+    }
+}
+
+/// 检测到新的播放时调用，重复发送的元数据不计入
+pub fn note_new_play() {
+    if end_after_pause().is_none() {
+        return;
+    }
+    if let Ok(mut current) = CURRENT.lock() {
+        current
+            .get_or_insert_with(ListeningSession::new)
+            .tracks_played += 1;
+    }
+}
+
+/// 插件关闭时结束当前会话
+pub fn finish() {
+    let session = CURRENT.lock().ok().and_then(|mut current| current.take());
+    if let Some(session) = session {
+        report(session);
+    }
+}
+
+fn report(mut session: ListeningSession) {
+    session.pause();
+    if session.listened < MIN_LISTENED {
+        debug!(listened = ?session.listened, "收听会话太短，不发送总结");
+        return;
+    }
+
+    info!(
+        listened = ?session.listened,
+        tracks_played = session.tracks_played,
+        "收听会话已结束"
+    );
+    smtc_core::dispatch_event(&SmtcEvent::SessionSummary {
+        started_at: unix_millis(session.started_at),
+        ended_at: unix_millis(session.last_played_at),
+        tracks_played: session.tracks_played,
+        listened_ms: session.listened.as_millis() as u64,
+    });
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
@@ -1,12 +1,50 @@
 use std::{
+    any::Any,
+    backtrace::Backtrace,
+    collections::{
+        HashMap,
+        VecDeque,
+    },
     fmt::Write,
-    fs,
-    path::PathBuf,
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::{
+        self,
+        Write as _,
+    },
+    mem,
+    panic::{
+        self,
+        PanicHookInfo,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
     str::FromStr,
     sync::{
         LazyLock,
         Mutex,
+        Once,
         OnceLock,
+        RwLock,
+        mpsc::{
+            self,
+            Receiver,
+            RecvTimeoutError,
+            SyncSender,
+        },
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
     },
 };
 
@@ -16,21 +54,54 @@ use anyhow::{
 };
 use serde::Serialize;
 use tracing::{
+    Level,
     Subscriber,
     error,
+    info,
+    span,
     trace,
+    warn,
 };
 use tracing_appender::{
-    non_blocking::WorkerGuard,
-    rolling::RollingFileAppender,
+    non_blocking::{
+        NonBlocking,
+        WorkerGuard,
+    },
+    rolling::{
+        RollingFileAppender,
+        Rotation,
+    },
 };
 use tracing_subscriber::{
     EnvFilter,
     Layer,
     filter::LevelFilter,
+    fmt::{
+        MakeWriter,
+        writer::OptionalWriter,
+    },
     layer::SubscriberExt,
+    reload,
     util::SubscriberInitExt,
 };
+use windows::{
+    Win32::System::Diagnostics::Debug::OutputDebugStringW,
+    core::HSTRING,
+};
+
+use crate::{
+    config::{
+        self,
+        LogFormat,
+        LogRotation,
+        LoggingConfig,
+    },
+    model::LogRetentionPayload,
+    redact::{
+        self,
+        RedactingWriter,
+    },
+};
 
 #[derive(Serialize)]
 struct LogEntry {
@@ -51,8 +122,21 @@ unsafe impl Send for LoggingCallback {}
 static LOGGING_CALLBACK: LazyLock<Mutex<Option<LoggingCallback>>> =
     LazyLock::new(|| Mutex::new(None));
 
+/// 批量发送日志的间隔
+const LOG_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+/// 缓冲区中的日志达到此数量时立即发送，不再等待下一个间隔
+const LOG_BATCH_MAX_ENTRIES: usize = 64;
+
+/// 等待发送到前端的日志
+///
+/// 每条日志都提交一个渲染线程任务的话，trace 级别下 (例如连续跳转进度时)
+/// 会塞满 CEF 的任务队列，所以这里先攒起来再一次性发送
+static PENDING_LOGS: LazyLock<Mutex<Vec<LogEntry>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+static FLUSH_THREAD: Once = Once::new();
+
 pub fn register_callback(v8_function: cef_safe::CefV8Value) {
     clear_callback();
+    start_flush_thread();
 
     let callback_result = cef_safe::CefV8Context::current().map(|v8_context| LoggingCallback {
         v8_context,
@@ -89,57 +173,424 @@ where
         event: &tracing::Event<'_>,
         _ctx: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        let maybe_context = match LOGGING_CALLBACK.lock() {
-            Ok(guard) => guard.as_ref().map(|cb| cb.v8_context.clone()),
+        let has_callback = match LOGGING_CALLBACK.lock() {
+            Ok(guard) => guard.is_some(),
+            Err(_) => return,
+        };
+
+        if !has_callback {
+            return;
+        }
+
+        let metadata = event.metadata();
+        let message = MessageVisitor::format_event(event);
+
+        let log_entry = LogEntry {
+            level: metadata.level().to_string(),
+            message,
+            target: metadata.target().to_string(),
+        };
+
+        let should_flush = match PENDING_LOGS.lock() {
+            Ok(mut pending) => {
+                pending.push(log_entry);
+                pending.len() >= LOG_BATCH_MAX_ENTRIES
+            }
             Err(_) => return,
         };
 
-        if let Some(context) = maybe_context {
-            let metadata = event.metadata();
-            let mut message_visitor = MessageVisitor::default();
-            event.record(&mut message_visitor);
+        if should_flush {
+            flush_pending_logs();
+        }
+    }
+}
+
+fn start_flush_thread() {
+    FLUSH_THREAD.call_once(|| {
+        let spawn_result = thread::Builder::new()
+            .name("log-flush-thread".into())
+            .spawn(|| {
+                loop {
+                    thread::sleep(LOG_BATCH_INTERVAL);
+                    flush_pending_logs();
+                }
+            });
+
+        if let Err(e) = spawn_result {
+            eprintln!("[InfLink-rs] 无法启动日志发送线程: {e}");
+        }
+    });
+}
+
+/// 将缓冲区中的日志作为一个 JSON 数组，通过一次 JS 回调发送到前端
+fn flush_pending_logs() {
+    let entries = match PENDING_LOGS.lock() {
+        Ok(mut pending) => mem::take(&mut *pending),
+        Err(_) => return,
+    };
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let maybe_context = match LOGGING_CALLBACK.lock() {
+        Ok(guard) => guard.as_ref().map(|cb| cb.v8_context.clone()),
+        Err(_) => return,
+    };
+
+    let Some(context) = maybe_context else {
+        return;
+    };
+
+    let Ok(logs_json) = serde_json::to_string(&entries) else {
+        return;
+    };
+
+    let post_result = cef_safe::renderer_post_task_in_v8_ctx(context, move || {
+        let Ok(guard) = LOGGING_CALLBACK.lock() else {
+            return;
+        };
+
+        if let Some(callback) = guard.as_ref() {
+            match cef_safe::CefV8Value::try_from_str(&logs_json) {
+                Ok(arg) => {
+                    if let Err(e) = callback.v8_function.execute_function(None, vec![arg]) {
+                        eprintln!("[InfLink-rs] JS 日志回调执行失败: {e}");
+                    }
+                }
+                Err(e) => {
+                    eprintln!("[InfLink-rs] 创建 V8 字符串失败: {e}");
+                }
+            }
+        }
+    });
+
+    if let Err(e) = post_result {
+        eprintln!("[InfLink-rs] 提交日志任务到 CEF 失败: {e}");
+    }
+}
+
+/// 将日志写入 `OutputDebugStringW`
+///
+/// 附加了 `DebugView` 或 `WinDbg` 的开发者可以直接看到与网易云/CEF 输出交织在一起的插件日志
+pub struct DebugOutputLayer;
+
+impl<S> Layer<S> for DebugOutputLayer
+where
+    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+        let message = MessageVisitor::format_event(event);
+
+        let line = format!(
+            "[InfLink-rs] {} {}: {}\n",
+            metadata.level(),
+            metadata.target(),
+            message
+        );
+
+        unsafe { OutputDebugStringW(&HSTRING::from(line)) };
+    }
+}
+
+/// 转发队列的容量，队列满时新的日志会被丢弃
+const LOG_FORWARD_QUEUE_CAPACITY: usize = 1024;
+const LOG_FORWARD_RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 将日志实时转发到一个 WebSocket 端点
+///
+/// 每条日志作为一个 JSON 文本消息发送，开发者可以在不附加调试器的情况下查看实时日志。
+/// 连接断开期间产生的日志会被丢弃
+pub struct LogForwardLayer {
+    tx: SyncSender<String>,
+}
 
-            let log_entry = LogEntry {
-                level: metadata.level().to_string(),
-                message: message_visitor.message,
-                target: metadata.target().to_string(),
-            };
+impl LogForwardLayer {
+    pub fn new(url: String) -> Self {
+        let (tx, rx) = mpsc::sync_channel(LOG_FORWARD_QUEUE_CAPACITY);
 
-            if let Ok(log_json) = serde_json::to_string(&log_entry) {
-                let post_result = cef_safe::renderer_post_task_in_v8_ctx(context, move || {
-                    let Ok(guard) = LOGGING_CALLBACK.lock() else {
+        if let Err(e) = thread::Builder::new()
+            .name("log-forward-thread".into())
+            .spawn(move || run_log_forward_loop(&url, &rx))
+        {
+            eprintln!("[InfLink-rs] 无法启动日志转发线程: {e}");
+        }
+
+        Self { tx }
+    }
+}
+
+impl<S> Layer<S> for LogForwardLayer
+where
+    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+
+        // 避免 WebSocket 库自身的日志被再次转发
+        if metadata.target().starts_with("tungstenite") {
+            return;
+        }
+
+        let message = MessageVisitor::format_event(event);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let entry = RecentLogEntry {
+            timestamp,
+            level: metadata.level().to_string(),
+            message,
+            target: metadata.target().to_string(),
+        };
+
+        if let Ok(json) = serde_json::to_string(&entry) {
+            let _ = self.tx.try_send(json);
+        }
+    }
+}
+
+fn run_log_forward_loop(url: &str, rx: &Receiver<String>) {
+    let mut failure_reported = false;
+
+    loop {
+        match tungstenite::connect(url) {
+            Ok((mut socket, _)) => {
+                info!("已连接到日志转发端点 {url}");
+                failure_reported = false;
+
+                loop {
+                    let Ok(line) = rx.recv() else {
+                        let _ = socket.close(None);
                         return;
                     };
 
-                    if let Some(callback) = guard.as_ref() {
-                        match cef_safe::CefV8Value::try_from_str(&log_json) {
-                            Ok(arg) => {
-                                if let Err(e) =
-                                    callback.v8_function.execute_function(None, vec![arg])
-                                {
-                                    eprintln!("[InfLink-rs] JS 日志回调执行失败: {e}");
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("[InfLink-rs] 创建 V8 字符串失败: {e}");
-                            }
-                        }
+                    if let Err(e) = socket.send(tungstenite::Message::text(line)) {
+                        warn!("日志转发连接已断开: {e}");
+                        break;
                     }
-                });
-
-                if let Err(e) = post_result {
-                    eprintln!("[InfLink-rs] 提交日志任务到 CEF 失败: {e}");
                 }
             }
+            Err(e) => {
+                if !failure_reported {
+                    warn!("无法连接到日志转发端点 {url}: {e}");
+                    failure_reported = true;
+                }
+            }
+        }
+
+        // 等待重连期间丢弃积压的日志，避免重连后发送大量过时的日志
+        let deadline = Instant::now() + LOG_FORWARD_RECONNECT_INTERVAL;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// 内存中保留的最近日志条数
+const RECENT_LOGS_CAPACITY: usize = 4000;
+
+#[derive(Serialize, Clone)]
+pub struct RecentLogEntry {
+    /// Unix 时间戳 (毫秒)
+    timestamp: u64,
+    level: String,
+    message: String,
+    target: String,
+}
+
+/// 最近的日志，供设置界面查看或附加到问题反馈中
+static RECENT_LOGS: LazyLock<Mutex<VecDeque<(Level, RecentLogEntry)>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LOGS_CAPACITY)));
+
+pub struct RecentLogsLayer;
+
+impl<S> Layer<S> for RecentLogsLayer
+where
+    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let metadata = event.metadata();
+        let message = MessageVisitor::format_event(event);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let entry = RecentLogEntry {
+            timestamp,
+            level: metadata.level().to_string(),
+            message,
+            target: metadata.target().to_string(),
+        };
+
+        if let Ok(mut logs) = RECENT_LOGS.lock() {
+            if logs.len() >= RECENT_LOGS_CAPACITY {
+                logs.pop_front();
+            }
+            logs.push_back((*metadata.level(), entry));
         }
     }
 }
 
+/// 获取最近的日志，按时间从旧到新排列
+///
+/// `level` 为最低日志级别，`limit` 为最多返回的条数 (从最新的日志开始计算)
+pub fn recent_logs(
+    level: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<RecentLogEntry>, String> {
+    let level_filter = match level {
+        Some(level_str) => LevelFilter::from_str(level_str)
+            .map_err(|e| format!("无效的日志级别 '{level_str}': {e}"))?,
+        None => LevelFilter::TRACE,
+    };
+
+    let mut result: Vec<RecentLogEntry> = RECENT_LOGS
+        .lock()
+        .map_err(|e| format!("RECENT_LOGS 锁中毒: {e}"))?
+        .iter()
+        .rev()
+        .filter(|(entry_level, _)| *entry_level <= level_filter)
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|(_, entry)| entry.clone())
+        .collect();
+    result.reverse();
+
+    Ok(result)
+}
+
+#[derive(Default)]
+struct SpanStats {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanTiming {
+    target: &'static str,
+    name: &'static str,
+    count: u64,
+    total_ms: f64,
+    avg_ms: f64,
+    max_ms: f64,
+}
+
+/// 按 (target, name) 聚合的 span 耗时
+static SPAN_TIMINGS: LazyLock<Mutex<HashMap<(&'static str, &'static str), SpanStats>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct SpanStart(Instant);
+
+/// 记录每个 span 从创建到关闭的耗时，用于分析性能问题
+///
+/// 只有在配置中开启 `logging.span_timing` 时才会启用
+pub struct SpanTimingLayer;
+
+impl<S> Layer<S> for SpanTimingLayer
+where
+    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        _attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span
+            .extensions()
+            .get::<SpanStart>()
+            .map(|start| start.0.elapsed())
+        else {
+            return;
+        };
+
+        let metadata = span.metadata();
+        if let Ok(mut timings) = SPAN_TIMINGS.lock() {
+            let stats = timings
+                .entry((metadata.target(), metadata.name()))
+                .or_default();
+            stats.count += 1;
+            stats.total += elapsed;
+            stats.max = stats.max.max(elapsed);
+        }
+    }
+}
+
+/// 获取聚合后的 span 耗时，按总耗时从高到低排列
+///
+/// 未启用 span 计时时返回 `None`
+pub fn span_timings() -> Option<Vec<SpanTiming>> {
+    if !config::get().logging.span_timing {
+        return None;
+    }
+
+    let timings = SPAN_TIMINGS.lock().ok()?;
+    let mut result: Vec<SpanTiming> = timings
+        .iter()
+        .map(|(&(target, name), stats)| {
+            let total_ms = stats.total.as_secs_f64() * 1000.0;
+            SpanTiming {
+                target,
+                name,
+                count: stats.count,
+                total_ms,
+                avg_ms: total_ms / stats.count.max(1) as f64,
+                max_ms: stats.max.as_secs_f64() * 1000.0,
+            }
+        })
+        .collect();
+    drop(timings);
+
+    result.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    Some(result)
+}
+
 #[derive(Default)]
 struct MessageVisitor {
     message: String,
 }
 
+impl MessageVisitor {
+    /// 将事件的消息和字段格式化为一行文本，并隐藏其中的敏感信息
+    fn format_event(event: &tracing::Event<'_>) -> String {
+        let mut visitor = Self::default();
+        event.record(&mut visitor);
+        redact::redact(&visitor.message).into_owned()
+    }
+}
+
 impl tracing::field::Visit for MessageVisitor {
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
         if field.name() == "message" {
@@ -164,68 +615,408 @@ impl tracing::field::Visit for MessageVisitor {
     }
 }
 
-static LOG_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
-static FRONTEND_FILTER_LEVEL: LazyLock<Mutex<LevelFilter>> =
-    LazyLock::new(|| Mutex::new(LevelFilter::INFO));
+struct FileWriter {
+    writer: NonBlocking,
+    _guard: WorkerGuard,
+    retention: LogRetention,
+}
 
-struct FrontendFilter;
+/// 当前的文件日志写入器
+///
+/// 非阻塞写入器只有在 `WorkerGuard` 被 drop 时才会强制写入缓冲区，所以发生 panic 时
+/// 会换上一个继续写入当前文件的写入器并 drop 旧的 guard，以免日志恰好在崩溃之前中断
+static FILE_WRITER: RwLock<Option<FileWriter>> = RwLock::new(None);
 
-impl<S: Subscriber> tracing_subscriber::layer::Filter<S> for FrontendFilter {
-    fn enabled(
-        &self,
-        meta: &tracing::Metadata<'_>,
-        _ctx: &tracing_subscriber::layer::Context<'_, S>,
-    ) -> bool {
-        FRONTEND_FILTER_LEVEL
-            .lock()
-            .is_ok_and(|guard| meta.level() <= &*guard)
+const LOG_FILE_PREFIX: &str = "inflink-rs";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// 日志文件的轮转和保留策略
+#[derive(Debug, Clone, Copy)]
+struct LogRetention {
+    rotation: LogRotation,
+    max_files: usize,
+    max_file_size: u64,
+}
+
+impl LogRetention {
+    fn from_config(config: &LoggingConfig) -> Self {
+        Self {
+            rotation: config.rotation,
+            max_files: config.max_log_files.max(1),
+            max_file_size: config.max_file_size_mb.max(1) * 1024 * 1024,
+        }
     }
 }
 
-pub fn set_frontend_log_level(level_str: &str) -> Result<(), String> {
-    let level = LevelFilter::from_str(level_str)
-        .map_err(|e| format!("无效的日志级别 '{level_str}': {e}"))?;
+fn build_file_writer(log_dir: &Path, retention: LogRetention) -> Option<FileWriter> {
+    let appender: Box<dyn io::Write + Send> = match retention.rotation {
+        LogRotation::Size => Box::new(SizeRollingWriter::new(log_dir, retention).ok()?),
+        LogRotation::Hourly => rolling_appender(log_dir, Rotation::HOURLY, retention)?,
+        LogRotation::Daily => rolling_appender(log_dir, Rotation::DAILY, retention)?,
+    };
+    Some(non_blocking_writer(appender, retention))
+}
 
-    {
-        let mut guard = FRONTEND_FILTER_LEVEL
-            .lock()
-            .map_err(|e| format!("FRONTEND_FILTER_LEVEL 锁中毒: {e}"))?;
-        *guard = level;
+/// 重新创建写入器，但继续写入当前的日志文件，而不是像 [`build_file_writer`] 那样
+/// 按大小轮转时总是创建新文件
+fn reopen_file_writer(log_dir: &Path, retention: LogRetention) -> Option<FileWriter> {
+    let appender: Box<dyn io::Write + Send> = match retention.rotation {
+        LogRotation::Size => Box::new(SizeRollingWriter::resume(log_dir, retention).ok()?),
+        // 按时间轮转的写入器本来就会追加到当前时间段的文件
+        LogRotation::Hourly => rolling_appender(log_dir, Rotation::HOURLY, retention)?,
+        LogRotation::Daily => rolling_appender(log_dir, Rotation::DAILY, retention)?,
+    };
+    Some(non_blocking_writer(appender, retention))
+}
+
+fn rolling_appender(
+    log_dir: &Path,
+    rotation: Rotation,
+    retention: LogRetention,
+) -> Option<Box<dyn io::Write + Send>> {
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(retention.max_files)
+        .build(log_dir)
+        .ok()?;
+    Some(Box::new(appender))
+}
+
+fn non_blocking_writer(appender: Box<dyn io::Write + Send>, retention: LogRetention) -> FileWriter {
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    FileWriter {
+        writer,
+        _guard: guard,
+        retention,
     }
+}
 
-    trace!("前端日志级别已设置为 {level_str}");
-    Ok(())
+/// 按文件大小轮转的日志写入器
+///
+/// 文件名中的时间戳为创建时的 Unix 时间戳 (毫秒)，例如 `inflink-rs.1700000000000.log`
+struct SizeRollingWriter {
+    log_dir: PathBuf,
+    retention: LogRetention,
+    file: File,
+    written: u64,
 }
 
-fn cleanup_old_logs(log_dir: &PathBuf, max_files: usize) {
-    if let Ok(entries) = fs::read_dir(log_dir) {
-        let mut log_files: Vec<_> = entries
-            .filter_map(Result::ok)
-            .filter(|e| {
-                e.path().is_file()
-                    && e.file_name().to_string_lossy().starts_with("inflink-rs.")
-                    && e.file_name().to_string_lossy().ends_with(".log")
-            })
-            .collect();
+impl SizeRollingWriter {
+    fn new(log_dir: &Path, retention: LogRetention) -> io::Result<Self> {
+        Ok(Self {
+            log_dir: log_dir.to_path_buf(),
+            retention,
+            file: Self::create_file(log_dir)?,
+            written: 0,
+        })
+    }
 
-        if log_files.len() <= max_files {
-            return;
+    /// 继续写入目录中最新的日志文件，没有日志文件时创建新文件
+    ///
+    /// 文件已经写满时下一次写入会照常轮转
+    fn resume(log_dir: &Path, retention: LogRetention) -> io::Result<Self> {
+        let latest = list_log_files(log_dir).last().map(fs::DirEntry::path);
+        let Some(file) = latest.and_then(|path| OpenOptions::new().append(true).open(path).ok())
+        else {
+            return Self::new(log_dir, retention);
+        };
+
+        Ok(Self {
+            log_dir: log_dir.to_path_buf(),
+            retention,
+            written: file.metadata().map_or(0, |m| m.len()),
+            file,
+        })
+    }
+
+    fn create_file(log_dir: &Path) -> io::Result<File> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_dir.join(format!("{LOG_FILE_PREFIX}.{timestamp}.{LOG_FILE_SUFFIX}")))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = Self::create_file(&self.log_dir)?;
+        self.written = 0;
+        cleanup_old_logs(&self.log_dir, self.retention.max_files);
+        Ok(())
+    }
+}
+
+impl io::Write for SizeRollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.retention.max_file_size {
+            self.rotate()?;
         }
 
-        log_files.sort_by_key(fs::DirEntry::file_name);
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
 
-        let files_to_delete_count = log_files.len() - max_files;
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
 
-        for entry in log_files.iter().take(files_to_delete_count) {
-            let path = entry.path();
-            if let Err(e) = fs::remove_file(&path) {
-                error!("删除旧日志文件 {path:?} 失败: {e}");
-            }
+/// 总是写入到 [`FILE_WRITER`] 中当前的写入器
+struct SwappableFileWriter;
+
+impl<'a> MakeWriter<'a> for SwappableFileWriter {
+    type Writer = RedactingWriter<OptionalWriter<NonBlocking>>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        let writer = FILE_WRITER
+            .read()
+            .ok()
+            .and_then(|file_writer| file_writer.as_ref().map(|w| w.writer.clone()))
+            .map_or_else(OptionalWriter::none, OptionalWriter::some);
+        RedactingWriter(writer)
+    }
+}
+
+fn install_panic_hook() {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        write_panic_to_log(info);
+        previous_hook(info);
+    }));
+}
+
+/// 取出 `catch_unwind` 捕获到的 panic 信息
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&'static str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("未知类型的 Panic")
+}
+
+/// 同步写入缓冲区中的日志，然后把 panic 信息和调用栈追加到当前的日志文件
+fn write_panic_to_log(info: &PanicHookInfo<'_>) {
+    let Some(log_dir) = LOG_DIR.get() else {
+        return;
+    };
+
+    // panic 可能发生在持有锁的时候，这里不能阻塞
+    let Ok(mut file_writer) = FILE_WRITER.try_write() else {
+        return;
+    };
+
+    // 先写入旧写入器中的日志和 panic 信息，再换上继续写入同一个文件的写入器，
+    // panic 被 catch_unwind 捕获后插件会继续运行，不能每次 panic 都创建一个新文件
+    if let Some(old_writer) = file_writer.take() {
+        let retention = old_writer.retention;
+        drop(old_writer);
+        append_panic_report(log_dir, info);
+        *file_writer = reopen_file_writer(log_dir, retention);
+        drop(file_writer);
+        cleanup_old_logs(log_dir, retention.max_files);
+    }
+}
+
+fn append_panic_report(log_dir: &Path, info: &PanicHookInfo<'_>) {
+    let Some(log_file) = list_log_files(log_dir).last().map(fs::DirEntry::path) else {
+        return;
+    };
+
+    let current_thread = thread::current();
+    let thread_name = current_thread.name().unwrap_or("<unnamed>");
+    let location = info
+        .location()
+        .map_or_else(|| "未知位置".to_string(), ToString::to_string);
+    let message = redact::redact(info.payload_as_str().unwrap_or("未知类型的 Panic"));
+    let backtrace = Backtrace::force_capture();
+
+    let report = format!(
+        "\n========== PANIC ==========\n线程 '{thread_name}' 在 {location} 发生 Panic: \
+         {message}\n调用栈:\n{backtrace}\n===========================\n"
+    );
+
+    if let Ok(mut file) = OpenOptions::new().append(true).open(&log_file) {
+        let _ = file.write_all(report.as_bytes());
+        let _ = file.flush();
+    }
+}
+
+type FilterReloader = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// 前端日志和文件日志过滤器的热重载句柄
+struct LogFilterHandles {
+    frontend: FilterReloader,
+    file: Option<FilterReloader>,
+}
+
+static LOG_FILTER_HANDLES: OnceLock<LogFilterHandles> = OnceLock::new();
+
+/// 设置后端日志过滤指令
+///
+/// 支持 `EnvFilter` 格式的指令，既可以是单独的级别 (`info`)，也可以针对特定目标
+/// (`backend::discord=trace,backend=info`)
+///
+/// 指令会完整地应用到前端日志。文件日志默认保留所有级别以便排查问题，
+/// 因此只会应用其中带目标的指令
+pub fn set_frontend_log_level(directives: &str) -> Result<(), String> {
+    let handles = LOG_FILTER_HANDLES
+        .get()
+        .ok_or_else(|| "日志系统未初始化".to_string())?;
+
+    let frontend_filter = EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| format!("无效的日志指令 '{directives}': {e}"))?;
+    let file_filter = file_filter_from_directives(directives)?;
+
+    (handles.frontend)(frontend_filter)?;
+    if let Some(reload_file) = &handles.file {
+        reload_file(file_filter)?;
+    }
+
+    trace!("后端日志指令已设置为 {directives}");
+    Ok(())
+}
+
+fn file_filter_from_directives(directives: &str) -> Result<EnvFilter, String> {
+    let file_directives = directives
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty() && LevelFilter::from_str(d).is_err())
+        .fold(String::from("trace"), |mut acc, d| {
+            acc.push(',');
+            acc.push_str(d);
+            acc
+        });
+
+    EnvFilter::builder()
+        .parse(&file_directives)
+        .map_err(|e| format!("无效的文件日志指令 '{file_directives}': {e}"))
+}
+
+/// 列出目录中的所有日志文件，按修改时间从旧到新排列
+///
+/// 不同轮转方式的文件名格式不同，所以这里只匹配前缀和后缀，并按修改时间而不是文件名排序，
+/// 这样切换轮转方式后旧格式的文件也会被正确清理
+fn list_log_files(log_dir: &Path) -> Vec<fs::DirEntry> {
+    let Ok(entries) = fs::read_dir(log_dir) else {
+        return Vec::new();
+    };
+
+    let mut log_files: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            e.path().is_file()
+                && name.starts_with(&format!("{LOG_FILE_PREFIX}."))
+                && name.ends_with(&format!(".{LOG_FILE_SUFFIX}"))
+        })
+        .collect();
+
+    log_files.sort_by_cached_key(|e| {
+        (
+            e.metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH),
+            e.file_name(),
+        )
+    });
+    log_files
+}
+
+fn cleanup_old_logs(log_dir: &Path, max_files: usize) {
+    let log_files = list_log_files(log_dir);
+
+    if log_files.len() <= max_files {
+        return;
+    }
+
+    let files_to_delete_count = log_files.len() - max_files;
+
+    for entry in log_files.iter().take(files_to_delete_count) {
+        let path = entry.path();
+        if let Err(e) = fs::remove_file(&path) {
+            error!("删除旧日志文件 {path:?} 失败: {e}");
         }
     }
 }
 
+/// 当前正在写入的日志目录，只有文件日志初始化成功时才会被设置
+static LOG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLocation {
+    log_dir: PathBuf,
+    current_file: Option<PathBuf>,
+}
+
+pub fn log_location() -> Result<LogLocation, String> {
+    let log_dir = LOG_DIR.get().ok_or_else(|| "文件日志未启用".to_string())?;
+
+    Ok(LogLocation {
+        log_dir: log_dir.clone(),
+        current_file: list_log_files(log_dir).last().map(fs::DirEntry::path),
+    })
+}
+
+/// 在资源管理器中打开日志目录
+pub fn open_log_folder() -> Result<(), String> {
+    let log_dir = LOG_DIR.get().ok_or_else(|| "文件日志未启用".to_string())?;
+
+    Command::new("explorer")
+        .arg(log_dir)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("打开日志目录 {} 失败: {e}", log_dir.display()))
+}
+
+/// 修改日志的轮转和保留策略
+///
+/// 新的策略会立即生效并写入配置文件
+pub fn set_log_retention(payload: &LogRetentionPayload) -> Result<(), String> {
+    let log_dir = LOG_DIR.get().ok_or_else(|| "文件日志未启用".to_string())?;
+
+    if payload.max_log_files == 0 {
+        return Err("最多保留的日志文件数必须大于 0".to_string());
+    }
+    if payload.max_file_size_mb == Some(0) {
+        return Err("日志文件的最大大小必须大于 0".to_string());
+    }
+
+    let mut logging = config::get().logging;
+    logging.rotation = payload.rotation;
+    logging.max_log_files = payload.max_log_files;
+    if let Some(max_file_size_mb) = payload.max_file_size_mb {
+        logging.max_file_size_mb = max_file_size_mb;
+    }
+    let retention = LogRetention::from_config(&logging);
+
+    let new_writer =
+        build_file_writer(log_dir, retention).ok_or_else(|| "创建日志文件失败".to_string())?;
+    let old_writer = FILE_WRITER
+        .write()
+        .map_err(|e| format!("FILE_WRITER 锁中毒: {e}"))?
+        .replace(new_writer);
+    drop(old_writer);
+
+    cleanup_old_logs(log_dir, retention.max_files);
+    info!("日志保留策略已更新: {retention:?}");
+
+    config::update(|config| config.logging = logging)
+}
+
 pub fn init() -> Result<()> {
+    let logging = config::get().logging;
+    let retention = LogRetention::from_config(&logging);
+    redact::set_enabled(logging.redact);
+
     let directive = "backend=trace"
         .parse()
         .context("硬编码的日志指令无效，这不应该发生")?;
@@ -235,59 +1026,109 @@ pub fn init() -> Result<()> {
         .add_directive(directive);
 
     let mut log_path: Option<PathBuf> = None;
+    let mut file_reloader: Option<FilterReloader> = None;
+
+    let log_dir = logging.log_dir.clone().or_else(config::data_dir);
 
-    let file_layer = dirs::data_dir().and_then(|mut path| {
-        path.push("InfLink-rs");
+    let file_layer = log_dir.and_then(|path| {
         log_path = Some(path.clone());
 
         fs::create_dir_all(&path).ok()?;
 
-        let rotation = tracing_appender::rolling::Rotation::DAILY;
+        let file_writer = build_file_writer(&path, retention)?;
+        match FILE_WRITER.write() {
+            Ok(mut guard) => *guard = Some(file_writer),
+            Err(e) => {
+                eprintln!("[InfLink-rs] FILE_WRITER 锁中毒: {e}");
+                return None;
+            }
+        }
 
-        let file_appender = RollingFileAppender::builder()
-            .rotation(rotation)
-            .filename_prefix("inflink-rs")
-            .filename_suffix("log")
-            .max_log_files(7)
-            .build(&path)
-            .ok()?;
+        let (file_filter, file_handle) = reload::Layer::new(EnvFilter::new("trace"));
+        file_reloader = Some(Box::new(move |filter| {
+            file_handle.reload(filter).map_err(|e| e.to_string())
+        }));
 
-        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+        let fmt_layer = tracing_subscriber::fmt::layer()
+            .with_writer(SwappableFileWriter)
+            .with_ansi(false);
 
-        if LOG_GUARD.set(guard).is_err() {
-            error!("Logger Guard 已经被初始化，不应重复调用 init()");
-        }
+        let fmt_layer = match logging.format {
+            LogFormat::Text => fmt_layer.boxed(),
+            // 每行一个 JSON 对象，方便用 jq 等工具处理
+            LogFormat::Json => fmt_layer.json().boxed(),
+        };
 
-        Some(
-            tracing_subscriber::fmt::layer()
-                .with_writer(non_blocking)
-                .with_ansi(false)
-                .with_filter(EnvFilter::new("trace")),
-        )
+        Some(fmt_layer.with_filter(file_filter))
     });
 
-    let frontend_layer = FrontendTracingLayer.with_filter(FrontendFilter);
+    let file_layer_enabled = file_layer.is_some();
+
+    let (frontend_filter, frontend_handle) = reload::Layer::new(EnvFilter::new("info"));
+    let frontend_layer = FrontendTracingLayer.with_filter(frontend_filter);
+
+    let handles = LogFilterHandles {
+        frontend: Box::new(move |filter| frontend_handle.reload(filter).map_err(|e| e.to_string())),
+        file: file_reloader,
+    };
+    if LOG_FILTER_HANDLES.set(handles).is_err() {
+        error!("日志过滤器句柄已经被初始化，不应重复调用 init()");
+    }
 
     tracing_subscriber::registry()
         .with(default_filter)
         .with(file_layer)
         .with(frontend_layer)
+        .with(RecentLogsLayer)
+        .with(logging.span_timing.then_some(SpanTimingLayer))
+        .with(logging.debug_output.then_some(DebugOutputLayer))
+        .with(logging.forward_url.map(LogForwardLayer::new))
         .try_init()
         .context("无法初始化 Tracing subscriber")?;
 
     trace!("Tracing subscriber 已初始化");
 
     if let Some(path) = log_path {
-        cleanup_old_logs(&path, 7);
+        cleanup_old_logs(&path, retention.max_files);
+
+        if file_layer_enabled {
+            let _ = LOG_DIR.set(path);
+        }
     }
 
+    install_panic_hook();
+
     Ok(())
 }
 
+pub fn has_callback() -> bool {
+    LOGGING_CALLBACK.lock().is_ok_and(|guard| guard.is_some())
+}
+
+/// 回调所在的 V8 上下文已被销毁时清除回调，必须在渲染线程上调用
+pub fn release_callback_if_context_invalid() {
+    let released = LOGGING_CALLBACK.lock().is_ok_and(|mut guard| {
+        guard
+            .take_if(|cb| !cb.v8_context.is_valid() || !cb.v8_function.is_valid())
+            .is_some()
+    });
+
+    if released {
+        if let Ok(mut pending) = PENDING_LOGS.lock() {
+            pending.clear();
+        }
+        info!("日志回调所在的 V8 上下文已被销毁，已清除回调");
+    }
+}
+
 pub fn clear_callback() {
     if let Ok(mut guard) = LOGGING_CALLBACK.lock()
         && guard.is_some()
     {
         *guard = None;
     }
+
+    if let Ok(mut pending) = PENDING_LOGS.lock() {
+        pending.clear();
+    }
 }
@@ -0,0 +1,134 @@
+use std::{
+    fs,
+    io,
+    path::Path,
+    sync::Mutex,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use serde::Serialize;
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+
+use crate::{
+    clock,
+    config,
+    tasks,
+    updater,
+};
+
+/// 启动后等待一段时间再清理，避免和网易云的启动过程抢占磁盘
+const STARTUP_DELAY: Duration = Duration::from_mins(5);
+const INTERVAL: Duration = Duration::from_hours(24);
+/// 只删除修改时间早于这个时长的文件，避免删掉正在写入的更新
+const MIN_AGE: Duration = Duration::from_hours(24);
+
+/// 最近一次清理的结果
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub finished_at_ms: i64,
+    pub removed_files: usize,
+    pub freed_bytes: u64,
+    /// 删除失败的文件数，失败的文件会在下次清理时重试
+    pub failed_files: usize,
+}
+
+static LAST_REPORT: Mutex<Option<MaintenanceReport>> = Mutex::new(None);
+
+/// 启动每天一次的数据目录清理，暂停插件后恢复时会再次调用
+pub fn init() {
+    let spawn_result = tasks::spawn("maintenance-thread", |token| {
+        let mut delay = STARTUP_DELAY;
+        while token.sleep(delay) {
+            run();
+            delay = INTERVAL;
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动数据目录清理线程: {e}");
+    }
+}
+
+/// 最近一次清理的结果，还没有清理过时为空
+pub fn last_report() -> Option<MaintenanceReport> {
+    LAST_REPORT.lock().ok().and_then(|report| *report)
+}
+
+fn run() {
+    let Some(data_dir) = config::data_dir() else {
+        return;
+    };
+
+    let mut report = MaintenanceReport::default();
+    prune_staged_updates(&data_dir.join(updater::STAGING_DIR_NAME), &mut report);
+    report.finished_at_ms = clock::unix_now_ms();
+
+    if report.removed_files > 0 || report.failed_files > 0 {
+        info!(
+            removed = report.removed_files,
+            freed_bytes = report.freed_bytes,
+            failed = report.failed_files,
+            "数据目录清理完成"
+        );
+    } else {
+        debug!("数据目录清理完成，没有需要删除的文件");
+    }
+
+    if let Ok(mut last) = LAST_REPORT.lock() {
+        *last = Some(report);
+    }
+}
+
+/// 删除以前下载的更新文件，本次运行中下载且还没有替换的更新会被保留
+fn prune_staged_updates(dir: &Path, report: &mut MaintenanceReport) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("读取更新目录 {} 失败: {e}", dir.display());
+            return;
+        }
+    };
+
+    let staged = updater::staged_path();
+    let now = SystemTime::now();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "plugin") || staged.as_ref() == Some(&path) {
+            continue;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let is_stale = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= MIN_AGE);
+        if !metadata.is_file() || !is_stale {
+            continue;
+        }
+
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                debug!(path = %path.display(), "已删除过期的更新文件");
+                report.removed_files += 1;
+                report.freed_bytes += metadata.len();
+            }
+            Err(e) => {
+                warn!("删除过期的更新文件 {} 失败: {e}", path.display());
+                report.failed_files += 1;
+            }
+        }
+    }
+}
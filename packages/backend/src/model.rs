@@ -4,11 +4,17 @@ use std::{
     sync::Arc,
 };
 
+use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
 };
 
+use crate::{
+    config::LogRotation,
+    timeline::Timeline,
+};
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SharedMetadata(pub Arc<MetadataPayload>);
 
@@ -26,26 +32,67 @@ impl AsRef<MetadataPayload> for SharedMetadata {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type", content = "payload")]
 pub enum AppMessage {
     UpdateMetadata(MetadataPayload),
 
     UpdatePlayState(PlayStatePayload),
-    UpdateTimeline(TimelinePayload),
+    UpdateTimeline(Timeline),
     UpdatePlayMode(PlayModePayload),
+    SetControls(ControlsPayload),
+    /// 提前准备下一首歌曲的封面
+    PrefetchCover(PrefetchCoverPayload),
 
-    EnableSmtc,
-    DisableSmtc,
+    /// 以下控制命令由外部控制器 (例如 Stream Deck 插件) 发出，会作为事件转发给前端执行
+    TogglePlayPause,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    /// 在当前位置的基础上跳转，由后端根据缓存的时间线计算目标位置
+    SeekRelative(SeekRelativePayload),
+
+    EnableSink(SinkPayload),
+    DisableSink(SinkPayload),
+    /// 返回每个输出端当前是否启用
+    ListSinks,
 
-    EnableDiscord,
-    DisableDiscord,
     DiscordConfig(DiscordConfigPayload),
+    UpdateListenTogether(ListenTogetherPayload),
+
+    GetRecentLogs(GetRecentLogsPayload),
+    GetLogPath,
+    OpenLogFolder,
+    GetDiagnostics,
+    GetSchema,
+    SetLogRetention(LogRetentionPayload),
+    AckEvent(AckEventPayload),
+    ApplyUpdate,
+    TestSink(SinkPayload),
+    /// 读取后端缓存的当前封面，供其他插件复用
+    GetCurrentCover(GetCurrentCoverPayload),
+    /// 运行一组快速检查，返回每一项的结果
+    SelfTest,
+    /// 把当前歌曲的分享文本复制到剪贴板，返回复制的文本
+    CopyShareText(CopyShareTextPayload),
+    /// 用当前的 Discord 配置为示例元数据生成 Activity，供设置界面预览
+    PreviewPresence(PreviewPresencePayload),
+
+    /// 停止所有输出端、后台任务和 HTTP 服务但不卸载插件，通过 `Resume` 恢复
+    Quiesce,
+    Resume,
+
+    /// 由多实例仲裁线程发出，其他网易云实例取得发布权时为 true
+    #[serde(skip)]
+    InstanceStandby(bool),
+    /// 后台补全缺失字段后的元数据，只更新各个输出端的显示，不视为切歌
+    #[serde(skip)]
+    EnrichedMetadata(MetadataPayload),
 
     Shutdown,
 }
 
-#[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, PartialEq, Eq)]
 pub struct CoverPayload {
     pub base64: Option<String>,
     pub url: Option<String>,
@@ -60,7 +107,7 @@ impl fmt::Debug for CoverPayload {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct MetadataPayload {
     pub song_name: String,
@@ -69,15 +116,39 @@ pub struct MetadataPayload {
     pub cover: Option<CoverPayload>,
     pub ncm_id: Option<u64>,
     pub duration: Option<f64>,
+    pub playlist: Option<PlaylistInfo>,
+    pub album_id: Option<u64>,
+    #[serde(default)]
+    pub artist_ids: Vec<u64>,
+    #[serde(default)]
+    pub context: PlaybackContext,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// 歌曲的播放来源，私人 FM 这样的电台模式无法回到上一首、跳转或调整播放顺序
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackContext {
+    #[default]
+    Normal,
+    Radio,
+}
+
+/// 当前歌曲所在的播放列表
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistInfo {
+    pub name: String,
+    /// 当前歌曲在播放列表中的位置，从 1 开始
+    pub position: u32,
+    pub length: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackStatus {
     Playing,
     Paused,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
 pub enum RepeatMode {
     None,
     Track,
@@ -85,26 +156,59 @@ pub enum RepeatMode {
     AI,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct PlayStatePayload {
     pub status: PlaybackStatus,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TimelinePayload {
     pub current_time: f64,
     pub total_time: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct SeekRelativePayload {
+    /// 正数为快进
+    pub delta_ms: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayModePayload {
     pub is_shuffling: bool,
     pub repeat_mode: RepeatMode,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// 系统媒体浮窗中各个按钮是否可用，省略的字段视为可用
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ControlsPayload {
+    pub play: bool,
+    pub pause: bool,
+    pub next: bool,
+    pub previous: bool,
+    pub stop: bool,
+    pub seek: bool,
+}
+
+impl Default for ControlsPayload {
+    fn default() -> Self {
+        Self {
+            play: true,
+            pause: true,
+            next: true,
+            previous: true,
+            stop: true,
+            seek: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DiscordConfigPayload {
     pub show_when_paused: bool,
@@ -113,7 +217,22 @@ pub struct DiscordConfigPayload {
     pub app_name_mode: DiscordAppNameMode,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ListenTogetherPayload {
+    /// 不在一起听房间中时为空
+    pub room: Option<ListenTogetherRoom>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenTogetherRoom {
+    pub room_id: String,
+    /// 房间中的当前人数
+    pub size: u32,
+    pub max_size: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, PartialEq, Eq)]
 #[serde(tag = "type", content = "value")]
 pub enum DiscordAppNameMode {
     #[default]
@@ -124,22 +243,115 @@ pub enum DiscordAppNameMode {
     Custom(String),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
 pub enum DiscordDisplayMode {
     Name,    // Listening to Spotify
     State,   // Listening to Rick Astley
     Details, // Listening to Never Gonna Give You Up
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct GetRecentLogsPayload {
+    /// 最低日志级别，例如 `"warn"` 只返回 WARN 和 ERROR 级别的日志
+    pub level: Option<String>,
+    /// 最多返回的条数，从最新的日志开始计算
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRetentionPayload {
+    pub rotation: LogRotation,
+    pub max_log_files: usize,
+    /// 按大小轮转时单个日志文件的最大大小 (MB)，未设置时保持不变
+    pub max_file_size_mb: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+pub struct AckEventPayload {
+    /// 确认收到的最大事件序号
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchCoverPayload {
+    pub ncm_id: u64,
+    pub cover: CoverPayload,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+pub struct SinkPayload {
+    pub sink: SinkId,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCurrentCoverPayload {
+    #[serde(default)]
+    pub format: CoverFormat,
+    /// 封面最长边的像素数，超过时会等比缩小
+    pub max_size: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct CopyShareTextPayload {
+    /// 可用的占位符见 `formatting::render_template`，默认为 `{title} - {artist} {url}`
+    pub template: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewPresencePayload {
+    pub metadata: MetadataPayload,
+    pub status: PlaybackStatus,
+    /// 播放进度 (毫秒)
+    #[serde(default)]
+    pub current_time: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverFormat {
+    /// 在响应中直接返回 base64
+    #[default]
+    Base64,
+    /// 写入临时文件并返回文件路径
+    File,
+}
+
+/// 后端的输出端，新增输出端时在这里添加，统一通过 `EnableSink`/`DisableSink` 管理
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkId {
+    Smtc,
+    Discord,
+}
+
+impl SinkId {
+    pub const ALL: [Self; 2] = [Self::Smtc, Self::Discord];
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
 pub enum CommandStatus {
     Success,
     Error,
 }
 
+/// 命令中 `type` 和 `payload` 之外的字段
+#[derive(Deserialize, Debug, Default)]
+pub struct CommandEnvelope {
+    /// 前端生成的关联 ID，会出现在日志的 span、命令结果和处理期间分发的事件中
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct CommandResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     pub status: CommandStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// 查询类命令的返回数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
@@ -0,0 +1,203 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+};
+
+use anyhow::Result;
+use base64::{
+    Engine,
+    engine::general_purpose,
+};
+use tracing::{
+    debug,
+    warn,
+};
+use windows::{
+    Foundation::Uri,
+    Graphics::Imaging::{
+        BitmapAlphaMode,
+        BitmapDecoder,
+        BitmapInterpolationMode,
+        BitmapPixelFormat,
+        BitmapTransform,
+        ColorManagementMode,
+        ExifOrientationMode,
+    },
+    Storage::Streams::{
+        DataWriter,
+        InMemoryRandomAccessStream,
+        RandomAccessStream,
+        RandomAccessStreamReference,
+    },
+    core::HSTRING,
+};
+
+use crate::{
+    config,
+    model::{
+        CoverPayload,
+        SharedMetadata,
+    },
+    smtc_core::{
+        self,
+        SmtcEvent,
+    },
+    tasks,
+    winrt::{
+        JoinTimeout,
+        LOCAL_TIMEOUT,
+        NETWORK_TIMEOUT,
+    },
+};
+
+/// 提取颜色前把封面缩小到这个边长，足够区分主要颜色
+const SAMPLE_SIZE: u32 = 32;
+
+/// 每次切歌加一，后台线程完成时如果已经切到别的歌就丢弃结果
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 在后台提取当前封面的主要颜色，完成后发送 `CoverPalette` 事件
+///
+/// 颜色数量由 `smtc.palette_colors` 决定，为 0 时不提取
+pub fn extract(metadata: SharedMetadata) {
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let count = config::get().smtc.palette_colors as usize;
+    if count == 0 || metadata.cover.is_none() {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("cover-palette-thread", move |token| {
+        let Some(cover) = metadata.cover.as_ref() else {
+            return;
+        };
+        let colors = match sample_pixels(cover) {
+            Ok(pixels) => median_cut(pixels, count),
+            Err(e) => {
+                debug!(ncm_id = ?metadata.ncm_id, "提取封面颜色失败: {e:?}");
+                return;
+            }
+        };
+
+        if token.is_cancelled() || GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        smtc_core::dispatch_event(&SmtcEvent::CoverPalette {
+            ncm_id: metadata.ncm_id,
+            colors: colors.iter().map(|&color| hex(color)).collect(),
+        });
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动封面颜色提取线程: {e}");
+    }
+}
+
+/// 解码封面并缩小到 `SAMPLE_SIZE`，返回每个像素的 RGB 值
+fn sample_pixels(cover: &CoverPayload) -> Result<Vec<[u8; 3]>> {
+    let stream = if let Some(base64_data) = &cover.base64 {
+        let bytes = general_purpose::STANDARD.decode(base64_data)?;
+        let stream = InMemoryRandomAccessStream::new()?;
+        let writer = DataWriter::CreateDataWriter(&stream)?;
+        writer.WriteBytes(&bytes)?;
+        writer.StoreAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+        writer.DetachStream()?;
+        stream
+    } else if let Some(url) = &cover.url {
+        let uri = Uri::CreateUri(&HSTRING::from(url))?;
+        let source = RandomAccessStreamReference::CreateFromUri(&uri)?
+            .OpenReadAsync()?
+            .join_timeout(NETWORK_TIMEOUT)?;
+        let stream = InMemoryRandomAccessStream::new()?;
+        RandomAccessStream::CopyAsync(&source.GetInputStreamAt(0)?, &stream.GetOutputStreamAt(0)?)?
+            .join_timeout(NETWORK_TIMEOUT)?;
+        stream
+    } else {
+        return Ok(Vec::new());
+    };
+    stream.Seek(0)?;
+
+    let decoder = BitmapDecoder::CreateAsync(&stream)?.join_timeout(LOCAL_TIMEOUT)?;
+    let transform = BitmapTransform::new()?;
+    transform.SetScaledWidth(SAMPLE_SIZE)?;
+    transform.SetScaledHeight(SAMPLE_SIZE)?;
+    transform.SetInterpolationMode(BitmapInterpolationMode::Linear)?;
+
+    let pixels = decoder
+        .GetPixelDataTransformedAsync(
+            BitmapPixelFormat::Bgra8,
+            BitmapAlphaMode::Ignore,
+            &transform,
+            ExifOrientationMode::IgnoreExifOrientation,
+            ColorManagementMode::DoNotColorManage,
+        )?
+        .join_timeout(LOCAL_TIMEOUT)?
+        .DetachPixelData()?;
+
+    Ok(pixels
+        .chunks_exact(4)
+        .map(|bgra| [bgra[2], bgra[1], bgra[0]])
+        .collect())
+}
+
+/// 中位切分：反复沿颜色范围最大的通道把像素最多的一半切开，
+/// 返回每组的平均色，像素数量多的排在前面
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<[u8; 3]> {
+    let mut buckets = vec![pixels];
+    while buckets.len() < count {
+        let widest = buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| {
+                let (channel, range) = widest_channel(bucket);
+                (index, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, channel, range)) = widest else {
+            break;
+        };
+        // 剩下的每组都只有一种颜色，无法继续切分
+        if range == 0 {
+            break;
+        }
+
+        let mut bucket = buckets.swap_remove(index);
+        bucket.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = bucket.split_off(bucket.len() / 2);
+        buckets.push(bucket);
+        buckets.push(upper);
+    }
+
+    buckets.retain(|bucket| !bucket.is_empty());
+    buckets.sort_by_key(|bucket| Reverse(bucket.len()));
+    buckets.iter().map(|bucket| average(bucket)).collect()
+}
+
+/// 返回范围最大的通道及其范围
+fn widest_channel(bucket: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = bucket.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+            (channel, max.saturating_sub(min))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or_default()
+}
+
+fn average(bucket: &[[u8; 3]]) -> [u8; 3] {
+    let mut sums = [0u64; 3];
+    for pixel in bucket {
+        for (sum, &value) in sums.iter_mut().zip(pixel) {
+            *sum += u64::from(value);
+        }
+    }
+    sums.map(|sum| (sum / bucket.len() as u64) as u8)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
@@ -0,0 +1,135 @@
+use std::{
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+use serde::Serialize;
+use tracing::{
+    debug,
+    info,
+};
+use windows::{
+    Foundation::Metadata::ApiInformation,
+    Wdk::System::SystemServices::RtlGetVersion,
+    Win32::{
+        Foundation::HMODULE,
+        System::{
+            LibraryLoader::{
+                GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+                GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+                GetModuleFileNameW,
+                GetModuleHandleExW,
+            },
+            SystemInformation::OSVERSIONINFOW,
+        },
+    },
+    core::{
+        HSTRING,
+        PCWSTR,
+    },
+};
+
+const SMTC_TYPE: &str = "Windows.Media.SystemMediaTransportControls";
+const GSMTC_MANAGER_TYPE: &str =
+    "Windows.Media.Control.GlobalSystemMediaTransportControlsSessionManager";
+
+/// 当前系统支持的可选功能，较旧的 Windows 10 版本缺少其中一部分
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// 例如 `10.0.19045`
+    os_version: String,
+    /// SMTC 的时间线 (进度条)
+    pub smtc_timeline: bool,
+    /// SMTC 的随机播放状态
+    pub smtc_shuffle: bool,
+    /// SMTC 的重复播放模式
+    pub smtc_auto_repeat: bool,
+    /// 全局媒体会话 (GSMTC)，Windows 10 1809 起可用
+    pub global_media_sessions: bool,
+}
+
+static CAPABILITIES: LazyLock<Capabilities> = LazyLock::new(|| {
+    let capabilities = Capabilities::detect();
+    info!(?capabilities, "已检测系统能力");
+    capabilities
+});
+
+pub fn capabilities() -> &'static Capabilities {
+    &CAPABILITIES
+}
+
+impl Capabilities {
+    fn detect() -> Self {
+        Self {
+            os_version: os_version(),
+            smtc_timeline: method_present(SMTC_TYPE, "UpdateTimelineProperties"),
+            smtc_shuffle: property_present(SMTC_TYPE, "ShuffleEnabled"),
+            smtc_auto_repeat: property_present(SMTC_TYPE, "AutoRepeatMode"),
+            global_media_sessions: type_present(GSMTC_MANAGER_TYPE),
+        }
+    }
+}
+
+/// `GetVersionEx` 会受到应用清单的影响，所以直接读取真实的系统版本
+fn os_version() -> String {
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+
+    if unsafe { RtlGetVersion(&raw mut info) }.is_ok() {
+        format!(
+            "{}.{}.{}",
+            info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+        )
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// 插件 DLL 所在的目录
+pub fn plugin_dir() -> Option<PathBuf> {
+    let mut module = HMODULE::default();
+    // 用本函数的地址找到包含它的模块，也就是插件自己而不是网易云的主程序
+    unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            PCWSTR(plugin_dir as *const u16),
+            &raw mut module,
+        )
+    }
+    .ok()?;
+
+    let mut buffer = vec![0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(Some(module), &mut buffer) } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf16_lossy(&buffer[..len]));
+    path.parent().map(PathBuf::from)
+}
+
+fn type_present(type_name: &str) -> bool {
+    ApiInformation::IsTypePresent(&HSTRING::from(type_name)).unwrap_or_else(|e| {
+        debug!("检测类型 {type_name} 失败: {e:?}");
+        false
+    })
+}
+
+fn method_present(type_name: &str, method: &str) -> bool {
+    ApiInformation::IsMethodPresent(&HSTRING::from(type_name), &HSTRING::from(method))
+        .unwrap_or_else(|e| {
+            debug!("检测方法 {type_name}.{method} 失败: {e:?}");
+            false
+        })
+}
+
+fn property_present(type_name: &str, property: &str) -> bool {
+    ApiInformation::IsPropertyPresent(&HSTRING::from(type_name), &HSTRING::from(property))
+        .unwrap_or_else(|e| {
+            debug!("检测属性 {type_name}.{property} 失败: {e:?}");
+            false
+        })
+}
@@ -0,0 +1,225 @@
+use std::sync::atomic::{
+    AtomicU16,
+    AtomicU32,
+    Ordering,
+};
+
+use anyhow::{
+    Result,
+    bail,
+};
+use serde::Serialize;
+use tracing::info;
+use windows::{
+    Win32::{
+        Foundation::{
+            CloseHandle,
+            HANDLE,
+            INVALID_HANDLE_VALUE,
+            WAIT_ABANDONED,
+            WAIT_OBJECT_0,
+        },
+        System::{
+            Memory::{
+                CreateFileMappingW,
+                FILE_MAP_ALL_ACCESS,
+                MapViewOfFile,
+                PAGE_READWRITE,
+            },
+            Threading::{
+                CreateMutexW,
+                GetCurrentProcessId,
+                ReleaseMutex,
+                WaitForSingleObject,
+            },
+        },
+    },
+    core::w,
+};
+
+use crate::smtc_core::{
+    self,
+    SmtcEvent,
+};
+
+/// 写入共享内存的插件名，其他插件可以据此显示是谁占用了 Discord 状态
+const PLUGIN_NAME: &str = "InfLink-rs";
+
+/// 插件名最多保存的 UTF-16 单元数，超出部分被截断
+const OWNER_NAME_LEN: usize = 64;
+
+/// 共享内存的布局，其他插件需要按同样的布局读写
+#[repr(C)]
+struct OwnerRecord {
+    /// 持有锁的进程 ID，0 表示没有
+    pid: AtomicU32,
+    /// 以 0 结尾的 UTF-16 插件名
+    name: [AtomicU16; OWNER_NAME_LEN],
+}
+
+/// 当前持有 Discord 状态的插件
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceOwner {
+    /// 对方没有写入插件名时为空
+    pub name: Option<String>,
+    pub pid: Option<u32>,
+}
+
+/// 与其他设置 Discord 状态的网易云插件协调，同一时间只有一个插件发布 Activity
+///
+/// 约定: 发布前持有命名互斥量 `Local\DiscordPresence.Lock`，并把插件名和进程 ID
+/// 写入共享内存 `Local\DiscordPresence.Owner`，互斥量已被占用时让出
+///
+/// 互斥量归获取它的线程所有，所以只能在 Discord RPC 线程上使用
+#[derive(Debug)]
+pub struct PresenceLock {
+    mutex: HANDLE,
+    record: &'static OwnerRecord,
+    held: bool,
+    /// 最近一次报告给前端的持有者，初始为没有持有者
+    reported: Option<PresenceOwner>,
+}
+
+impl std::fmt::Debug for OwnerRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnerRecord")
+            .field("pid", &self.pid.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+// Safety: 共享内存在进程的整个生命周期内保持映射，互斥量句柄可以在任意线程上使用
+unsafe impl Send for PresenceLock {}
+
+impl PresenceLock {
+    pub fn open() -> Result<Self> {
+        let mapping = unsafe {
+            CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                None,
+                PAGE_READWRITE,
+                0,
+                size_of::<OwnerRecord>() as u32,
+                w!("Local\\DiscordPresence.Owner"),
+            )?
+        };
+        let view =
+            unsafe { MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, size_of::<OwnerRecord>()) };
+        // 映射视图会保持共享内存对象存活
+        let _ = unsafe { CloseHandle(mapping) };
+        if view.Value.is_null() {
+            bail!(
+                "映射共享内存失败: {:?}",
+                windows::core::Error::from_thread()
+            );
+        }
+
+        let mutex = unsafe { CreateMutexW(None, false, w!("Local\\DiscordPresence.Lock"))? };
+
+        Ok(Self {
+            mutex,
+            record: unsafe { &*view.Value.cast::<OwnerRecord>() },
+            held: false,
+            reported: None,
+        })
+    }
+
+    /// 尝试取得发布权，已经持有时直接返回 `true`
+    pub fn try_acquire(&mut self) -> bool {
+        if !self.held {
+            let wait = unsafe { WaitForSingleObject(self.mutex, 0) };
+            // 之前的持有者崩溃时会得到 WAIT_ABANDONED，此时锁已经归本线程所有
+            if wait == WAIT_OBJECT_0 || wait == WAIT_ABANDONED {
+                self.held = true;
+                self.write_owner();
+                info!("已取得 Discord 状态的发布权");
+            }
+        }
+        self.report();
+        self.held
+    }
+
+    /// 不再需要发布 Activity 时让出，其他插件可以立即接管
+    pub fn release(&mut self) {
+        if self.held {
+            self.unlock();
+            info!("已让出 Discord 状态的发布权");
+        }
+        self.report();
+    }
+
+    fn unlock(&mut self) {
+        self.record.pid.store(0, Ordering::SeqCst);
+        self.record.name[0].store(0, Ordering::SeqCst);
+        let _ = unsafe { ReleaseMutex(self.mutex) };
+        self.held = false;
+    }
+
+    fn write_owner(&self) {
+        let name = PLUGIN_NAME
+            .encode_utf16()
+            .take(OWNER_NAME_LEN - 1)
+            .chain(std::iter::once(0));
+        for (slot, unit) in self.record.name.iter().zip(name) {
+            slot.store(unit, Ordering::SeqCst);
+        }
+        self.record
+            .pid
+            .store(unsafe { GetCurrentProcessId() }, Ordering::SeqCst);
+    }
+
+    fn owner(&self) -> Option<PresenceOwner> {
+        if self.held {
+            return Some(PresenceOwner {
+                name: Some(PLUGIN_NAME.to_string()),
+                pid: Some(unsafe { GetCurrentProcessId() }),
+            });
+        }
+
+        // 共享内存只在持有锁时写入，这里读到的可能是正在更新的内容，下次轮询时会纠正
+        let pid = self.record.pid.load(Ordering::SeqCst);
+        let name: Vec<u16> = self
+            .record
+            .name
+            .iter()
+            .map(|unit| unit.load(Ordering::SeqCst))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        if pid == 0 && name.is_empty() {
+            // 锁可能被不写入共享内存的插件持有
+            let wait = unsafe { WaitForSingleObject(self.mutex, 0) };
+            if wait == WAIT_OBJECT_0 || wait == WAIT_ABANDONED {
+                let _ = unsafe { ReleaseMutex(self.mutex) };
+                return None;
+            }
+        }
+
+        Some(PresenceOwner {
+            name: (!name.is_empty()).then(|| String::from_utf16_lossy(&name)),
+            pid: (pid != 0).then_some(pid),
+        })
+    }
+
+    /// 持有者改变时通知前端
+    fn report(&mut self) {
+        let owner = self.owner();
+        if self.reported == owner {
+            return;
+        }
+        info!(?owner, "Discord 状态的持有者已改变");
+        smtc_core::dispatch_event(&SmtcEvent::PresenceOwnerChanged {
+            owner: owner.clone(),
+        });
+        self.reported = owner;
+    }
+}
+
+impl Drop for PresenceLock {
+    fn drop(&mut self) {
+        if self.held {
+            self.unlock();
+        }
+        let _ = unsafe { CloseHandle(self.mutex) };
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::Write,
+    sync::{
+        LazyLock,
+        Mutex,
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+    },
+    time::Instant,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use tracing::{
+    info,
+    warn,
+};
+
+use crate::config;
+
+const RECORDING_FILE_NAME: &str = "command-recording.jsonl";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// 回放期间不记录回放的命令
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+static RECORDING: LazyLock<Mutex<Option<Recording>>> = LazyLock::new(|| Mutex::new(None));
+
+struct Recording {
+    file: File,
+    started_at: Instant,
+}
+
+/// 录制文件中的一行
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedCommand {
+    /// 距离开始录制的时间
+    elapsed_ms: u64,
+    command: Value,
+}
+
+pub fn init() {
+    if !config::get().recorder.enabled {
+        return;
+    }
+
+    let Some(dir) = config::data_dir() else {
+        return;
+    };
+    let path = dir.join(RECORDING_FILE_NAME);
+
+    let file = fs::create_dir_all(&dir)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&path));
+    match file {
+        Ok(file) => {
+            if let Ok(mut recording) = RECORDING.lock() {
+                *recording = Some(Recording {
+                    file,
+                    started_at: Instant::now(),
+                });
+            }
+            ENABLED.store(true, Ordering::Relaxed);
+            info!("正在录制命令到 {}", path.display());
+        }
+        Err(e) => warn!("打开命令录制文件 {} 失败: {e}", path.display()),
+    }
+}
+
+/// 停止录制并关闭录制文件，插件暂停后恢复时会重新调用 [`init`]
+pub fn shutdown() {
+    ENABLED.store(false, Ordering::Relaxed);
+    if let Ok(mut recording) = RECORDING.lock() {
+        *recording = None;
+    }
+}
+
+/// 记录一个前端命令，封面的 base64 数据会被去掉
+pub fn record(command_json: &str) {
+    if !ENABLED.load(Ordering::Relaxed) || REPLAYING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let Ok(mut command) = serde_json::from_str::<Value>(command_json) else {
+        return;
+    };
+    strip_covers(&mut command);
+
+    let Ok(mut guard) = RECORDING.lock() else {
+        return;
+    };
+    let Some(recording) = guard.as_mut() else {
+        return;
+    };
+
+    let line = RecordedCommand {
+        elapsed_ms: recording.started_at.elapsed().as_millis() as u64,
+        command,
+    };
+    let result = serde_json::to_string(&line)
+        .map_err(std::io::Error::from)
+        .and_then(|json| writeln!(recording.file, "{json}"));
+    if let Err(e) = result {
+        warn!("写入命令录制文件失败，停止录制: {e}");
+        *guard = None;
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+}
+
+fn strip_covers(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if let Some(base64) = map.get_mut("base64")
+                && base64.is_string()
+            {
+                *base64 = Value::Null;
+            }
+            map.values_mut().for_each(strip_covers);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_covers),
+        _ => {}
+    }
+}
+
+/// 按录制时的时间间隔把录制文件中的命令重新送入 Dispatcher，只在调试构建中可用
+#[cfg(debug_assertions)]
+pub fn replay(path: &std::path::Path) -> Result<usize, String> {
+    REPLAYING.store(true, Ordering::Relaxed);
+    let result = replay_file(path);
+    REPLAYING.store(false, Ordering::Relaxed);
+
+    if let Ok(count) = &result {
+        info!(count, "命令回放完成");
+    }
+    result
+}
+
+#[cfg(debug_assertions)]
+fn replay_file(path: &std::path::Path) -> Result<usize, String> {
+    use std::{
+        io::{
+            BufRead,
+            BufReader,
+        },
+        thread,
+        time::Duration,
+    };
+
+    /// 录制中的长时间空闲 (例如暂停) 在回放时缩短到这个时长
+    const MAX_GAP: Duration = Duration::from_secs(2);
+
+    let file =
+        File::open(path).map_err(|e| format!("打开录制文件 {} 失败: {e}", path.display()))?;
+
+    let mut count = 0;
+    let mut last_elapsed_ms = None;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("读取录制文件失败: {e}"))?;
+        let recorded: RecordedCommand = match serde_json::from_str(&line) {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                warn!("跳过无法解析的录制行: {e}");
+                continue;
+            }
+        };
+
+        if let Some(last) = last_elapsed_ms {
+            let gap = Duration::from_millis(recorded.elapsed_ms.saturating_sub(last));
+            thread::sleep(gap.min(MAX_GAP));
+        }
+        last_elapsed_ms = Some(recorded.elapsed_ms);
+
+        crate::dispatcher::send_command(&recorded.command.to_string());
+        count += 1;
+    }
+
+    Ok(count)
+}
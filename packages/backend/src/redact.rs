@@ -0,0 +1,90 @@
+use std::{
+    borrow::Cow,
+    io,
+    sync::{
+        LazyLock,
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+    },
+};
+
+use regex::Regex;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 按顺序应用的脱敏规则
+///
+/// URL 规则需要在密钥规则之前应用，这样查询字符串中的密钥会被整体隐藏
+static RULES: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        // data URL 形式的封面
+        (
+            r"data:([\w/+.-]+);base64,[A-Za-z0-9+/=]+",
+            "data:$1;base64,<omitted>",
+        ),
+        // 其他很长的 base64 字符串
+        (r"[A-Za-z0-9+/]{256,}={0,2}", "<base64 omitted>"),
+        // 带查询字符串的 URL，保留协议、主机和路径
+        (
+            r#"(https?://[^\s?"'#<>\\]+)\?[^\s"'#<>\\]+"#,
+            "$1?<redacted>",
+        ),
+        // Last.fm / ListenBrainz 等服务的密钥和令牌，例如 `api_key=...` 或 `"token": "..."`
+        (
+            r#"(?i)\b(api_?key|session_?key|access_?token|token|secret|password|authorization|sk)(\\?"?\s*[:=]\s*\\?"?)([^\s"\\,&}\])]+)"#,
+            "${1}${2}<redacted>",
+        ),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| {
+        (
+            Regex::new(pattern).expect("硬编码的脱敏规则无效，这不应该发生"),
+            replacement,
+        )
+    })
+    .collect()
+});
+
+/// 设置是否启用日志脱敏
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 隐藏文本中的封面 base64 数据、密钥和 URL 查询字符串
+pub fn redact(text: &str) -> Cow<'_, str> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = Cow::Borrowed(text);
+    for (pattern, replacement) in RULES.iter() {
+        if let Cow::Owned(replaced) = pattern.replace_all(&result, *replacement) {
+            result = Cow::Owned(replaced);
+        }
+    }
+    result
+}
+
+/// 在写入之前对内容进行脱敏的写入器
+///
+/// `tracing_subscriber::fmt` 会把每条日志格式化完成后一次性写入，
+/// 所以这里可以按完整的日志行进行替换
+pub struct RedactingWriter<W>(pub W);
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => {
+                self.0.write_all(redact(text).as_bytes())?;
+                Ok(buf.len())
+            }
+            Err(_) => self.0.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    cell::RefCell,
+    sync::Arc,
+};
+
+/// 前端为一次操作生成的关联 ID，用于把前后端的日志对应起来
+pub type RequestId = Arc<str>;
+
+thread_local! {
+    static CURRENT: RefCell<Option<RequestId>> = const { RefCell::new(None) };
+}
+
+/// 当前线程正在处理的命令的关联 ID，命令结果和期间分发的事件会带上它
+pub fn current() -> Option<RequestId> {
+    CURRENT.with_borrow(Clone::clone)
+}
+
+/// 在返回的守卫存活期间把 `request_id` 设为当前线程的关联 ID
+pub fn enter(request_id: Option<RequestId>) -> Guard {
+    Guard {
+        previous: CURRENT.replace(request_id),
+    }
+}
+
+pub struct Guard {
+    previous: Option<RequestId>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
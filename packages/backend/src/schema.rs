@@ -0,0 +1,26 @@
+use schemars::{
+    Schema,
+    schema_for,
+};
+use serde::Serialize;
+
+use crate::{
+    config::BackendConfig,
+    model::AppMessage,
+};
+
+/// 后端配置和所有命令的 JSON Schema，供前端设置界面生成表单和校验输入，
+/// 避免在前端重复定义一遍 Rust 中的结构
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schemas {
+    config: Schema,
+    commands: Schema,
+}
+
+pub fn collect() -> Schemas {
+    Schemas {
+        config: schema_for!(BackendConfig),
+        commands: schema_for!(AppMessage),
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::{
+    info,
+    warn,
+};
+
+use crate::{
+    cover,
+    discord,
+    model::SinkId,
+    platform,
+    sinks,
+    smtc_core,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// 当前环境不需要或无法进行这项检查，例如 Discord 未启用
+    Skipped,
+}
+
+/// `SelfTest` 返回的单项检查结果
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    name: &'static str,
+    status: CheckStatus,
+    /// 失败原因或跳过的原因
+    detail: Option<String>,
+    duration_ms: u64,
+}
+
+/// 依次运行所有检查，单项失败不影响后面的检查
+pub fn run() -> Vec<CheckResult> {
+    let results = vec![
+        check("timeline", || {
+            if !platform::capabilities().smtc_timeline {
+                return Err(Outcome::Skip("系统不支持 SMTC 时间线".into()));
+            }
+            smtc_core::self_test_timeline().map_err(|e| Outcome::Fail(format!("{e:?}")))
+        }),
+        check("coverDecode", || cover::self_test().map_err(Outcome::Fail)),
+        check("discord", || {
+            if !sinks::is_enabled(SinkId::Discord) {
+                return Err(Outcome::Skip("Discord 未启用".into()));
+            }
+            discord::probe().map_err(Outcome::Fail)
+        }),
+        check("rendererTask", || {
+            smtc_core::self_test_renderer().map_err(Outcome::Fail)
+        }),
+    ];
+
+    let failed = results
+        .iter()
+        .filter(|result| result.status == CheckStatus::Failed)
+        .count();
+    if failed > 0 {
+        warn!(failed, "自检发现问题: {results:?}");
+    } else {
+        info!("自检全部通过");
+    }
+    results
+}
+
+enum Outcome {
+    Fail(String),
+    Skip(String),
+}
+
+fn check(name: &'static str, f: impl FnOnce() -> Result<(), Outcome>) -> CheckResult {
+    let start = Instant::now();
+    let (status, detail) = match f() {
+        Ok(()) => (CheckStatus::Passed, None),
+        Err(Outcome::Fail(reason)) => (CheckStatus::Failed, Some(reason)),
+        Err(Outcome::Skip(reason)) => (CheckStatus::Skipped, Some(reason)),
+    };
+    CheckResult {
+        name,
+        status,
+        detail,
+        duration_ms: start.elapsed().as_millis() as u64,
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    io,
+    panic::{
+        self,
+        AssertUnwindSafe,
+    },
+    sync::{
+        Mutex,
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            Ordering,
+        },
+        mpsc::{
+            self,
+            Receiver,
+            SyncSender,
+        },
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tracing::{
+    error,
+    info,
+    warn,
+};
+
+use crate::{
+    config::{
+        self,
+        SinksConfig,
+    },
+    logger,
+    model::SinkId,
+};
+
+/// 工作线程连续 panic 超过这个次数后不再重启
+const MAX_RESTARTS: u32 = 5;
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+static SMTC_ENABLED: AtomicBool = AtomicBool::new(false);
+static DISCORD_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// `ListSinks` 返回的单个输出端状态
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SinkState {
+    sink: SinkId,
+    /// 本次运行中是否已启用
+    enabled: bool,
+    /// 配置文件中记录的状态，也就是用户最近一次的选择
+    persisted: bool,
+}
+
+const fn flag(sink: SinkId) -> &'static AtomicBool {
+    match sink {
+        SinkId::Smtc => &SMTC_ENABLED,
+        SinkId::Discord => &DISCORD_ENABLED,
+    }
+}
+
+const fn persisted(sinks: &SinksConfig, sink: SinkId) -> bool {
+    match sink {
+        SinkId::Smtc => sinks.smtc,
+        SinkId::Discord => sinks.discord,
+    }
+}
+
+const fn persisted_mut(sinks: &mut SinksConfig, sink: SinkId) -> &mut bool {
+    match sink {
+        SinkId::Smtc => &mut sinks.smtc,
+        SinkId::Discord => &mut sinks.discord,
+    }
+}
+
+/// 记录输出端已被启用或禁用，状态变化时写回配置文件
+pub fn set_enabled(sink: SinkId, enabled: bool) {
+    if flag(sink).swap(enabled, Ordering::Relaxed) != enabled {
+        info!(?sink, enabled, "输出端状态已改变");
+    }
+
+    if persisted(&config::get().sinks, sink) == enabled {
+        return;
+    }
+    if let Err(e) = config::update(|config| *persisted_mut(&mut config.sinks, sink) = enabled) {
+        warn!(?sink, "保存输出端状态失败: {e}");
+    }
+}
+
+pub fn list() -> Vec<SinkState> {
+    let sinks = config::get().sinks;
+    SinkId::ALL
+        .into_iter()
+        .map(|sink| SinkState {
+            sink,
+            enabled: flag(sink).load(Ordering::Relaxed),
+            persisted: persisted(&sinks, sink),
+        })
+        .collect()
+}
+
+pub fn is_enabled(sink: SinkId) -> bool {
+    flag(sink).load(Ordering::Relaxed)
+}
+
+/// 输出端工作线程的运行状况，包含在诊断信息中
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerHealth {
+    sink: SinkId,
+    running: bool,
+    /// 因为 panic 被重启的次数
+    restarts: u32,
+    last_panic: Option<String>,
+    /// 当前工作线程是第几次启动的，只有当前的线程可以更新状态
+    #[serde(skip)]
+    generation: u64,
+}
+
+static WORKERS: Mutex<Vec<WorkerHealth>> = Mutex::new(Vec::new());
+
+/// 每次启动工作线程时递增，旧线程退出时据此判断自己是否已被新线程取代
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn worker_health() -> Vec<WorkerHealth> {
+    WORKERS
+        .lock()
+        .map(|workers| workers.clone())
+        .unwrap_or_default()
+}
+
+/// 创建输出端工作线程的消息队列，长度由配置文件中的 `sinks.queue_len` 决定
+///
+/// 队列满时发送方应该丢弃消息而不是等待，避免工作线程卡住时阻塞调用方
+pub fn worker_queue<T>() -> (SyncSender<T>, Receiver<T>) {
+    mpsc::sync_channel(config::get().sinks.queue_len.max(1))
+}
+
+/// 监督输出端的工作线程，工作函数 panic 后在同一个线程中重新运行，
+/// 这样工作线程持有的通道接收端不会丢失
+///
+/// `run` 的参数表示这次是否是重启，工作函数正常返回时线程退出
+pub struct Supervisor {
+    sink: SinkId,
+    thread_name: &'static str,
+}
+
+impl Supervisor {
+    pub const fn new(sink: SinkId, thread_name: &'static str) -> Self {
+        Self { sink, thread_name }
+    }
+
+    pub fn spawn<F>(self, mut run: F) -> io::Result<()>
+    where
+        F: FnMut(bool) + Send + 'static,
+    {
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        self.update(|health| {
+            health.running = true;
+            health.restarts = 0;
+            health.generation = generation;
+        });
+        thread::Builder::new()
+            .name(self.thread_name.into())
+            .spawn(move || {
+                let mut restarted = false;
+                loop {
+                    let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| run(restarted)))
+                    else {
+                        break;
+                    };
+
+                    let message = logger::panic_message(&*payload).to_string();
+                    let mut restarts = 0;
+                    self.update_current(generation, |health| {
+                        health.restarts += 1;
+                        health.last_panic = Some(message.clone());
+                        restarts = health.restarts;
+                    });
+                    if restarts > MAX_RESTARTS {
+                        error!(sink = ?self.sink, "输出端工作线程反复 Panic，不再重启: {message}");
+                        break;
+                    }
+
+                    warn!(sink = ?self.sink, restarts, "输出端工作线程 Panic，即将重启: {message}");
+                    thread::sleep(RESTART_DELAY);
+                    restarted = true;
+                }
+                self.update_current(generation, |health| health.running = false);
+            })?;
+        Ok(())
+    }
+
+    /// 只在这个线程仍是该输出端当前的工作线程时更新状态，
+    /// 重新初始化后旧线程退出时不会覆盖新线程的状态
+    fn update_current(&self, generation: u64, f: impl FnOnce(&mut WorkerHealth)) {
+        self.update(|health| {
+            if health.generation == generation {
+                f(health);
+            }
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut WorkerHealth)) {
+        let Ok(mut workers) = WORKERS.lock() else {
+            return;
+        };
+        if let Some(health) = workers.iter_mut().find(|health| health.sink == self.sink) {
+            f(health);
+        } else {
+            let mut health = WorkerHealth {
+                sink: self.sink,
+                running: false,
+                restarts: 0,
+                last_panic: None,
+                generation: 0,
+            };
+            f(&mut health);
+            workers.push(health);
+        }
+    }
+}
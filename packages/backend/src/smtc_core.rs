@@ -1,9 +1,32 @@
 use std::{
+    collections::VecDeque,
+    mem,
+    panic::{
+        self,
+        AssertUnwindSafe,
+    },
     sync::{
+        Arc,
         LazyLock,
         Mutex,
+        atomic::{
+            AtomicBool,
+            AtomicU32,
+            AtomicU64,
+            Ordering,
+        },
+        mpsc::{
+            self,
+            Receiver,
+            RecvTimeoutError,
+            Sender,
+        },
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
     },
-    time::Instant,
 };
 
 use anyhow::Result;
@@ -22,6 +45,7 @@ use tracing::{
     debug,
     error,
     info,
+    info_span,
     instrument,
     warn,
 };
@@ -33,20 +57,26 @@ use windows::{
     },
     Media::{
         AutoRepeatModeChangeRequestedEventArgs,
+        Core::MediaSource,
         MediaPlaybackAutoRepeatMode,
         MediaPlaybackStatus,
         MediaPlaybackType,
-        Playback::MediaPlayer,
+        Playback::{
+            MediaPlaybackItem,
+            MediaPlayer,
+        },
         PlaybackPositionChangeRequestedEventArgs,
         ShuffleEnabledChangeRequestedEventArgs,
         SystemMediaTransportControls,
         SystemMediaTransportControlsButton,
         SystemMediaTransportControlsButtonPressedEventArgs,
+        SystemMediaTransportControlsDisplayUpdater,
         SystemMediaTransportControlsTimelineProperties,
     },
     Storage::Streams::{
         DataWriter,
         InMemoryRandomAccessStream,
+        RandomAccessStream,
         RandomAccessStreamReference,
     },
     core::{
@@ -55,16 +85,45 @@ use windows::{
     },
 };
 
-use crate::model::{
-    CoverPayload,
-    MetadataPayload,
-    PlaybackStatus,
-    RepeatMode,
+use crate::{
+    config::{
+        self,
+        BackendConfig,
+        MatchHints,
+    },
+    event_sink::{
+        self,
+        EventSink,
+    },
+    instance,
+    logger,
+    model::{
+        CommandStatus,
+        ControlsPayload,
+        CoverPayload,
+        MetadataPayload,
+        PlaybackContext,
+        PlaybackStatus,
+        PrefetchCoverPayload,
+        RepeatMode,
+    },
+    platform,
+    presence_lock::PresenceOwner,
+    request_id,
+    tasks,
+    timeline::Timeline,
+    winrt::{
+        JoinTimeout,
+        LOCAL_TIMEOUT,
+        NETWORK_TIMEOUT,
+    },
 };
 
 const HNS_PER_MILLISECOND: f64 = 10_000.0;
 
 static GLOBAL_CALLBACK: LazyLock<Mutex<Option<SmtcCallback>>> = LazyLock::new(|| Mutex::new(None));
+/// 每次注册回调时递增，延迟清除回调时用来避免误删新注册的回调
+static CALLBACK_GENERATION: AtomicU64 = AtomicU64::new(0);
 
 struct SmtcCallback {
     v8_context: CefV8Context,
@@ -87,7 +146,7 @@ struct SmtcHandlerTokens {
 
 #[derive(Serialize, Clone, Debug)]
 #[serde(tag = "type")]
-enum SmtcEvent {
+pub enum SmtcEvent {
     Play,
     Pause,
     Stop,
@@ -95,7 +154,280 @@ enum SmtcEvent {
     PreviousSong,
     ToggleShuffle,
     ToggleRepeat,
-    Seek { position_ms: f64 },
+    Seek {
+        position_ms: f64,
+    },
+    TogglePlayPause,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    /// 前端报告的播放状态与网易云的音频输出不一致，后端已按实际输出修正
+    PlayStateMismatch {
+        reported: PlaybackStatus,
+        observed: PlaybackStatus,
+    },
+    /// 系统的默认输出设备改变，网易云会切换到新设备播放，为空表示没有可用的输出设备
+    AudioDeviceChanged {
+        name: Option<String>,
+    },
+    /// 插件线程的 CPU 占用持续超过阈值
+    HighCpuUsage {
+        percent: f64,
+        busiest_thread: String,
+    },
+    /// 封面的处理进度，封面没有显示在媒体浮窗中时前端可以提示用户重试
+    CoverStatus {
+        ncm_id: Option<u64>,
+        state: CoverState,
+    },
+    /// 封面解码后超过 `smtc.max_cover_kb`，后端没有使用 base64 数据，
+    /// 有封面 URL 时改由系统下载
+    CoverTooLarge {
+        ncm_id: Option<u64>,
+        decoded_bytes: usize,
+        limit_bytes: usize,
+    },
+    /// 封面的主要颜色，按占比从高到低排列，格式为 `#rrggbb`
+    CoverPalette {
+        ncm_id: Option<u64>,
+        colors: Vec<String>,
+    },
+    /// 元数据中的时长与时间线总长不一致 (例如试听片段)，已改用时间线的总长
+    DurationCorrected {
+        ncm_id: Option<u64>,
+        reported_ms: f64,
+        actual_ms: f64,
+    },
+    /// 一段连续的收听已结束 (长时间暂停或插件关闭)，时间均为 Unix 毫秒时间戳
+    SessionSummary {
+        started_at: u64,
+        ended_at: u64,
+        tracks_played: u32,
+        listened_ms: u64,
+    },
+    /// 在当前位置的基础上跳转，正数为快进
+    SeekRelative {
+        delta_ms: f64,
+    },
+    /// 新版本已下载完成，可以通过 `ApplyUpdate` 命令在下次启动时应用
+    UpdateReady {
+        version: String,
+    },
+    /// SMTC 会话已关闭，之后不会再收到事件，前端应重置对应的开关状态
+    SessionStateChanged {
+        enabled: bool,
+    },
+    /// 开启 `discord.cooperative` 后，发布 Discord 状态的插件改变，为空表示没有插件发布
+    PresenceOwnerChanged {
+        owner: Option<PresenceOwner>,
+    },
+    /// 后端关闭时报告 Discord 已断开连接
+    DiscordStatus {
+        connected: bool,
+    },
+    /// 播放模式已应用到 SMTC，前端可以据此确认媒体浮窗中的开关状态
+    PlayModeApplied {
+        is_shuffling: bool,
+        repeat_mode: RepeatMode,
+    },
+    /// 异步命令 (例如 `SelfTest`) 执行完成，通过事件的 `request_id` 对应到命令，
+    /// 字段与命令的返回值相同
+    CommandCompleted {
+        status: CommandStatus,
+        message: Option<String>,
+        data: Option<serde_json::Value>,
+    },
+    /// 插件被 `Quiesce` 暂停或被 `Resume` 恢复
+    QuiesceStateChanged {
+        quiesced: bool,
+    },
+    /// 配置文件被修改并已重新加载，机密字段已被清空
+    ConfigChanged {
+        config: Box<BackendConfig>,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoverState {
+    Fetching,
+    /// 封面已经交给 SMTC，使用 URL 的封面由系统在显示时加载
+    Ready,
+    Failed,
+}
+
+/// 带序号的事件，前端需要用 `AckEvent` 命令确认收到
+#[derive(Serialize)]
+struct SequencedEvent<'a> {
+    seq: u64,
+    /// 处理某个命令期间分发的事件带有该命令的关联 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a SmtcEvent,
+}
+
+/// 超过这个时间仍未被确认的事件会让回调被视为失效
+const EVENT_ACK_TIMEOUT: Duration = Duration::from_secs(3);
+/// 重新注册回调时只重新投递这个时间内的事件，更早的操作已经没有意义了
+const EVENT_REDELIVERY_MAX_AGE: Duration = Duration::from_secs(30);
+const MAX_PENDING_EVENTS: usize = 64;
+
+struct PendingEvent {
+    seq: u64,
+    json: String,
+    dispatched_at: Instant,
+}
+
+static NEXT_EVENT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// 已分发但还没有被前端确认的事件，按序号从小到大排列
+static PENDING_EVENTS: LazyLock<Mutex<VecDeque<PendingEvent>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+/// 事件回调的状态，用于诊断信息
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EventCallbackStatus {
+    registered: bool,
+    /// 有事件超过超时时间仍未被确认，回调可能已经失效
+    stale: bool,
+    pending_events: usize,
+    last_acked_seq: u64,
+    /// 被合并掉的重复按钮事件数
+    coalesced_button_events: u64,
+    /// SMTC 事件处理器中被捕获的 panic 次数
+    handler_panics: u64,
+    /// 因为多次 panic 而重新绑定处理器的次数
+    handler_rebinds: u64,
+}
+
+static LAST_ACKED_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 因为在合并窗口内重复按下而被丢弃的按钮事件数
+static COALESCED_BUTTON_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// 在短时间内连续按下同一个按钮时只分发第一次，避免向渲染线程堆积任务
+struct ButtonCoalescer {
+    /// 为 0 时不合并
+    window: Duration,
+    last: Mutex<Option<(mem::Discriminant<SmtcEvent>, Instant)>>,
+}
+
+impl ButtonCoalescer {
+    const fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: Mutex::new(None),
+        }
+    }
+
+    fn dispatch(&self, event: &SmtcEvent) {
+        if self.should_coalesce(event) {
+            COALESCED_BUTTON_EVENTS.fetch_add(1, Ordering::Relaxed);
+            debug!(?event, "合并短时间内重复的按钮事件");
+            return;
+        }
+        dispatch_event(event);
+    }
+
+    fn should_coalesce(&self, event: &SmtcEvent) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+        let Ok(mut last) = self.last.lock() else {
+            return false;
+        };
+
+        let now = Instant::now();
+        let kind = mem::discriminant(event);
+        if last.is_some_and(|(last_kind, at)| last_kind == kind && now - at < self.window) {
+            return true;
+        }
+        *last = Some((kind, now));
+        false
+    }
+}
+
+/// 同一按钮两次按下的间隔小于这个时间时视为按住不放产生的自动重复
+const HOLD_REPEAT_GAP: Duration = Duration::from_millis(150);
+/// 按住时两次快进/快退之间的最小间隔，键盘的自动重复通常远快于此
+const HOLD_SEEK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 把按住下一首/上一首产生的连续按钮事件转换为歌曲内的快进/快退
+///
+/// 第一次按下时还不知道是否会按住，所以照常切歌，之后的自动重复才会被转换
+struct HoldDetector {
+    threshold: Duration,
+    step_ms: f64,
+    state: Mutex<Option<HoldState>>,
+}
+
+struct HoldState {
+    button: SystemMediaTransportControlsButton,
+    started_at: Instant,
+    last_press: Instant,
+    last_seek: Option<Instant>,
+}
+
+/// 按钮事件经过按住检测后的结果
+enum HoldAction {
+    /// 普通的按下，照常分发
+    Press,
+    /// 按住期间的自动重复，忽略
+    Swallow,
+    Seek(SmtcEvent),
+}
+
+impl HoldDetector {
+    const fn new(threshold: Duration, step_ms: u64) -> Self {
+        Self {
+            threshold,
+            step_ms: step_ms as f64,
+            state: Mutex::new(None),
+        }
+    }
+
+    fn on_press(&self, button: SystemMediaTransportControlsButton) -> HoldAction {
+        let direction = match button {
+            SystemMediaTransportControlsButton::Next => 1.0,
+            SystemMediaTransportControlsButton::Previous => -1.0,
+            _ => return HoldAction::Press,
+        };
+        if self.step_ms <= 0.0 {
+            return HoldAction::Press;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return HoldAction::Press;
+        };
+
+        let now = Instant::now();
+        let Some(hold) = state
+            .as_mut()
+            .filter(|hold| hold.button == button && now - hold.last_press < HOLD_REPEAT_GAP)
+        else {
+            *state = Some(HoldState {
+                button,
+                started_at: now,
+                last_press: now,
+                last_seek: None,
+            });
+            return HoldAction::Press;
+        };
+
+        hold.last_press = now;
+        let seek_due = hold
+            .last_seek
+            .is_none_or(|at| now - at >= HOLD_SEEK_INTERVAL);
+        if now - hold.started_at < self.threshold || !seek_due {
+            return HoldAction::Swallow;
+        }
+
+        hold.last_seek = Some(now);
+        drop(state);
+        HoldAction::Seek(SmtcEvent::SeekRelative {
+            delta_ms: direction * self.step_ms,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -103,6 +435,22 @@ pub struct SmtcContext {
     player: MediaPlayer,
     tokens: SmtcHandlerTokens,
     is_enabled: bool,
+    /// 前端通过 `SetControls` 请求的按钮状态
+    controls: ControlsPayload,
+    shared: HandlerShared,
+}
+
+/// 上下文与事件处理器共享的状态，重新绑定处理器时保留
+#[derive(Debug)]
+struct HandlerShared {
+    /// 最近一次时间线更新中的总时长 (毫秒)，用于限制跳转请求的范围
+    known_duration_ms: Arc<AtomicU64>,
+    /// 当前是否处于私人 FM 等电台模式
+    radio_mode: Arc<AtomicBool>,
+    /// 是否允许在系统浮窗中拖动进度条
+    seek_enabled: Arc<AtomicBool>,
+    /// 自上次绑定以来处理器 panic 的次数
+    panics: Arc<AtomicU32>,
 }
 
 impl SmtcContext {
@@ -145,7 +493,10 @@ pub fn register_event_callback(v8_function: CefV8Value) {
         Ok(mut guard) => match callback_result {
             Ok(cb) => {
                 *guard = Some(cb);
+                CALLBACK_GENERATION.fetch_add(1, Ordering::Relaxed);
+                drop(guard);
                 debug!("SMTC 事件回调已成功注册");
+                redeliver_pending_events();
             }
             Err(e) => error!("创建回调对象失败: {e:?}"),
         },
@@ -153,6 +504,89 @@ pub fn register_event_callback(v8_function: CefV8Value) {
     }
 }
 
+pub fn notify_update_ready(version: String) {
+    dispatch_event(&SmtcEvent::UpdateReady { version });
+}
+
+pub fn notify_config_changed(config: BackendConfig) {
+    dispatch_event(&SmtcEvent::ConfigChanged {
+        config: Box::new(config.without_secrets()),
+    });
+}
+
+/// 确认序号小于等于 `seq` 的所有事件
+pub fn ack_event(seq: u64) {
+    LAST_ACKED_SEQ.fetch_max(seq, Ordering::Relaxed);
+
+    if let Ok(mut pending) = PENDING_EVENTS.lock() {
+        while pending.front().is_some_and(|event| event.seq <= seq) {
+            pending.pop_front();
+        }
+    }
+}
+
+pub fn event_callback_status() -> EventCallbackStatus {
+    let registered = GLOBAL_CALLBACK.lock().is_ok_and(|guard| guard.is_some());
+
+    let (stale, pending_events) = PENDING_EVENTS.lock().map_or((false, 0), |pending| {
+        let stale = pending
+            .front()
+            .is_some_and(|event| event.dispatched_at.elapsed() > EVENT_ACK_TIMEOUT);
+        (stale, pending.len())
+    });
+
+    EventCallbackStatus {
+        registered,
+        stale,
+        pending_events,
+        last_acked_seq: LAST_ACKED_SEQ.load(Ordering::Relaxed),
+        coalesced_button_events: COALESCED_BUTTON_EVENTS.load(Ordering::Relaxed),
+        handler_panics: HANDLER_PANICS.load(Ordering::Relaxed),
+        handler_rebinds: HANDLER_REBINDS.load(Ordering::Relaxed),
+    }
+}
+
+/// 把之前没有被确认的事件投递给新注册的回调
+fn redeliver_pending_events() {
+    let events: Vec<(u64, String)> = {
+        let Ok(mut pending) = PENDING_EVENTS.lock() else {
+            return;
+        };
+        pending.retain(|event| event.dispatched_at.elapsed() <= EVENT_REDELIVERY_MAX_AGE);
+        pending
+            .iter()
+            .map(|event| (event.seq, event.json.clone()))
+            .collect()
+    };
+
+    if events.is_empty() {
+        return;
+    }
+
+    info!(count = events.len(), "重新投递未被确认的 SMTC 事件");
+    for (seq, json) in events {
+        debug!(seq, "重新投递 SMTC 事件");
+        post_event_json(json);
+    }
+}
+
+pub fn has_event_callback() -> bool {
+    GLOBAL_CALLBACK.lock().is_ok_and(|guard| guard.is_some())
+}
+
+/// 回调所在的 V8 上下文已被销毁时清除回调，必须在渲染线程上调用
+pub fn release_callback_if_context_invalid() {
+    let released = GLOBAL_CALLBACK.lock().is_ok_and(|mut guard| {
+        guard
+            .take_if(|cb| !cb.v8_context.is_valid() || !cb.v8_function.is_valid())
+            .is_some()
+    });
+
+    if released {
+        info!("事件回调所在的 V8 上下文已被销毁，已清除回调");
+    }
+}
+
 #[instrument]
 pub fn unregister_event_callback() {
     match GLOBAL_CALLBACK.lock() {
@@ -167,18 +601,123 @@ pub fn unregister_event_callback() {
     }
 }
 
+/// 在渲染线程处理完已投递的事件之后再清除回调，保证关闭前的最后几个事件能送达前端
+fn unregister_event_callback_after_pending() {
+    let Ok(guard) = GLOBAL_CALLBACK.lock() else {
+        unregister_event_callback();
+        return;
+    };
+    let Some(v8_ctx) = guard.as_ref().map(|cb| cb.v8_context.clone()) else {
+        return;
+    };
+    let generation = CALLBACK_GENERATION.load(Ordering::Relaxed);
+    drop(guard);
+
+    let post_result = renderer_post_task_in_v8_ctx(v8_ctx, move || {
+        // 前端可能在任务执行前已经重新初始化并注册了新的回调
+        if CALLBACK_GENERATION.load(Ordering::Relaxed) == generation {
+            unregister_event_callback();
+        }
+    });
+    if post_result.is_err() {
+        warn!("向渲染线程发送任务失败，立即清除事件回调");
+        unregister_event_callback();
+    }
+}
+
+/// 向渲染线程投递一个空任务，检查事件回调所在的上下文是否还能接收任务
+///
+/// 自检命令在渲染线程上同步执行，所以只检查投递是否成功，不等待任务执行
+pub fn self_test_renderer() -> Result<(), String> {
+    let v8_ctx = GLOBAL_CALLBACK
+        .lock()
+        .map_err(|_| "事件回调锁中毒".to_string())?
+        .as_ref()
+        .map(|cb| cb.v8_context.clone())
+        .ok_or("没有注册事件回调")?;
+    renderer_post_task_in_v8_ctx(v8_ctx, || {})
+        .map_err(|e| format!("向渲染线程发送任务失败: {e:?}"))
+}
+
+/// 创建一组时间线属性，不会应用到 SMTC 上
+pub fn self_test_timeline() -> Result<()> {
+    let props = SystemMediaTransportControlsTimelineProperties::new()?;
+    props.SetStartTime(TimeSpan { Duration: 0 })?;
+    props.SetPosition(TimeSpan {
+        Duration: (1000.0 * HNS_PER_MILLISECOND) as i64,
+    })?;
+    props.SetEndTime(TimeSpan {
+        Duration: (2000.0 * HNS_PER_MILLISECOND) as i64,
+    })?;
+    Ok(())
+}
+
+/// 通知前端 SMTC 会话已关闭，然后清除事件回调
+pub fn close_session() {
+    dispatch_event(&SmtcEvent::SessionStateChanged { enabled: false });
+    unregister_event_callback_after_pending();
+}
+
 #[instrument]
-fn dispatch_event(event: &SmtcEvent) {
-    debug!(?event, "分发 SMTC 事件");
+pub fn dispatch_event(event: &SmtcEvent) {
+    if let Some(event_json) = sequence_event(event) {
+        event_sink::broadcast(&event_json);
+    }
+}
 
-    let event_json = match serde_json::to_string(&event) {
+/// 只通过 V8 回调发送给前端，不经过 HTTP 事件流等其他接收方，用于命令的结果
+pub fn dispatch_reply(event: &SmtcEvent) {
+    if let Some(event_json) = sequence_event(event) {
+        post_event_json(event_json);
+    }
+}
+
+/// 为事件分配序号并记录到待确认列表，返回要发送的 JSON
+fn sequence_event(event: &SmtcEvent) -> Option<String> {
+    let seq = NEXT_EVENT_SEQ.fetch_add(1, Ordering::Relaxed);
+    debug!(?event, seq, "分发 SMTC 事件");
+
+    let request_id = request_id::current();
+    let event_json = match serde_json::to_string(&SequencedEvent {
+        seq,
+        request_id: request_id.as_deref(),
+        event,
+    }) {
         Ok(json) => json,
         Err(e) => {
             error!("序列化SMTC事件失败: {e}");
-            return;
+            return None;
         }
     };
 
+    if let Ok(mut pending) = PENDING_EVENTS.lock() {
+        if pending.len() >= MAX_PENDING_EVENTS {
+            pending.pop_front();
+        }
+        pending.push_back(PendingEvent {
+            seq,
+            json: event_json.clone(),
+            dispatched_at: Instant::now(),
+        });
+    }
+
+    Some(event_json)
+}
+
+/// 通过前端注册的 V8 回调投递事件
+pub struct V8CallbackSink;
+
+impl EventSink for V8CallbackSink {
+    fn name(&self) -> &'static str {
+        "v8-callback"
+    }
+
+    fn send(&self, event_json: &str) {
+        post_event_json(event_json.to_owned());
+    }
+}
+
+fn post_event_json(event_json: String) {
     let maybe_v8_ctx = if let Ok(guard) = GLOBAL_CALLBACK.lock() {
         guard.as_ref().map(|cb| cb.v8_context.clone())
     } else {
@@ -218,13 +757,63 @@ fn dispatch_event(event: &SmtcEvent) {
             error!("向渲染线程发送任务失败");
         }
     } else {
-        warn!("无法分发 SMTC 事件，因为没有注册回调函数");
+        // 没有前端的环境中事件仍然可以通过其他接收方送达
+        debug!("没有注册 V8 回调函数，跳过通过回调分发事件");
     }
 }
 
+/// 静音播放源的采样率，8 位单声道，一秒的数据只有 8 KB
+const SILENT_SAMPLE_RATE: u32 = 8000;
+
+/// 让播放器拥有一个真实的播放项，但不会自动播放，也不会输出声音
+fn attach_silent_source(player: &MediaPlayer) -> Result<()> {
+    let stream = InMemoryRandomAccessStream::new()?;
+    let writer = DataWriter::CreateDataWriter(&stream)?;
+    writer.WriteBytes(&silent_wav())?;
+    writer.StoreAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+    writer.DetachStream()?;
+    stream.Seek(0)?;
+
+    let source = MediaSource::CreateFromStream(&stream, &HSTRING::from("audio/wav"))?;
+    player.SetAutoPlay(false)?;
+    player.SetIsMuted(true)?;
+    player.SetSource(&MediaPlaybackItem::Create(&source)?)?;
+    debug!("已设置静音播放源");
+    Ok(())
+}
+
+/// 一秒静音的 PCM WAV 文件，8 位采样以 128 表示静音
+fn silent_wav() -> Vec<u8> {
+    let data_len: u32 = SILENT_SAMPLE_RATE;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM，单声道
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SILENT_SAMPLE_RATE.to_le_bytes());
+    // 每秒字节数，每个采样的字节数，位深
+    wav.extend_from_slice(&SILENT_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 128);
+    wav
+}
+
 #[instrument]
 pub fn initialize() -> Result<SmtcContext> {
     let player = MediaPlayer::new()?;
+    // 按钮和播放状态都由我们手动处理，不让播放器根据自己的播放源自动更新 SMTC
+    player.CommandManager()?.SetIsEnabled(false)?;
+    if config::get().smtc.silent_source
+        && let Err(e) = attach_silent_source(&player)
+    {
+        warn!("设置静音播放源失败: {e:?}");
+    }
     let smtc = player.SystemMediaTransportControls()?;
 
     smtc.SetIsEnabled(false)?;
@@ -235,78 +824,375 @@ pub fn initialize() -> Result<SmtcContext> {
     smtc.SetIsPreviousEnabled(true)?;
     debug!("已启用各个 SMTC 控制能力");
 
+    let shared = HandlerShared {
+        known_duration_ms: Arc::new(AtomicU64::new(0)),
+        radio_mode: Arc::new(AtomicBool::new(false)),
+        seek_enabled: Arc::new(AtomicBool::new(true)),
+        panics: Arc::new(AtomicU32::new(0)),
+    };
+    let tokens = attach_handlers(&smtc, &shared)?;
+
+    let context = SmtcContext {
+        player,
+        tokens,
+        is_enabled: false,
+        controls: ControlsPayload::default(),
+        shared,
+    };
+
+    debug!("SMTC 已初始化");
+    Ok(context)
+}
+
+/// 事件处理器累计 panic 超过这个次数后重新绑定，丢弃处理器内部可能已损坏的状态
+const HANDLER_REBIND_THRESHOLD: u32 = 3;
+
+/// 所有事件处理器中发生的 panic 次数
+static HANDLER_PANICS: AtomicU64 = AtomicU64::new(0);
+static HANDLER_REBINDS: AtomicU64 = AtomicU64::new(0);
+
+/// 在 `WinRT` 回调中执行处理器，panic 不能穿过 FFI 边界传播到系统中
+fn guarded(
+    panics: &AtomicU32,
+    handler: &'static str,
+    f: impl FnOnce() -> windows::core::Result<()>,
+) -> windows::core::Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        HANDLER_PANICS.fetch_add(1, Ordering::Relaxed);
+        let count = panics.fetch_add(1, Ordering::Relaxed) + 1;
+        error!(
+            handler,
+            count,
+            "SMTC 事件处理器发生 Panic: {}",
+            logger::panic_message(&*payload)
+        );
+        Ok(())
+    })
+}
+
+/// 处理器 panic 次数达到阈值时移除并重新绑定所有处理器
+pub fn rebind_handlers_if_poisoned(ctx: &mut SmtcContext) -> Result<()> {
+    if ctx.shared.panics.load(Ordering::Relaxed) < HANDLER_REBIND_THRESHOLD {
+        return Ok(());
+    }
+
+    warn!("SMTC 事件处理器多次 Panic，正在重新绑定");
+    ctx.remove_handlers()?;
+    ctx.shared.panics.store(0, Ordering::Relaxed);
+    ctx.tokens = attach_handlers(&ctx.smtc()?, &ctx.shared)?;
+    HANDLER_REBINDS.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+fn attach_handlers(
+    smtc: &SystemMediaTransportControls,
+    shared: &HandlerShared,
+) -> Result<SmtcHandlerTokens> {
+    let smtc_config = config::get().smtc;
+    let coalescer = Arc::new(ButtonCoalescer::new(Duration::from_millis(
+        smtc_config.button_coalesce_ms,
+    )));
+    let hold_detector = HoldDetector::new(
+        Duration::from_millis(smtc_config.hold_seek_threshold_ms),
+        smtc_config.hold_seek_step_ms,
+    );
+
+    let gesture_detector = GestureDetector::new(
+        Duration::from_millis(smtc_config.gesture_window_ms),
+        Arc::clone(&coalescer),
+    );
+
+    let button_coalescer = Arc::clone(&coalescer);
+    let button_panics = Arc::clone(&shared.panics);
     let handler = TypedEventHandler::new(
         move |_sender: Ref<SystemMediaTransportControls>,
               args: Ref<SystemMediaTransportControlsButtonPressedEventArgs>|
               -> windows::core::Result<()> {
-            if let Some(args) = args.as_ref() {
-                let button = args.Button()?;
-                debug!(?button, "SMTC 按钮被按下");
-                let event = match button {
-                    SystemMediaTransportControlsButton::Play => SmtcEvent::Play,
-                    SystemMediaTransportControlsButton::Pause => SmtcEvent::Pause,
-                    SystemMediaTransportControlsButton::Stop => SmtcEvent::Stop,
-                    SystemMediaTransportControlsButton::Next => SmtcEvent::NextSong,
-                    SystemMediaTransportControlsButton::Previous => SmtcEvent::PreviousSong,
-                    _ => return Ok(()),
-                };
-                dispatch_event(&event);
-            }
-            Ok(())
+            guarded(&button_panics, "button_pressed", || {
+                if let Some(args) = args.as_ref() {
+                    let button = args.Button()?;
+                    debug!(?button, "SMTC 按钮被按下");
+                    dispatch_button(&hold_detector, &gesture_detector, &button_coalescer, button);
+                }
+                Ok(())
+            })
         },
     );
     let button_pressed = smtc.ButtonPressed(&handler)?;
 
+    let shuffle_coalescer = Arc::clone(&coalescer);
+    let shuffle_radio_mode = Arc::clone(&shared.radio_mode);
+    let shuffle_panics = Arc::clone(&shared.panics);
     let shuffle_handler = TypedEventHandler::new(
         move |_: Ref<SystemMediaTransportControls>,
               _: Ref<ShuffleEnabledChangeRequestedEventArgs>| {
-            debug!("SMTC 请求切换随机播放模式");
-            dispatch_event(&SmtcEvent::ToggleShuffle);
-            Ok(())
+            guarded(&shuffle_panics, "shuffle_changed", || {
+                dispatch_play_mode_request(
+                    &shuffle_coalescer,
+                    &shuffle_radio_mode,
+                    &SmtcEvent::ToggleShuffle,
+                );
+                Ok(())
+            })
         },
     );
     let shuffle_changed = smtc.ShuffleEnabledChangeRequested(&shuffle_handler)?;
 
+    let repeat_radio_mode = Arc::clone(&shared.radio_mode);
+    let repeat_panics = Arc::clone(&shared.panics);
     let repeat_handler = TypedEventHandler::new(
         move |_: Ref<SystemMediaTransportControls>,
               _: Ref<AutoRepeatModeChangeRequestedEventArgs>| {
-            debug!("SMTC 请求切换重复播放模式");
-            dispatch_event(&SmtcEvent::ToggleRepeat);
-            Ok(())
+            guarded(&repeat_panics, "repeat_changed", || {
+                dispatch_play_mode_request(
+                    &coalescer,
+                    &repeat_radio_mode,
+                    &SmtcEvent::ToggleRepeat,
+                );
+                Ok(())
+            })
         },
     );
     let repeat_changed = smtc.AutoRepeatModeChangeRequested(&repeat_handler)?;
 
+    let seek_duration_ms = Arc::clone(&shared.known_duration_ms);
+    let handler_seek_enabled = Arc::clone(&shared.seek_enabled);
+    let seek_panics = Arc::clone(&shared.panics);
+    let seek_debouncer = SeekDebouncer::new(Duration::from_millis(smtc_config.seek_debounce_ms));
     let seek_handler = TypedEventHandler::new(
         move |_: Ref<SystemMediaTransportControls>,
               args: Ref<PlaybackPositionChangeRequestedEventArgs>|
               -> windows::core::Result<()> {
-            if let Some(args) = args.as_ref() {
-                let position = args.RequestedPlaybackPosition()?;
-                let position_ms = (position.Duration as f64) / HNS_PER_MILLISECOND;
-                debug!(position_ms, "SMTC 请求跳转播放位置");
-                dispatch_event(&SmtcEvent::Seek { position_ms });
-            }
-            Ok(())
+            guarded(&seek_panics, "seek_requested", || {
+                if !handler_seek_enabled.load(Ordering::Relaxed) {
+                    debug!("前端已禁用跳转，忽略 SMTC 跳转请求");
+                    return Ok(());
+                }
+                if let Some(args) = args.as_ref() {
+                    let position = args.RequestedPlaybackPosition()?;
+                    let requested_ms = (position.Duration as f64) / HNS_PER_MILLISECOND;
+                    let position_ms = clamp_seek_position(
+                        requested_ms,
+                        seek_duration_ms.load(Ordering::Relaxed) as f64,
+                    );
+                    debug!(requested_ms, position_ms, "SMTC 请求跳转播放位置");
+                    seek_debouncer.request(position_ms);
+                }
+                Ok(())
+            })
         },
     );
     let seek_requested = smtc.PlaybackPositionChangeRequested(&seek_handler)?;
 
     debug!("SMTC 事件处理器已全部附加");
+    Ok(SmtcHandlerTokens {
+        button_pressed,
+        shuffle_changed,
+        repeat_changed,
+        seek_requested,
+    })
+}
 
-    let context = SmtcContext {
-        player,
-        tokens: SmtcHandlerTokens {
-            button_pressed,
-            shuffle_changed,
-            repeat_changed,
-            seek_requested,
+const fn button_event(button: SystemMediaTransportControlsButton) -> Option<SmtcEvent> {
+    match button {
+        SystemMediaTransportControlsButton::Play => Some(SmtcEvent::Play),
+        SystemMediaTransportControlsButton::Pause => Some(SmtcEvent::Pause),
+        SystemMediaTransportControlsButton::Stop => Some(SmtcEvent::Stop),
+        SystemMediaTransportControlsButton::Next => Some(SmtcEvent::NextSong),
+        SystemMediaTransportControlsButton::Previous => Some(SmtcEvent::PreviousSong),
+        _ => None,
+    }
+}
+
+fn dispatch_button(
+    hold_detector: &HoldDetector,
+    gesture_detector: &GestureDetector,
+    coalescer: &ButtonCoalescer,
+    button: SystemMediaTransportControlsButton,
+) {
+    match hold_detector.on_press(button) {
+        HoldAction::Press => match button_event(button) {
+            Some(event @ (SmtcEvent::Play | SmtcEvent::Pause)) => gesture_detector.press(event),
+            Some(event) => coalescer.dispatch(&event),
+            None => {}
         },
-        is_enabled: false,
+        HoldAction::Swallow => {}
+        HoldAction::Seek(event) => dispatch_event(&event),
+    }
+}
+
+/// 把快速连按播放/暂停转换为切歌，部分耳机用这种方式代替单独的切歌按键
+///
+/// 连按两次为下一首，三次或更多为上一首。需要等待一段时间确认没有后续按下，
+/// 所以在单独的线程中计时，处理器被移除后通道断开，线程随之退出
+struct GestureDetector {
+    tx: Option<Sender<SmtcEvent>>,
+    coalescer: Arc<ButtonCoalescer>,
+}
+
+impl GestureDetector {
+    fn new(window: Duration, coalescer: Arc<ButtonCoalescer>) -> Self {
+        if window.is_zero() {
+            return Self {
+                tx: None,
+                coalescer,
+            };
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let thread_coalescer = Arc::clone(&coalescer);
+        match thread::Builder::new()
+            .name("smtc-gesture-thread".into())
+            .spawn(move || run_gesture_loop(&rx, window, &thread_coalescer))
+        {
+            Ok(_) => Self {
+                tx: Some(tx),
+                coalescer,
+            },
+            Err(e) => {
+                warn!("无法启动按键手势线程，将直接分发按钮事件: {e}");
+                Self {
+                    tx: None,
+                    coalescer,
+                }
+            }
+        }
+    }
+
+    fn press(&self, event: SmtcEvent) {
+        match &self.tx {
+            Some(tx) => {
+                if let Err(mpsc::SendError(event)) = tx.send(event) {
+                    self.coalescer.dispatch(&event);
+                }
+            }
+            None => self.coalescer.dispatch(&event),
+        }
+    }
+}
+
+fn run_gesture_loop(rx: &Receiver<SmtcEvent>, window: Duration, coalescer: &ButtonCoalescer) {
+    while let Ok(first) = rx.recv() {
+        let mut presses = 1u32;
+
+        let disconnected = loop {
+            match rx.recv_timeout(window) {
+                Ok(_) => presses += 1,
+                Err(RecvTimeoutError::Timeout) => break false,
+                Err(RecvTimeoutError::Disconnected) => break true,
+            }
+        };
+
+        match presses {
+            1 => coalescer.dispatch(&first),
+            2 => {
+                debug!("连按两次播放/暂停，切换到下一首");
+                dispatch_event(&SmtcEvent::NextSong);
+            }
+            _ => {
+                debug!(presses, "连按多次播放/暂停，切换到上一首");
+                dispatch_event(&SmtcEvent::PreviousSong);
+            }
+        }
+
+        if disconnected {
+            return;
+        }
+    }
+}
+
+/// 电台模式没有播放顺序，忽略系统浮窗切换随机和重复播放的请求
+fn dispatch_play_mode_request(
+    coalescer: &ButtonCoalescer,
+    radio_mode: &AtomicBool,
+    event: &SmtcEvent,
+) {
+    if radio_mode.load(Ordering::Relaxed) {
+        debug!(?event, "电台模式下忽略 SMTC 切换播放模式的请求");
+        return;
+    }
+    debug!(?event, "SMTC 请求切换播放模式");
+    coalescer.dispatch(event);
+}
+
+/// 合并短时间内连续的跳转请求，只分发最后一次
+///
+/// 拖动系统浮窗的进度条时 Windows 会连续触发很多次跳转请求，
+/// 逐个转发会让网易云的播放器在中间位置之间来回跳转而卡顿
+struct SeekDebouncer {
+    tx: Option<Sender<f64>>,
+}
+
+impl SeekDebouncer {
+    fn new(quiet_period: Duration) -> Self {
+        if quiet_period.is_zero() {
+            return Self { tx: None };
+        }
+
+        let (tx, rx) = mpsc::channel();
+        match thread::Builder::new()
+            .name("smtc-seek-debounce-thread".into())
+            .spawn(move || run_seek_debounce_loop(&rx, quiet_period))
+        {
+            Ok(_) => Self { tx: Some(tx) },
+            Err(e) => {
+                warn!("无法启动跳转防抖线程，将直接分发跳转请求: {e}");
+                Self { tx: None }
+            }
+        }
+    }
+
+    fn request(&self, position_ms: f64) {
+        if let Some(tx) = &self.tx
+            && tx.send(position_ms).is_ok()
+        {
+            return;
+        }
+
+        dispatch_event(&SmtcEvent::Seek { position_ms });
+    }
+}
+
+fn run_seek_debounce_loop(rx: &Receiver<f64>, quiet_period: Duration) {
+    while let Ok(mut position_ms) = rx.recv() {
+        let mut merged_count = 0u32;
+
+        // 处理器被移除后通道会断开，此时仍然分发最后一次请求
+        let disconnected = loop {
+            match rx.recv_timeout(quiet_period) {
+                Ok(next) => {
+                    position_ms = next;
+                    merged_count += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => break false,
+                Err(RecvTimeoutError::Disconnected) => break true,
+            }
+        };
+
+        if merged_count > 0 {
+            debug!(merged_count, position_ms, "已合并连续的跳转请求");
+        }
+        dispatch_event(&SmtcEvent::Seek { position_ms });
+
+        if disconnected {
+            return;
+        }
+    }
+}
+
+/// 将跳转位置限制在 `[0, duration_ms]` 内，时长未知 (为 0) 时只限制下界
+fn clamp_seek_position(position_ms: f64, duration_ms: f64) -> f64 {
+    let position_ms = if position_ms.is_finite() {
+        position_ms.max(0.0)
+    } else {
+        0.0
     };
 
-    debug!("SMTC 已初始化");
-    Ok(context)
+    if duration_ms > 0.0 {
+        position_ms.min(duration_ms)
+    } else {
+        position_ms
+    }
 }
 
 #[instrument]
@@ -327,8 +1213,14 @@ pub fn update_play_state(ctx: &SmtcContext, status: PlaybackStatus) -> Result<()
 }
 
 #[instrument]
-pub fn update_timeline(ctx: &SmtcContext, current_ms: f64, total_ms: f64) -> Result<()> {
-    if !ctx.is_enabled {
+pub fn update_timeline(ctx: &SmtcContext, timeline: Timeline) -> Result<()> {
+    let current_ms = timeline.position_ms();
+    let total_ms = timeline.duration_ms();
+    ctx.shared
+        .known_duration_ms
+        .store(total_ms as u64, Ordering::Relaxed);
+
+    if !ctx.is_enabled || !platform::capabilities().smtc_timeline {
         return Ok(());
     }
 
@@ -340,82 +1232,242 @@ pub fn update_timeline(ctx: &SmtcContext, current_ms: f64, total_ms: f64) -> Res
     props.SetEndTime(TimeSpan {
         Duration: (total_ms * HNS_PER_MILLISECOND) as i64,
     })?;
+    // 可跳转范围为空时系统浮窗不允许拖动进度条
+    let max_seek_ms = if ctx.shared.seek_enabled.load(Ordering::Relaxed) {
+        total_ms
+    } else {
+        0.0
+    };
+    props.SetMinSeekTime(TimeSpan { Duration: 0 })?;
+    props.SetMaxSeekTime(TimeSpan {
+        Duration: (max_seek_ms * HNS_PER_MILLISECOND) as i64,
+    })?;
 
     let smtc = ctx.smtc()?;
     smtc.UpdateTimelineProperties(&props)?;
     Ok(())
 }
 
+/// 按钮的可用状态与 SMTC 是否启用无关，所以总是立即应用。
+/// 进度条的可拖动范围会在下一次时间线更新时生效
+#[instrument]
+pub fn set_controls(ctx: &mut SmtcContext, controls: ControlsPayload) -> Result<()> {
+    ctx.controls = controls;
+    apply_controls(ctx)
+}
+
+/// 进入电台模式时禁用上一首和跳转，并关闭随机和重复播放，离开时恢复前端请求的按钮状态
+#[instrument]
+pub fn set_playback_context(ctx: &SmtcContext, context: PlaybackContext) -> Result<()> {
+    let radio = context == PlaybackContext::Radio;
+    if ctx.shared.radio_mode.swap(radio, Ordering::Relaxed) == radio {
+        return Ok(());
+    }
+
+    info!(?context, "播放来源已改变");
+    apply_controls(ctx)?;
+
+    if radio {
+        let capabilities = platform::capabilities();
+        let smtc = ctx.smtc()?;
+        if capabilities.smtc_shuffle {
+            smtc.SetShuffleEnabled(false)?;
+        }
+        if capabilities.smtc_auto_repeat {
+            smtc.SetAutoRepeatMode(MediaPlaybackAutoRepeatMode::None)?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_controls(ctx: &SmtcContext) -> Result<()> {
+    let controls = ctx.controls;
+    let radio = ctx.shared.radio_mode.load(Ordering::Relaxed);
+
+    let smtc = ctx.smtc()?;
+    smtc.SetIsPlayEnabled(controls.play)?;
+    smtc.SetIsPauseEnabled(controls.pause)?;
+    smtc.SetIsNextEnabled(controls.next)?;
+    smtc.SetIsPreviousEnabled(controls.previous && !radio)?;
+    smtc.SetIsStopEnabled(controls.stop)?;
+    ctx.shared
+        .seek_enabled
+        .store(controls.seek && !radio, Ordering::Relaxed);
+    debug!(?controls, radio, "SMTC 控制按钮已更新");
+    Ok(())
+}
+
 #[instrument]
 pub fn update_play_mode(
     ctx: &SmtcContext,
     is_shuffling: bool,
     repeat_mode: &RepeatMode,
 ) -> Result<()> {
-    if !ctx.is_enabled {
+    let capabilities = platform::capabilities();
+    if !ctx.is_enabled || !(capabilities.smtc_shuffle || capabilities.smtc_auto_repeat) {
+        return Ok(());
+    }
+
+    // 电台模式没有播放顺序的概念
+    if ctx.shared.radio_mode.load(Ordering::Relaxed) {
         return Ok(());
     }
 
     let smtc = ctx.smtc()?;
-    smtc.SetShuffleEnabled(is_shuffling)?;
+    if capabilities.smtc_shuffle {
+        smtc.SetShuffleEnabled(is_shuffling)?;
+    }
 
-    let repeat_mode_win = match repeat_mode {
-        RepeatMode::Track => MediaPlaybackAutoRepeatMode::Track,
-        RepeatMode::List => MediaPlaybackAutoRepeatMode::List,
-        RepeatMode::None | RepeatMode::AI => MediaPlaybackAutoRepeatMode::None,
-    };
-    smtc.SetAutoRepeatMode(repeat_mode_win)?;
+    if capabilities.smtc_auto_repeat {
+        let repeat_mode_win = match repeat_mode {
+            RepeatMode::Track => MediaPlaybackAutoRepeatMode::Track,
+            RepeatMode::List => MediaPlaybackAutoRepeatMode::List,
+            RepeatMode::None | RepeatMode::AI => MediaPlaybackAutoRepeatMode::None,
+        };
+        smtc.SetAutoRepeatMode(repeat_mode_win)?;
+    }
+
+    dispatch_event(&SmtcEvent::PlayModeApplied {
+        is_shuffling,
+        repeat_mode: repeat_mode.clone(),
+    });
     Ok(())
 }
 
+#[instrument(skip_all)]
 fn create_cover_stream_ref(cover: Option<&CoverPayload>) -> Option<RandomAccessStreamReference> {
-    match cover {
-        None => {
-            warn!("未提供封面, 将清空现有封面");
-            None
+    let Some(payload) = cover else {
+        warn!("未提供封面, 将清空现有封面");
+        return None;
+    };
+
+    let Some(base64_data) = &payload.base64 else {
+        return create_cover_from_url(payload.url.as_deref());
+    };
+
+    let limit = max_cover_bytes();
+    let decoded_bytes = decoded_len(base64_data);
+    if limit.is_some_and(|limit| decoded_bytes > limit) {
+        warn!(decoded_bytes, ?limit, "封面超过大小上限，改用封面 URL");
+        return create_cover_from_url(payload.url.as_deref());
+    }
+
+    debug!("正在从 Base64 数据解码封面");
+    let start_time = Instant::now();
+    match create_cover_from_base64(base64_data) {
+        Ok(stream_ref) => {
+            debug!(duration = ?start_time.elapsed(), "封面 Base64 解码完成");
+            Some(stream_ref)
+        }
+        Err(e) => {
+            warn!("从 Base64 创建封面失败: {e:?}");
+            create_cover_from_url(payload.url.as_deref())
         }
-        Some(payload) => {
-            if let Some(base64_data) = &payload.base64 {
-                debug!("正在从 Base64 数据解码封面");
-                let start_time = Instant::now();
+    }
+}
 
-                let bytes = match general_purpose::STANDARD.decode(base64_data) {
-                    Ok(b) => {
-                        let elapsed = start_time.elapsed();
-                        debug!(duration = ?elapsed, "封面 Base64 解码完成");
-                        b
-                    }
-                    Err(e) => {
-                        warn!("解码封面 Base64 失败: {e}");
-                        return create_cover_from_url(payload.url.as_deref());
-                    }
-                };
-
-                let stream_result: windows::core::Result<RandomAccessStreamReference> = (|| {
-                    let stream = InMemoryRandomAccessStream::new()?;
-                    let writer = DataWriter::CreateDataWriter(&stream)?;
-                    writer.WriteBytes(&bytes)?;
-                    writer.StoreAsync()?.join()?;
-                    writer.DetachStream()?;
-                    stream.Seek(0)?;
-                    RandomAccessStreamReference::CreateFromStream(&stream)
-                })(
-                );
+/// 每次解码的 Base64 字符数，必须是 4 的倍数才能在中间切分
+const COVER_DECODE_CHUNK: usize = 64 * 1024;
 
-                match stream_result {
-                    Ok(stream_ref) => Some(stream_ref),
-                    Err(e) => {
-                        error!("创建封面内存流失败: {e:?}");
-                        None
-                    }
-                }
-            } else {
-                create_cover_from_url(payload.url.as_deref())
-            }
+/// 分块解码并写入内存流，避免同时持有完整的解码结果和流中的副本
+fn create_cover_from_base64(base64_data: &str) -> Result<RandomAccessStreamReference> {
+    let stream = InMemoryRandomAccessStream::new()?;
+    let writer = DataWriter::CreateDataWriter(&stream)?;
+    let mut buffer = vec![0; COVER_DECODE_CHUNK / 4 * 3];
+    for chunk in base64_data.as_bytes().chunks(COVER_DECODE_CHUNK) {
+        let len = general_purpose::STANDARD.decode_slice(chunk, &mut buffer)?;
+        writer.WriteBytes(&buffer[..len])?;
+        writer.StoreAsync()?.join_timeout(LOCAL_TIMEOUT)?;
+    }
+    writer.DetachStream()?;
+    stream.Seek(0)?;
+    Ok(RandomAccessStreamReference::CreateFromStream(&stream)?)
+}
+
+/// Base64 解码后的大致字节数
+const fn decoded_len(base64_data: &str) -> usize {
+    base64_data.len() / 4 * 3
+}
+
+fn max_cover_bytes() -> Option<usize> {
+    let max_cover_kb = config::get().smtc.max_cover_kb;
+    (max_cover_kb > 0).then(|| max_cover_kb as usize * 1024)
+}
+
+/// 预先准备好的下一首歌曲的封面
+struct PrefetchedCover {
+    ncm_id: u64,
+    cover: CoverPayload,
+    stream_ref: RandomAccessStreamReference,
+}
+
+/// 只保留最近一次预取的封面，切歌时如果正好是这首歌就直接使用
+static PREFETCHED_COVER: Mutex<Option<PrefetchedCover>> = Mutex::new(None);
+
+/// 在后台准备即将播放的歌曲的封面，URL 封面会被提前下载到内存中，
+/// 这样切歌时媒体浮窗可以立即显示封面，而不需要等系统下载
+pub fn prefetch_cover(payload: PrefetchCoverPayload) {
+    let spawn_result = tasks::spawn("cover-prefetch-thread", move |token| {
+        let PrefetchCoverPayload { ncm_id, cover } = payload;
+        let stream_ref = if cover.base64.is_some() {
+            create_cover_stream_ref(Some(&cover))
+        } else if let Some(url) = cover.url.as_deref() {
+            download_cover(url)
+                .inspect_err(|e| debug!("预取封面 {url} 失败: {e:?}"))
+                .ok()
+        } else {
+            None
+        };
+
+        let Some(stream_ref) = stream_ref else {
+            return;
+        };
+        if token.is_cancelled() {
+            return;
+        }
+
+        debug!(ncm_id, "已预取封面");
+        if let Ok(mut prefetched) = PREFETCHED_COVER.lock() {
+            *prefetched = Some(PrefetchedCover {
+                ncm_id,
+                cover,
+                stream_ref,
+            });
         }
+    });
+
+    if let Err(e) = spawn_result {
+        debug!("无法启动封面预取线程: {e}");
     }
 }
 
+fn download_cover(url: &str) -> windows::core::Result<RandomAccessStreamReference> {
+    let uri = Uri::CreateUri(&HSTRING::from(url))?;
+    let source = RandomAccessStreamReference::CreateFromUri(&uri)?
+        .OpenReadAsync()?
+        .join_timeout(NETWORK_TIMEOUT)?;
+
+    let stream = InMemoryRandomAccessStream::new()?;
+    RandomAccessStream::CopyAsync(&source.GetInputStreamAt(0)?, &stream.GetOutputStreamAt(0)?)?
+        .join_timeout(NETWORK_TIMEOUT)?;
+    stream.Seek(0)?;
+    RandomAccessStreamReference::CreateFromStream(&stream)
+}
+
+/// 取出与当前歌曲和封面都匹配的预取结果
+fn take_prefetched_cover(payload: &MetadataPayload) -> Option<RandomAccessStreamReference> {
+    let ncm_id = payload.ncm_id?;
+    let cover = payload.cover.as_ref()?;
+    let mut prefetched = PREFETCHED_COVER.lock().ok()?;
+    // 元数据带有 base64 时解码很快，只有 URL 封面需要完全一致
+    prefetched
+        .take_if(|p| {
+            p.ncm_id == ncm_id
+                && (p.cover == *cover || (cover.base64.is_none() && p.cover.url == cover.url))
+        })
+        .map(|p| p.stream_ref)
+}
+
 fn create_cover_from_url(url: Option<&str>) -> Option<RandomAccessStreamReference> {
     let url = url?;
     debug!("正在从 URL 创建封面引用: {url}");
@@ -436,8 +1488,30 @@ fn create_cover_from_url(url: Option<&str>) -> Option<RandomAccessStreamReferenc
     }
 }
 
-#[instrument]
-pub fn update_metadata(ctx: &SmtcContext, payload: &MetadataPayload) -> Result<()> {
+/// 对 SMTC 显示信息的一组修改，只有调用 [`DisplayUpdate::commit`] 时才会调用一次 `Update()`，
+/// 避免同一批命令中的多次修改让媒体浮窗闪烁
+pub struct DisplayUpdate {
+    updater: SystemMediaTransportControlsDisplayUpdater,
+}
+
+pub fn begin_update(ctx: &SmtcContext) -> Result<DisplayUpdate> {
+    let updater = ctx.smtc()?.DisplayUpdater()?;
+    Ok(DisplayUpdate { updater })
+}
+
+impl DisplayUpdate {
+    pub fn commit(self) -> Result<()> {
+        info_span!("display_updater_update").in_scope(|| self.updater.Update())?;
+        Ok(())
+    }
+}
+
+#[instrument(skip(update))]
+pub fn update_metadata(
+    ctx: &SmtcContext,
+    update: &DisplayUpdate,
+    payload: &MetadataPayload,
+) -> Result<()> {
     if !ctx.is_enabled {
         return Ok(());
     }
@@ -450,10 +1524,46 @@ pub fn update_metadata(ctx: &SmtcContext, payload: &MetadataPayload) -> Result<(
         "正在更新 SMTC 歌曲元数据"
     );
 
-    let thumbnail_stream_ref = create_cover_stream_ref(payload.cover.as_ref());
+    let has_cover = payload.cover.is_some();
+    if has_cover {
+        dispatch_event(&SmtcEvent::CoverStatus {
+            ncm_id: payload.ncm_id,
+            state: CoverState::Fetching,
+        });
+    }
+
+    if let Some(base64_data) = payload.cover.as_ref().and_then(|c| c.base64.as_deref())
+        && let Some(limit_bytes) = max_cover_bytes()
+        && decoded_len(base64_data) > limit_bytes
+    {
+        dispatch_event(&SmtcEvent::CoverTooLarge {
+            ncm_id: payload.ncm_id,
+            decoded_bytes: decoded_len(base64_data),
+            limit_bytes,
+        });
+    }
 
-    let smtc = ctx.smtc()?;
-    let updater = smtc.DisplayUpdater()?;
+    let thumbnail_stream_ref = take_prefetched_cover(payload).map_or_else(
+        || create_cover_stream_ref(payload.cover.as_ref()),
+        |stream_ref| {
+            debug!("使用预取的封面");
+            Some(stream_ref)
+        },
+    );
+
+    if has_cover {
+        let state = if thumbnail_stream_ref.is_some() {
+            CoverState::Ready
+        } else {
+            CoverState::Failed
+        };
+        dispatch_event(&SmtcEvent::CoverStatus {
+            ncm_id: payload.ncm_id,
+            state,
+        });
+    }
+
+    let updater = &update.updater;
     updater.SetType(MediaPlaybackType::Music)?;
 
     let props = updater.MusicProperties()?;
@@ -465,10 +1575,8 @@ pub fn update_metadata(ctx: &SmtcContext, payload: &MetadataPayload) -> Result<(
     genres_collection.Clear()?;
 
     // 让部分应用可以精确匹配歌曲
-    if let Some(ncm_id) = payload.ncm_id
-        && ncm_id > 0
-    {
-        genres_collection.Append(&HSTRING::from(format!("NCM-{ncm_id}")))?;
+    for hint in match_hint_genres(&config::get().smtc.match_hints, payload) {
+        genres_collection.Append(&HSTRING::from(hint))?;
     }
 
     if let Some(stream_ref) = thumbnail_stream_ref.as_ref() {
@@ -478,17 +1586,53 @@ pub fn update_metadata(ctx: &SmtcContext, payload: &MetadataPayload) -> Result<(
         debug!("SMTC 封面已清空");
     }
 
-    updater.Update()?;
+    Ok(())
+}
+
+fn match_hint_genres(hints: &MatchHints, payload: &MetadataPayload) -> Vec<String> {
+    let prefix = &hints.prefix;
+    let mut genres = Vec::new();
+
+    if hints.include_song_id
+        && let Some(ncm_id) = payload.ncm_id
+        && ncm_id > 0
+    {
+        genres.push(format!("{prefix}-{ncm_id}"));
+    }
+
+    if hints.include_album_id
+        && let Some(album_id) = payload.album_id
+        && album_id > 0
+    {
+        genres.push(format!("{prefix}-ALBUM-{album_id}"));
+    }
+
+    if hints.include_artist_id {
+        genres.extend(
+            payload
+                .artist_ids
+                .iter()
+                .filter(|&&id| id > 0)
+                .map(|id| format!("{prefix}-ARTIST-{id}")),
+        );
+    }
+
+    genres
+}
+
+/// 其他网易云实例发布状态时隐藏本实例的媒体浮窗，但保留事件回调和已设置的元数据
+pub fn set_standby(ctx: &SmtcContext, standby: bool) -> Result<()> {
+    ctx.smtc()?.SetIsEnabled(ctx.is_enabled && !standby)?;
     Ok(())
 }
 
 pub fn set_enabled(ctx: &mut SmtcContext, enabled: bool) -> Result<()> {
     ctx.is_enabled = enabled;
     let smtc = ctx.smtc()?;
-    smtc.SetIsEnabled(enabled)?;
+    smtc.SetIsEnabled(enabled && instance::is_active())?;
 
     if !enabled {
-        unregister_event_callback();
+        close_session();
     }
 
     Ok(())
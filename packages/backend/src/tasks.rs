@@ -0,0 +1,135 @@
+use std::{
+    io,
+    sync::{
+        Arc,
+        Condvar,
+        Mutex,
+    },
+    thread::{
+        self,
+        JoinHandle,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use tracing::{
+    debug,
+    warn,
+};
+
+/// 同时存在的后台任务上限，防止轮询线程随功能增加无限制地增长
+const MAX_TASKS: usize = 16;
+/// 关闭时等待每个任务退出的总时长
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 后台任务的取消标记，任务应使用 [`CancellationToken::sleep`] 代替 `thread::sleep`，
+/// 这样关闭时可以立即被唤醒
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<(Mutex<bool>, Condvar)>);
+
+impl CancellationToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.0.lock().map_or(true, |cancelled| *cancelled)
+    }
+
+    /// 等待指定时长，返回 `false` 表示等待期间任务已被取消
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let (lock, condvar) = &*self.0;
+        let Ok(guard) = lock.lock() else {
+            return false;
+        };
+        condvar
+            .wait_timeout_while(guard, duration, |cancelled| !*cancelled)
+            .is_ok_and(|(cancelled, _)| !*cancelled)
+    }
+
+    fn cancel(&self) {
+        let (lock, condvar) = &*self.0;
+        if let Ok(mut cancelled) = lock.lock() {
+            *cancelled = true;
+        }
+        condvar.notify_all();
+    }
+}
+
+struct Task {
+    name: String,
+    token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+static TASKS: Mutex<Vec<Task>> = Mutex::new(Vec::new());
+
+/// 在命名线程中启动一个受管理的后台任务，关闭插件时会统一取消
+pub fn spawn<F>(name: &str, f: F) -> io::Result<()>
+where
+    F: FnOnce(CancellationToken) + Send + 'static,
+{
+    let mut tasks = TASKS
+        .lock()
+        .map_err(|_| io::Error::other("后台任务列表已损坏"))?;
+    tasks.retain(|task| !task.handle.is_finished());
+    if tasks.len() >= MAX_TASKS {
+        return Err(io::Error::other(format!(
+            "后台任务数量已达到上限 {MAX_TASKS}"
+        )));
+    }
+
+    let token = CancellationToken::default();
+    let task_token = token.clone();
+    let handle = thread::Builder::new()
+        .name(name.into())
+        .spawn(move || f(task_token))?;
+
+    tasks.push(Task {
+        name: name.into(),
+        token,
+        handle,
+    });
+    drop(tasks);
+    Ok(())
+}
+
+/// 取消所有后台任务并等待它们退出，超时仍未退出的任务会被放弃
+pub fn shutdown() {
+    cancel_where(|_| true);
+}
+
+/// 取消除 `keep` 之外的所有后台任务，这些任务之后可以通过各自的 `init` 重新启动
+pub fn cancel_except(keep: &[&str]) {
+    cancel_where(|name| !keep.contains(&name));
+}
+
+fn cancel_where(should_cancel: impl Fn(&str) -> bool) {
+    let tasks: Vec<Task> = TASKS
+        .lock()
+        .map(|mut tasks| {
+            let (cancelled, kept) = std::mem::take(&mut *tasks)
+                .into_iter()
+                .partition(|task| should_cancel(&task.name));
+            *tasks = kept;
+            cancelled
+        })
+        .unwrap_or_default();
+
+    for task in &tasks {
+        task.token.cancel();
+    }
+
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    for task in tasks {
+        while !task.handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        if task.handle.is_finished() {
+            let _ = task.handle.join();
+            debug!(task = task.name, "后台任务已退出");
+        } else {
+            warn!(task = task.name, "后台任务未能在关闭前退出");
+        }
+    }
+}
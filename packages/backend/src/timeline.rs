@@ -0,0 +1,386 @@
+use std::{
+    sync::Mutex,
+    time::Instant,
+};
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    clock,
+    model::{
+        PlaybackStatus,
+        TimelinePayload,
+    },
+};
+
+/// 报告的进度与推算的进度相差超过这个值时视为跳转 (毫秒)
+const SEEK_THRESHOLD_MS: f64 = 1_500.0;
+
+/// 单曲循环时，从距离结尾这么近的位置回到距离开头这么近的位置视为重新播放 (毫秒)
+const LOOP_TOLERANCE_MS: f64 = 3_000.0;
+
+/// 校验过的时间线，进度和总时长都是有限的非负数，进度不超过总时长
+///
+/// 反序列化时就会校验，所以 `UpdateTimeline` 命令中不会出现无效的时间线
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "TimelinePayload", into = "TimelinePayload")]
+pub struct Timeline {
+    position_ms: f64,
+    duration_ms: f64,
+}
+
+impl Timeline {
+    /// `NaN`、无穷大和负数会被视为无效数据并返回错误，
+    /// 而当前进度略微超过总时长的情况会被修正为总时长
+    pub fn new(position_ms: f64, duration_ms: f64) -> Result<Self, String> {
+        if !position_ms.is_finite() || !duration_ms.is_finite() {
+            return Err(format!(
+                "无效的时间线数据: currentTime={position_ms}, totalTime={duration_ms}"
+            ));
+        }
+
+        if position_ms < 0.0 || duration_ms < 0.0 {
+            return Err(format!(
+                "时间线数据不能为负数: currentTime={position_ms}, totalTime={duration_ms}"
+            ));
+        }
+
+        let position_ms = if duration_ms > 0.0 {
+            position_ms.min(duration_ms)
+        } else {
+            position_ms
+        };
+
+        Ok(Self {
+            position_ms,
+            duration_ms,
+        })
+    }
+
+    pub const fn position_ms(&self) -> f64 {
+        self.position_ms
+    }
+
+    /// 还不知道时长时为 0
+    pub const fn duration_ms(&self) -> f64 {
+        self.duration_ms
+    }
+}
+
+impl TryFrom<TimelinePayload> for Timeline {
+    type Error = String;
+
+    fn try_from(payload: TimelinePayload) -> Result<Self, Self::Error> {
+        Self::new(payload.current_time, payload.total_time)
+    }
+}
+
+impl From<Timeline> for TimelinePayload {
+    fn from(timeline: Timeline) -> Self {
+        Self {
+            current_time: timeline.position_ms,
+            total_time: timeline.duration_ms,
+        }
+    }
+}
+
+/// 前端最近一次报告的播放进度，播放时按经过的时间推算当前位置
+#[derive(Debug)]
+pub struct TimelineCache {
+    position_ms: f64,
+    duration_ms: f64,
+    /// 没有收到过时间线时为 `None`
+    updated_at: Option<Instant>,
+    playing: bool,
+    /// 截至 `updated_at` 实际播放的时长 (毫秒)，不包括暂停、往回跳转和往前跳过的部分
+    played_ms: f64,
+}
+
+static CACHE: Mutex<TimelineCache> = Mutex::new(TimelineCache::new());
+
+pub fn update(timeline: Timeline) {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.update(timeline.position_ms, timeline.duration_ms);
+    }
+}
+
+pub fn set_status(status: PlaybackStatus) {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.set_status(status);
+    }
+}
+
+pub fn reset() {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.reset();
+    }
+}
+
+pub fn seek_relative(delta_ms: f64) -> Option<f64> {
+    CACHE.lock().ok()?.seek_relative(delta_ms)
+}
+
+/// 当前歌曲实际播放的时长 (毫秒)，单曲循环时每一遍重新计算
+pub fn played_ms() -> f64 {
+    CACHE.lock().map_or(0.0, |cache| cache.played_ms())
+}
+
+impl TimelineCache {
+    const fn new() -> Self {
+        Self {
+            position_ms: 0.0,
+            duration_ms: 0.0,
+            updated_at: None,
+            playing: false,
+            played_ms: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, position_ms: f64, duration_ms: f64) {
+        self.update_at(position_ms, duration_ms, Instant::now());
+    }
+
+    /// 与 [`Self::update`] 相同，但使用给定的时刻，测试中用来模拟时间的流逝
+    fn update_at(&mut self, position_ms: f64, duration_ms: f64, now: Instant) {
+        if self.updated_at.is_some()
+            && (position_ms - self.position_ms_at(now)).abs() > SEEK_THRESHOLD_MS
+        {
+            clock::reanchor("跳转进度");
+        }
+        self.account(position_ms, now);
+        self.position_ms = position_ms;
+        self.duration_ms = duration_ms;
+        self.updated_at = Some(now);
+    }
+
+    pub fn set_status(&mut self, status: PlaybackStatus) {
+        self.set_status_at(status, Instant::now());
+    }
+
+    fn set_status_at(&mut self, status: PlaybackStatus, now: Instant) {
+        // 先按旧状态结算已经播放的部分
+        let position_ms = self.position_ms_at(now);
+        self.played_ms += position_ms - self.position_ms;
+        self.position_ms = position_ms;
+        if self.updated_at.is_some() {
+            self.updated_at = Some(now);
+        }
+        self.playing = status == PlaybackStatus::Playing;
+    }
+
+    /// 切歌后旧的进度不再有意义
+    pub const fn reset(&mut self) {
+        *self = Self {
+            playing: self.playing,
+            ..Self::new()
+        };
+    }
+
+    pub fn position_ms(&self) -> f64 {
+        self.position_ms_at(Instant::now())
+    }
+
+    fn position_ms_at(&self, now: Instant) -> f64 {
+        let Some(updated_at) = self.updated_at else {
+            return 0.0;
+        };
+        if !self.playing {
+            return self.position_ms;
+        }
+
+        let position =
+            self.position_ms + now.saturating_duration_since(updated_at).as_millis() as f64;
+        if self.duration_ms > 0.0 {
+            position.min(self.duration_ms)
+        } else {
+            position
+        }
+    }
+
+    /// 根据新报告的进度结算上次更新以来实际播放的时长
+    fn account(&mut self, position_ms: f64, now: Instant) {
+        let Some(updated_at) = self.updated_at else {
+            return;
+        };
+
+        let advanced = position_ms - self.position_ms;
+        if advanced >= 0.0 {
+            // 往前跳转时只计入实际经过的时间，暂停时进度不会前进
+            if self.playing {
+                let elapsed = now.saturating_duration_since(updated_at).as_millis() as f64;
+                self.played_ms += advanced.min(elapsed + SEEK_THRESHOLD_MS);
+            }
+        } else if self.duration_ms > 0.0
+            && self.position_ms >= self.duration_ms - LOOP_TOLERANCE_MS
+            && position_ms <= LOOP_TOLERANCE_MS
+        {
+            // 单曲循环回到开头，新的一遍从头计算
+            self.played_ms = position_ms;
+        }
+    }
+
+    pub fn played_ms(&self) -> f64 {
+        self.played_ms_at(Instant::now())
+    }
+
+    fn played_ms_at(&self, now: Instant) -> f64 {
+        self.played_ms + (self.position_ms_at(now) - self.position_ms).max(0.0)
+    }
+
+    /// 在当前位置的基础上跳转并返回目标位置，还不知道歌曲时长时返回 `None`
+    pub fn seek_relative(&mut self, delta_ms: f64) -> Option<f64> {
+        if self.updated_at.is_none() || self.duration_ms <= 0.0 || !delta_ms.is_finite() {
+            return None;
+        }
+
+        let target = (self.position_ms() + delta_ms).clamp(0.0, self.duration_ms);
+        self.update(target, self.duration_ms);
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+// 这里比较的都是原样保存或截断得到的值，不存在舍入误差
+#[allow(clippy::float_cmp)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const DURATION_MS: f64 = 200_000.0;
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    /// 从 `start` 开始在 0 处播放一首 200 秒的歌曲
+    fn playing_cache(start: Instant) -> TimelineCache {
+        let mut cache = TimelineCache::new();
+        cache.set_status_at(PlaybackStatus::Playing, start);
+        cache.update_at(0.0, DURATION_MS, start);
+        cache
+    }
+
+    #[test]
+    fn accepts_valid_timeline() {
+        let timeline = Timeline::new(1_000.0, 200_000.0).unwrap();
+        assert_eq!(timeline.position_ms(), 1_000.0);
+        assert_eq!(timeline.duration_ms(), 200_000.0);
+    }
+
+    #[test]
+    fn rejects_non_finite_values() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(Timeline::new(value, 200_000.0).is_err(), "position {value}");
+            assert!(Timeline::new(1_000.0, value).is_err(), "duration {value}");
+        }
+    }
+
+    #[test]
+    fn rejects_negative_values() {
+        assert!(Timeline::new(-1.0, 200_000.0).is_err());
+        assert!(Timeline::new(1_000.0, -1.0).is_err());
+        assert!(Timeline::new(-0.5, -0.5).is_err());
+    }
+
+    #[test]
+    fn clamps_position_to_duration() {
+        let timeline = Timeline::new(200_500.0, 200_000.0).unwrap();
+        assert_eq!(timeline.position_ms(), 200_000.0);
+    }
+
+    #[test]
+    fn keeps_position_when_duration_is_unknown() {
+        let timeline = Timeline::new(5_000.0, 0.0).unwrap();
+        assert_eq!(timeline.position_ms(), 5_000.0);
+        assert_eq!(timeline.duration_ms(), 0.0);
+    }
+
+    #[test]
+    fn payload_deserialization_validates() {
+        let valid: Timeline =
+            serde_json::from_str(r#"{"currentTime":1000,"totalTime":2000}"#).unwrap();
+        assert_eq!(valid, Timeline::new(1_000.0, 2_000.0).unwrap());
+
+        assert!(
+            serde_json::from_str::<Timeline>(r#"{"currentTime":-1,"totalTime":2000}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn counts_continuous_playback() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.update_at(10_000.0, DURATION_MS, at(start, 10_000));
+        assert_eq!(cache.played_ms_at(at(start, 10_000)), 10_000.0);
+        // 两次报告之间按经过的时间推算
+        assert_eq!(cache.played_ms_at(at(start, 12_000)), 12_000.0);
+    }
+
+    #[test]
+    fn excludes_paused_time() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.set_status_at(PlaybackStatus::Paused, at(start, 10_000));
+        assert_eq!(cache.played_ms_at(at(start, 40_000)), 10_000.0);
+
+        cache.set_status_at(PlaybackStatus::Playing, at(start, 40_000));
+        cache.update_at(15_000.0, DURATION_MS, at(start, 45_000));
+        assert_eq!(cache.played_ms_at(at(start, 45_000)), 15_000.0);
+    }
+
+    #[test]
+    fn seek_back_keeps_played_time() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.update_at(60_000.0, DURATION_MS, at(start, 60_000));
+        cache.update_at(20_000.0, DURATION_MS, at(start, 60_000));
+        assert_eq!(cache.played_ms_at(at(start, 60_000)), 60_000.0);
+
+        // 重新播放跳回的部分也是实际播放的时间
+        cache.update_at(30_000.0, DURATION_MS, at(start, 70_000));
+        assert_eq!(cache.played_ms_at(at(start, 70_000)), 70_000.0);
+    }
+
+    #[test]
+    fn seek_forward_counts_only_elapsed_time() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.update_at(10_000.0, DURATION_MS, at(start, 10_000));
+        cache.update_at(100_000.0, DURATION_MS, at(start, 11_000));
+        assert_eq!(
+            cache.played_ms_at(at(start, 11_000)),
+            11_000.0 + SEEK_THRESHOLD_MS
+        );
+    }
+
+    #[test]
+    fn repeat_one_restarts_count() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.update_at(198_000.0, DURATION_MS, at(start, 198_000));
+        cache.update_at(500.0, DURATION_MS, at(start, 200_500));
+        assert_eq!(cache.played_ms_at(at(start, 200_500)), 500.0);
+
+        cache.update_at(10_500.0, DURATION_MS, at(start, 210_500));
+        assert_eq!(cache.played_ms_at(at(start, 210_500)), 10_500.0);
+    }
+
+    #[test]
+    fn crossfade_ignores_late_updates_from_previous_track() {
+        let start = Instant::now();
+        let mut cache = playing_cache(start);
+        cache.update_at(195_000.0, DURATION_MS, at(start, 195_000));
+
+        // 淡入淡出时新歌的元数据先到，旧歌的进度稍后才到
+        cache.reset();
+        cache.update_at(196_000.0, DURATION_MS, at(start, 195_500));
+        cache.update_at(1_000.0, 180_000.0, at(start, 196_000));
+        cache.update_at(11_000.0, 180_000.0, at(start, 206_000));
+        assert_eq!(cache.played_ms_at(at(start, 206_000)), 10_000.0);
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{
+    Context,
+    Result,
+    ensure,
+};
+use serde::Deserialize;
+use sha2::{
+    Digest,
+    Sha256,
+};
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+use ureq::{
+    Agent,
+    tls::{
+        TlsConfig,
+        TlsProvider,
+    },
+};
+
+use crate::{
+    config,
+    platform,
+    smtc_core,
+    tasks,
+};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/apoint123/inflink-rs/releases/latest";
+const PLUGIN_ASSET_NAME: &str = "InfLink-rs.plugin";
+const USER_AGENT: &str = concat!("InfLink-rs/", env!("CARGO_PKG_VERSION"));
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// 数据目录下保存已下载更新的子目录
+pub const STAGING_DIR_NAME: &str = "update";
+const RUNTIME_DIR_NAME: &str = "plugins_runtime";
+const PLUGINS_DIR_NAME: &str = "plugins";
+
+/// 启动后等待一段时间再检查更新，避免和网易云的启动过程抢占网络
+const CHECK_DELAY: Duration = Duration::from_secs(30);
+const MAX_PLUGIN_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+    /// GitHub 计算的文件摘要，例如 `sha256:...`
+    digest: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct StagedUpdate {
+    version: String,
+    path: PathBuf,
+}
+
+/// 已下载并校验通过、等待应用的更新
+static STAGED_UPDATE: Mutex<Option<StagedUpdate>> = Mutex::new(None);
+
+/// 启动一次延迟的更新检查，插件暂停后恢复时会再次调用，已经下载了更新时不再检查
+pub fn init() {
+    if !config::get().updater.enabled || staged_path().is_some() {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("updater-thread", |token| {
+        if !token.sleep(CHECK_DELAY) {
+            return;
+        }
+        if let Err(e) = check_for_update() {
+            warn!("检查插件更新失败: {e:?}");
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动更新检查线程: {e}");
+    }
+}
+
+fn check_for_update() -> Result<()> {
+    let agent: Agent = Agent::config_builder()
+        .tls_config(
+            TlsConfig::builder()
+                .provider(TlsProvider::NativeTls)
+                .build(),
+        )
+        .timeout_global(Some(Duration::from_mins(2)))
+        .build()
+        .into();
+
+    let release: Release = agent
+        .get(RELEASES_URL)
+        .header("User-Agent", USER_AGENT)
+        .header("Accept", "application/vnd.github+json")
+        .call()?
+        .body_mut()
+        .read_json()
+        .context("解析 Release 信息失败")?;
+
+    let latest_version = release.tag_name.trim_start_matches('v');
+    if !is_newer_version(latest_version, CURRENT_VERSION) {
+        debug!(latest_version, "插件已是最新版本");
+        return Ok(());
+    }
+
+    info!("发现新版本 {latest_version}，正在下载");
+
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == PLUGIN_ASSET_NAME)
+        .context("Release 中没有插件文件")?;
+    // 摘要和下载地址来自同一个 API 响应，只能发现下载不完整或被损坏，
+    // 不能证明文件由作者发布，见 `apply_update`
+    let expected_digest = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .context("Release 中缺少插件文件的 SHA-256 摘要")?;
+
+    let bytes = agent
+        .get(&asset.browser_download_url)
+        .header("User-Agent", USER_AGENT)
+        .call()?
+        .body_mut()
+        .with_config()
+        .limit(MAX_PLUGIN_SIZE)
+        .read_to_vec()
+        .context("下载插件文件失败")?;
+
+    let actual_digest = Sha256::digest(&bytes)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    ensure!(
+        actual_digest.eq_ignore_ascii_case(expected_digest),
+        "插件文件校验失败: 期望 {expected_digest}，实际 {actual_digest}"
+    );
+
+    let staging_dir = config::data_dir()
+        .context("无法获取数据目录")?
+        .join(STAGING_DIR_NAME);
+    fs::create_dir_all(&staging_dir).context("创建更新目录失败")?;
+
+    let path = staging_dir.join(format!("InfLink-rs-{latest_version}.plugin"));
+    fs::write(&path, &bytes).context("保存插件文件失败")?;
+
+    info!(path = %path.display(), "新版本 {latest_version} 已下载并校验通过");
+
+    if let Ok(mut staged) = STAGED_UPDATE.lock() {
+        *staged = Some(StagedUpdate {
+            version: latest_version.to_string(),
+            path,
+        });
+    }
+
+    smtc_core::notify_update_ready(latest_version.to_string());
+    Ok(())
+}
+
+/// 本次运行中下载的、尚未被替换的更新文件
+pub fn staged_path() -> Option<PathBuf> {
+    STAGED_UPDATE
+        .lock()
+        .ok()?
+        .as_ref()
+        .map(|staged| staged.path.clone())
+}
+
+/// 按数字逐段比较版本号，忽略预发布后缀
+fn is_newer_version(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        version
+            .split('-')
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+
+    parse(candidate) > parse(current)
+}
+
+/// 用已下载的更新替换安装的插件文件
+///
+/// `BetterNCM` 只在启动时加载插件，所以替换后的版本会在下次启动网易云时生效。
+/// 要替换的文件由后端根据插件所在的目录确定，不接受前端传入的路径
+///
+/// 下载的文件只校验了 SHA-256 摘要，没有验证签名。Release 附带的构建来源证明
+/// (artifact attestation) 需要用 `gh attestation verify` 手动验证
+pub fn apply_update() -> Result<(), String> {
+    let staged = STAGED_UPDATE
+        .lock()
+        .map_err(|e| format!("更新状态锁中毒: {e}"))?
+        .clone()
+        .ok_or_else(|| "没有已下载的更新".to_string())?;
+    let target_path = installed_plugin_path()?;
+
+    fs::copy(&staged.path, &target_path).map_err(|e| format!("替换插件文件失败: {e}"))?;
+
+    info!(
+        target = %target_path.display(),
+        "已应用新版本 {}，将在下次启动时生效",
+        staged.version
+    );
+    Ok(())
+}
+
+/// 根据插件 DLL 的位置找到安装的插件文件
+///
+/// `BetterNCM` 把 `plugins` 目录中的插件文件解压到同一级的 `plugins_runtime` 目录中运行
+fn installed_plugin_path() -> Result<PathBuf, String> {
+    let plugin_dir = platform::plugin_dir().ok_or("无法获取插件所在的目录")?;
+    let betterncm_dir = plugin_dir
+        .ancestors()
+        .find(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.eq_ignore_ascii_case(RUNTIME_DIR_NAME))
+        })
+        .and_then(Path::parent)
+        .ok_or_else(|| {
+            format!(
+                "插件不在 {RUNTIME_DIR_NAME} 目录中: {}",
+                plugin_dir.display()
+            )
+        })?;
+
+    let path = betterncm_dir.join(PLUGINS_DIR_NAME).join(PLUGIN_ASSET_NAME);
+    if !path.is_file() {
+        return Err(format!("没有找到安装的插件文件: {}", path.display()));
+    }
+    Ok(path)
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{
+    debug,
+    warn,
+};
+use windows::Win32::{
+    Foundation::{
+        CloseHandle,
+        FILETIME,
+        HANDLE,
+        HLOCAL,
+        LocalFree,
+    },
+    System::{
+        Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot,
+            TH32CS_SNAPTHREAD,
+            THREADENTRY32,
+            Thread32First,
+            Thread32Next,
+        },
+        Threading::{
+            GetCurrentProcessId,
+            GetThreadDescription,
+            GetThreadTimes,
+            OpenThread,
+            THREAD_QUERY_LIMITED_INFORMATION,
+        },
+    },
+};
+
+use crate::{
+    config,
+    smtc_core::{
+        self,
+        SmtcEvent,
+    },
+    tasks,
+};
+
+/// 插件自己创建的线程，网易云的线程不计入统计
+const PLUGIN_THREAD_NAMES: &[&str] = &[
+    "audio-session-thread",
+    "command-worker-thread",
+    "config-watch-thread",
+    "context-watch-thread",
+    "cover-palette-thread",
+    "cover-prefetch-thread",
+    "discord-asset-thread",
+    "discord-rpc-thread",
+    "dispatcher-thread",
+    "enrichment-thread",
+    "gsmtc-monitor-thread",
+    "http-server-thread",
+    "http-sse-thread",
+    "instance-arbiter-thread",
+    "listening-session-thread",
+    "log-flush-thread",
+    "log-forward-thread",
+    "maintenance-thread",
+    "smtc-gesture-thread",
+    "smtc-seek-debounce-thread",
+    "updater-thread",
+];
+
+/// 单个线程在一个采样周期内的 CPU 占用
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadCpuUsage {
+    pub name: String,
+    /// 占单个逻辑核心的百分比
+    pub percent: f64,
+}
+
+/// 最近一次采样的结果
+static LAST_SAMPLE: Mutex<Vec<ThreadCpuUsage>> = Mutex::new(Vec::new());
+
+pub fn init() {
+    let watchdog_config = config::get().watchdog;
+    if !watchdog_config.enabled {
+        return;
+    }
+
+    let spawn_result = tasks::spawn("cpu-watchdog-thread", move |token| {
+        let interval = Duration::from_secs(watchdog_config.sample_interval_secs.max(1));
+        let mut sampler = CpuSampler::default();
+        let mut over_budget_samples = 0;
+
+        while token.sleep(interval) {
+            let usage = match sampler.sample() {
+                Ok(usage) => usage,
+                Err(e) => {
+                    debug!("采样线程 CPU 占用失败: {e:?}");
+                    continue;
+                }
+            };
+            let total: f64 = usage.iter().map(|thread| thread.percent).sum();
+
+            if total > watchdog_config.cpu_threshold_percent {
+                over_budget_samples += 1;
+                // 每次持续超标只提醒一次
+                if over_budget_samples == watchdog_config.sustained_samples.max(1) {
+                    report_high_usage(total, &usage);
+                }
+            } else {
+                over_budget_samples = 0;
+            }
+
+            if let Ok(mut last) = LAST_SAMPLE.lock() {
+                *last = usage;
+            }
+        }
+    });
+
+    if let Err(e) = spawn_result {
+        warn!("无法启动 CPU 监视线程: {e}");
+    }
+}
+
+/// 最近一次采样中插件各线程的 CPU 占用，未开启监视时为空
+pub fn last_sample() -> Vec<ThreadCpuUsage> {
+    LAST_SAMPLE
+        .lock()
+        .map(|last| last.clone())
+        .unwrap_or_default()
+}
+
+fn report_high_usage(total: f64, usage: &[ThreadCpuUsage]) {
+    let busiest = usage
+        .iter()
+        .max_by(|a, b| a.percent.total_cmp(&b.percent))
+        .map(|thread| thread.name.clone())
+        .unwrap_or_default();
+
+    warn!(
+        total_percent = format!("{total:.1}"),
+        busiest_thread = busiest,
+        threads = ?usage,
+        "插件线程的 CPU 占用持续偏高"
+    );
+    smtc_core::dispatch_event(&SmtcEvent::HighCpuUsage {
+        percent: total,
+        busiest_thread: busiest,
+    });
+}
+
+#[derive(Default)]
+struct CpuSampler {
+    /// 线程 ID -> 上次采样时的累计 CPU 时间 (100 纳秒为单位)
+    previous: HashMap<u32, u64>,
+    previous_at: Option<Instant>,
+}
+
+impl CpuSampler {
+    fn sample(&mut self) -> Result<Vec<ThreadCpuUsage>> {
+        let now = Instant::now();
+        let elapsed = self.previous_at.map(|at| now.duration_since(at));
+        self.previous_at = Some(now);
+
+        let mut current = HashMap::new();
+        let mut usage = Vec::new();
+
+        for (thread_id, name, cpu_time) in plugin_thread_times()? {
+            current.insert(thread_id, cpu_time);
+
+            if let Some(elapsed) = elapsed
+                && let Some(&previous) = self.previous.get(&thread_id)
+            {
+                let busy_secs = cpu_time.saturating_sub(previous) as f64 / 10_000_000.0;
+                usage.push(ThreadCpuUsage {
+                    name,
+                    percent: busy_secs / elapsed.as_secs_f64() * 100.0,
+                });
+            }
+        }
+
+        self.previous = current;
+        Ok(usage)
+    }
+}
+
+/// 枚举当前进程中属于插件的线程，返回线程 ID、名称和累计 CPU 时间
+fn plugin_thread_times() -> Result<Vec<(u32, String, u64)>> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0)? };
+    let process_id = unsafe { GetCurrentProcessId() };
+
+    let mut entry = THREADENTRY32 {
+        dwSize: size_of::<THREADENTRY32>() as u32,
+        ..Default::default()
+    };
+    let mut threads = Vec::new();
+    let mut next = unsafe { Thread32First(snapshot, &raw mut entry) };
+
+    while next.is_ok() {
+        if entry.th32OwnerProcessID == process_id
+            && let Ok(handle) =
+                unsafe { OpenThread(THREAD_QUERY_LIMITED_INFORMATION, false, entry.th32ThreadID) }
+        {
+            if let Some(name) = thread_description(handle)
+                && PLUGIN_THREAD_NAMES.contains(&name.as_str())
+                && let Some(cpu_time) = thread_cpu_time(handle)
+            {
+                threads.push((entry.th32ThreadID, name, cpu_time));
+            }
+            let _ = unsafe { CloseHandle(handle) };
+        }
+        next = unsafe { Thread32Next(snapshot, &raw mut entry) };
+    }
+
+    let _ = unsafe { CloseHandle(snapshot) };
+    Ok(threads)
+}
+
+/// `std::thread::Builder::name` 在 Windows 上会设置线程描述
+fn thread_description(handle: HANDLE) -> Option<String> {
+    let description = unsafe { GetThreadDescription(handle) }.ok()?;
+    let name = unsafe { description.to_string() }.ok();
+    unsafe { LocalFree(Some(HLOCAL(description.0.cast()))) };
+    name
+}
+
+fn thread_cpu_time(handle: HANDLE) -> Option<u64> {
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    unsafe {
+        GetThreadTimes(
+            handle,
+            &raw mut creation,
+            &raw mut exit,
+            &raw mut kernel,
+            &raw mut user,
+        )
+    }
+    .ok()?;
+
+    let to_u64 =
+        |time: FILETIME| u64::from(time.dwHighDateTime) << 32 | u64::from(time.dwLowDateTime);
+    Some(to_u64(kernel) + to_u64(user))
+}
@@ -0,0 +1,83 @@
+use std::{
+    sync::mpsc,
+    time::Duration,
+};
+
+use windows::{
+    Win32::Foundation::ERROR_TIMEOUT,
+    core::{
+        Error,
+        HRESULT,
+        Result,
+        RuntimeType,
+    },
+};
+use windows_future::{
+    IAsyncAction,
+    IAsyncOperation,
+    IAsyncOperationWithProgress,
+};
+
+/// 本地的 `WinRT` 操作 (写入内存流、解码图片等) 的超时时间
+pub const LOCAL_TIMEOUT: Duration = Duration::from_secs(5);
+/// 需要访问网络的 `WinRT` 操作 (下载封面等) 的超时时间
+pub const NETWORK_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// 带超时地等待 `WinRT` 异步操作
+///
+/// 系统媒体服务卡住时 `join()` 会一直阻塞，超时后取消操作并返回 `ERROR_TIMEOUT`，
+/// 让 Dispatcher 和渲染线程可以继续处理后续命令
+pub trait JoinTimeout {
+    type Output;
+
+    fn join_timeout(&self, timeout: Duration) -> Result<Self::Output>;
+}
+
+impl<T: RuntimeType + 'static> JoinTimeout for IAsyncOperation<T> {
+    type Output = T;
+
+    fn join_timeout(&self, timeout: Duration) -> Result<T> {
+        wait(timeout, |done| self.when(|_| done()), || self.Cancel())?;
+        self.GetResults()
+    }
+}
+
+impl<T: RuntimeType + 'static, P: RuntimeType + 'static> JoinTimeout
+    for IAsyncOperationWithProgress<T, P>
+{
+    type Output = T;
+
+    fn join_timeout(&self, timeout: Duration) -> Result<T> {
+        wait(timeout, |done| self.when(|_| done()), || self.Cancel())?;
+        self.GetResults()
+    }
+}
+
+impl JoinTimeout for IAsyncAction {
+    type Output = ();
+
+    fn join_timeout(&self, timeout: Duration) -> Result<()> {
+        wait(timeout, |done| self.when(|_| done()), || self.Cancel())?;
+        self.GetResults()
+    }
+}
+
+/// 结果只在调用线程上读取，有些结果类型 (例如流接口) 不能跨线程传递
+fn wait(
+    timeout: Duration,
+    when: impl FnOnce(Box<dyn FnOnce() + Send>) -> Result<()>,
+    cancel: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::sync_channel(1);
+    when(Box::new(move || {
+        let _ = tx.send(());
+    }))?;
+
+    rx.recv_timeout(timeout).map_err(|_| {
+        let _ = cancel();
+        Error::new(
+            HRESULT::from_win32(ERROR_TIMEOUT.0),
+            format!("WinRT 操作在 {timeout:?} 内没有完成"),
+        )
+    })
+}
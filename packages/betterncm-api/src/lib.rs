@@ -0,0 +1,208 @@
+use std::{
+    ffi::{
+        CStr,
+        c_char,
+        c_int,
+        c_void,
+    },
+    ptr::{
+        self,
+        NonNull,
+    },
+};
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NCMProcessType {
+    Undetected = 0x0,
+    Main = 0x0001,
+    Renderer = 0x10,
+    GpuProcess = 0x100,
+    Utility = 0x1000,
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeAPIType {
+    Int,
+    Boolean,
+    Double,
+    String,
+    V8Value,
+}
+
+pub type NativeFunction = unsafe extern "C" fn(args: *mut *mut c_void) -> *mut c_char;
+pub type AddNativeApiFn = extern "C" fn(
+    args: *const NativeAPIType,
+    args_num: c_int,
+    identifier: *const c_char,
+    function: NativeFunction,
+) -> c_int;
+
+/// `BetterNCMPluginMain` 收到的插件接口
+#[repr(C)]
+pub struct PluginAPI {
+    pub add_native_api: AddNativeApiFn,
+    pub betterncm_version: *const c_char,
+    pub process_type: NCMProcessType,
+    pub ncm_version: *const [u16; 3],
+}
+
+impl PluginAPI {
+    /// 注册一个原生 API，之后前端可以通过 `betterncm_native.native_plugin.call` 调用
+    pub fn register(&self, api: &NativeApi) {
+        // 没有参数时传入空指针
+        let args = if api.args.is_empty() {
+            ptr::null()
+        } else {
+            api.args.as_ptr()
+        };
+        (self.add_native_api)(
+            args,
+            api.args.len() as c_int,
+            api.identifier.as_ptr(),
+            api.function,
+        );
+    }
+}
+
+/// 一个待注册的原生 API，通常由 [`native_apis!`] 生成
+#[derive(Debug, Clone, Copy)]
+pub struct NativeApi {
+    /// 前端调用时使用的名称，例如 `inflink.dispatch`
+    pub identifier: &'static CStr,
+    pub args: &'static [NativeAPIType],
+    pub function: NativeFunction,
+}
+
+/// 可以作为原生 API 参数的类型
+pub trait NativeArg: Sized {
+    const TYPE: NativeAPIType;
+
+    /// 前端传入的参数无效 (例如空指针) 时返回 `None`
+    ///
+    /// # Safety
+    ///
+    /// `arg` 必须是 `BetterNCM` 按 [`Self::TYPE`] 传入的参数
+    unsafe fn from_raw(arg: *mut c_void) -> Option<Self>;
+}
+
+impl NativeArg for String {
+    const TYPE: NativeAPIType = NativeAPIType::String;
+
+    unsafe fn from_raw(arg: *mut c_void) -> Option<Self> {
+        if arg.is_null() {
+            return None;
+        }
+        let s = unsafe { CStr::from_ptr(arg.cast::<c_char>()) };
+        Some(s.to_string_lossy().into_owned())
+    }
+}
+
+/// 前端传入的 V8 对象，这个 crate 不依赖 CEF，所以只保存指针
+#[derive(Debug, Clone, Copy)]
+pub struct RawV8Value(NonNull<c_void>);
+
+impl RawV8Value {
+    #[must_use]
+    pub const fn as_ptr(self) -> *mut c_void {
+        self.0.as_ptr()
+    }
+}
+
+impl NativeArg for RawV8Value {
+    const TYPE: NativeAPIType = NativeAPIType::V8Value;
+
+    unsafe fn from_raw(arg: *mut c_void) -> Option<Self> {
+        NonNull::new(arg).map(Self)
+    }
+}
+
+/// 读取参数数组中的第 `index` 个参数，数组为空指针时返回空指针
+///
+/// 由 [`native_apis!`] 生成的代码调用
+#[doc(hidden)]
+pub unsafe fn arg_at(args: *mut *mut c_void, index: usize) -> *mut c_void {
+    if args.is_null() {
+        return ptr::null_mut();
+    }
+    unsafe { *args.add(index) }
+}
+
+/// 在编译期把 `"prefix"` 和函数名拼接成以 0 结尾的 API 名称
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __identifier {
+    ($prefix:literal, $name:ident) => {
+        const {
+            match ::core::ffi::CStr::from_bytes_with_nul(
+                concat!($prefix, ".", stringify!($name), "\0").as_bytes(),
+            ) {
+                Ok(identifier) => identifier,
+                Err(_) => panic!("原生 API 的名称中不能包含 NUL"),
+            }
+        }
+    };
+}
+
+/// 声明要注册的原生 API，生成 `[NativeApi; N]`
+///
+/// 每一项的格式为 `名称 => 处理函数(参数类型, ...)`，处理函数是普通的 Rust 函数，
+/// 参数类型实现 [`NativeArg`]，返回值直接交给 `BetterNCM`。生成的 `extern "C"` 函数会
+/// 按声明的类型读取参数，所以参数的数量和类型与处理函数不一致时无法通过编译。
+/// 有参数无效时不会调用处理函数，而是调用 `on_invalid_arg(名称, 参数序号)` 并返回空指针
+///
+/// ```ignore
+/// let apis = betterncm_api::native_apis! {
+///     prefix: "inflink",
+///     on_invalid_arg: invalid_arg,
+///     apis: [
+///         initialize => initialize(),
+///         dispatch => dispatch(String),
+///     ],
+/// };
+/// ```
+#[macro_export]
+macro_rules! native_apis {
+    (
+        prefix: $prefix:literal,
+        on_invalid_arg: $on_invalid_arg:path,
+        apis: [
+            $(
+                $(#[$meta:meta])*
+                $name:ident => $handler:ident ( $($arg:ty),* $(,)? )
+            ),* $(,)?
+        ] $(,)?
+    ) => {
+        [
+            $(
+                $(#[$meta])*
+                {
+                    #[allow(unused_variables, unused_mut, unused_assignments)]
+                    unsafe extern "C" fn function(
+                        args: *mut *mut ::core::ffi::c_void,
+                    ) -> *mut ::core::ffi::c_char {
+                        let mut index = 0usize;
+                        $handler($({
+                            let arg = unsafe {
+                                <$arg as $crate::NativeArg>::from_raw($crate::arg_at(args, index))
+                            };
+                            let Some(arg) = arg else {
+                                $on_invalid_arg(concat!($prefix, ".", stringify!($name)), index);
+                                return ::core::ptr::null_mut();
+                            };
+                            index += 1;
+                            arg
+                        }),*)
+                    }
+
+                    $crate::NativeApi {
+                        identifier: $crate::__identifier!($prefix, $name),
+                        args: &[$(<$arg as $crate::NativeArg>::TYPE),*],
+                        function,
+                    }
+                }
+            ),*
+        ]
+    };
+}
@@ -10,7 +10,10 @@ pub use error::{
     CefError,
     CefResult,
 };
-pub use task::renderer_post_task_in_v8_ctx;
+pub use task::{
+    renderer_post_task,
+    renderer_post_task_in_v8_ctx,
+};
 pub use v8::{
     CefV8Context,
     CefV8Value,
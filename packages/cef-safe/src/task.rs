@@ -4,7 +4,10 @@ use std::{
         AssertUnwindSafe,
         catch_unwind,
     },
-    ptr::NonNull,
+    ptr::{
+        self,
+        NonNull,
+    },
     sync::atomic::{
         AtomicUsize,
         Ordering,
@@ -31,8 +34,8 @@ struct RustClosureTask {
     cef_task: _cef_task_t,
     /// 需要在 CEF 线程上执行的闭包
     closure: Option<Box<dyn FnOnce() + Send + 'static>>,
-    /// 任务执行时需要进入的 V8 上下文
-    v8_context: CefV8Context,
+    /// 任务执行时需要进入的 V8 上下文，为 `None` 时不进入任何上下文
+    v8_context: Option<CefV8Context>,
     /// 手动实现的原子引用计数
     ref_count: AtomicUsize,
 }
@@ -42,16 +45,21 @@ mod internal_logic {
         _cef_base_ref_counted_t,
         _cef_task_t,
         AssertUnwindSafe,
+        CefV8Context,
         NonNull,
         Ordering,
         RustClosureTask,
         catch_unwind,
+        ptr,
     };
 
     pub(super) unsafe fn execute_rust_closure(task: *mut _cef_task_t) {
         let rust_task = unsafe { &mut *task.cast::<RustClosureTask>() };
 
-        let v8_context_ptr = rust_task.v8_context.as_raw();
+        let v8_context_ptr = rust_task
+            .v8_context
+            .as_ref()
+            .map_or(ptr::null_mut(), CefV8Context::as_raw);
         let entered_context = unsafe {
             NonNull::new(v8_context_ptr)
                 .and_then(|ctx_ptr| (*ctx_ptr.as_ptr()).enter)
@@ -190,6 +198,28 @@ pub fn renderer_post_task_in_v8_ctx<F>(v8_context: CefV8Context, f: F) -> CefRes
 where
     F: FnOnce() + Send + 'static,
 {
+    post_renderer_task(Some(v8_context), Box::new(f))
+}
+
+/// 将一个 Rust 闭包提交到 CEF 的渲染线程执行，不进入任何 V8 上下文
+///
+/// 适用于检查 V8 上下文是否仍然有效等不需要执行 JS 的操作，
+/// 因为对已经被销毁的上下文调用 `enter` 是没有意义的
+///
+/// # Returns
+/// - `Ok(())`: 任务成功提交到 CEF 的任务队列
+/// - `Err(CefError::TaskPostFailed)`: 无法获取任务运行器或提交任务失败
+pub fn renderer_post_task<F>(f: F) -> CefResult<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    post_renderer_task(None, Box::new(f))
+}
+
+fn post_renderer_task(
+    v8_context: Option<CefV8Context>,
+    closure: Box<dyn FnOnce() + Send + 'static>,
+) -> CefResult<()> {
     unsafe {
         let task_runner_ptr = cef_sys::cef_task_runner_get_for_thread(cef_thread_id_t_TID_RENDERER);
         if task_runner_ptr.is_null() {
@@ -207,7 +237,7 @@ where
                 },
                 execute: Some(execute_rust_closure),
             },
-            closure: Some(Box::new(f)),
+            closure: Some(closure),
             v8_context,
             ref_count: AtomicUsize::new(1),
         });
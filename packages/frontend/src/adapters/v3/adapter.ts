@@ -480,6 +480,12 @@ export class V3NcmAdapter extends BaseNcmAdapter {
 			duration = playingInfo.curTrack.duration;
 		}
 
+		const albumId = Number(playingInfo.curTrack?.album?.id) || undefined;
+		const artistIds =
+			playingInfo.resourceArtists
+				?.map((v) => Number(v.id))
+				.filter((id) => id > 0) ?? [];
+
 		return {
 			songName: playingInfo.resourceName || "未知歌名",
 			authorName:
@@ -489,6 +495,10 @@ export class V3NcmAdapter extends BaseNcmAdapter {
 			cover: coverUrl ? { url: coverUrl } : null,
 			ncmId: currentTrackId,
 			duration: duration > 0 ? duration : undefined,
+			albumId,
+			artistIds,
+			context:
+				playingInfo.playingMode === V3_PLAY_MODES.FM ? "Radio" : "Normal",
 		};
 	}
 
@@ -10,6 +10,7 @@ import {
 	Database,
 	Edit,
 	ExternalLink,
+	FolderOpen,
 	Headset,
 	MonitorPlay,
 	Palette,
@@ -18,6 +19,7 @@ import {
 } from "lucide-react";
 import { useEffect, useState } from "react";
 import type { DiscordDisplayMode } from "@/types/backend";
+import { NativeBackendInstance } from "../services/NativeBackend";
 import {
 	backendLogLevelAtom,
 	discordAppNameModeTypeAtom,
@@ -62,6 +64,7 @@ export function FeatureSettings() {
 	const [frontendLogLevel, setFrontendLogLevel] = useAtom(frontendLogLevelAtom);
 	const [backendLogLevel, setBackendLogLevel] = useAtom(backendLogLevelAtom);
 	const [internalLogging, setInternalLogging] = useAtom(internalLoggingAtom);
+	const [logLocation] = useState(() => NativeBackendInstance.getLogLocation());
 
 	const logLevels: LogLevel[] = ["trace", "debug", "info", "warn", "error"];
 	const logLevelOptions = logLevels.map((level) => ({
@@ -284,6 +287,17 @@ export function FeatureSettings() {
 				}
 			/>
 
+			<SettingItem
+				icon={<FolderOpen size={20} />}
+				title="后端日志文件"
+				description={logLocation?.currentFile ?? logLocation?.logDir}
+				action={
+					<AnimatedLink onClick={() => NativeBackendInstance.openLogFolder()}>
+						打开目录
+					</AnimatedLink>
+				}
+			/>
+
 			{import.meta.env.DEV ? (
 				<SettingItem
 					icon={<Bug size={20} />}
@@ -2,6 +2,9 @@ import type { INcmAdapter } from "../adapters/adapter";
 import type { ControlMessage } from "../types/backend";
 import logger from "../utils/logger";
 
+/** 外部控制器调节音量时每次的步长 */
+const VOLUME_STEP = 0.05;
+
 export function handleAdapterCommand(
 	adapter: INcmAdapter,
 	msg: ControlMessage,
@@ -25,6 +28,13 @@ export function handleAdapterCommand(
 		case "Seek":
 			adapter.seekTo(msg.position_ms);
 			break;
+		case "SeekRelative": {
+			const timeline = adapter.getTimelineInfo();
+			if (!timeline) break;
+			const target = timeline.currentTime + msg.delta_ms;
+			adapter.seekTo(Math.min(timeline.totalTime, Math.max(0, target)));
+			break;
+		}
 		case "ToggleShuffle":
 			adapter.toggleShuffle();
 			break;
@@ -40,6 +50,20 @@ export function handleAdapterCommand(
 		case "ToggleMute":
 			adapter.toggleMute();
 			break;
+		case "TogglePlayPause":
+			if (adapter.getPlaybackStatus() === "Playing") {
+				adapter.pause();
+			} else {
+				adapter.play();
+			}
+			break;
+		case "VolumeUp":
+		case "VolumeDown": {
+			const { volume } = adapter.getVolumeInfo();
+			const delta = msg.type === "VolumeUp" ? VOLUME_STEP : -VOLUME_STEP;
+			adapter.setVolume(Math.min(1, Math.max(0, volume + delta)));
+			break;
+		}
 		default: {
 			const exhaustedCheck: never = msg;
 			logger.warn(`未处理的命令:`, "handleAdapterCommand", exhaustedCheck);
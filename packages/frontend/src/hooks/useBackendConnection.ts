@@ -43,7 +43,7 @@ export function useBackendConnection(adapterState: AdapterState) {
 			if (!hasSentInitialMetadata.current) {
 				hasSentInitialMetadata.current = true;
 				if (configRef.current.smtcEnabled) {
-					nativeBackend.enableSmtcSession();
+					nativeBackend.enableSink("Smtc");
 				}
 			}
 		};
@@ -81,15 +81,15 @@ export function useBackendConnection(adapterState: AdapterState) {
 		const nativeBackend = NativeBackendInstance;
 
 		if (smtcEnabled) {
-			nativeBackend.enableSmtcSession();
+			nativeBackend.enableSink("Smtc");
 		} else {
-			nativeBackend.disableSmtcSession();
+			nativeBackend.disableSink("Smtc");
 		}
 
 		if (discordEnabled) {
-			nativeBackend.enableDiscordRpc();
+			nativeBackend.enableSink("Discord");
 		} else {
-			nativeBackend.disableDiscordRpc();
+			nativeBackend.disableSink("Discord");
 		}
 
 		nativeBackend.updateDiscordConfig({
@@ -1,13 +1,30 @@
 import type { PlaybackStatus, RepeatMode, SongInfo } from "@/types/api";
 import type {
 	AppMessage,
+	BackendSchemas,
 	CommandResult,
 	ControlMessage,
+	ControlsPayload,
+	CoverState,
+	CurrentCover,
+	Diagnostics,
+	DiscordActivityPreview,
 	DiscordConfigPayload,
+	GetCurrentCoverPayload,
+	ListenTogetherRoom,
 	LogEntry,
+	LogLocation,
+	LogRetentionPayload,
 	MetadataCoverPayload,
 	MetadataPayload,
-	SmtcEvent,
+	PresenceOwner,
+	PreviewPresencePayload,
+	RecentLogEntry,
+	SelfTestCheck,
+	SequencedSmtcEvent,
+	SessionSummary,
+	SinkId,
+	SinkState,
 } from "../types/backend";
 import type { LogLevel } from "../utils/logger";
 import logger from "../utils/logger";
@@ -17,11 +34,11 @@ const NATIVE_API_PREFIX = "inflink.";
 interface NativeApiMap {
 	initialize: (args?: []) => void;
 	terminate: (args?: []) => void;
-	registerLogger: (args: [callback: (logJson: string) => void]) => void;
+	registerLogger: (args: [callback: (logsJson: string) => void]) => void;
 	registerEventCallback: (
 		args: [callback: (eventJson: string) => void],
 	) => void;
-	setLogLevel: (args: [level: LogLevel]) => void;
+	setLogLevel: (args: [directives: string]) => void;
 	dispatch: (args: [commandJson: string]) => string;
 }
 
@@ -37,9 +54,34 @@ function isLogLevel(level: string): level is LogLevel {
 	return ALL_LOG_LEVELS.some((l) => l === level);
 }
 
+/** 为一次用户操作生成关联 ID，用于对应前后端的日志 */
+function newRequestId(): string {
+	return crypto.randomUUID().slice(0, 8);
+}
+
+/** 异步命令等待结果的最长时间，超过后视为失败 */
+const ASYNC_COMMAND_TIMEOUT_MS = 15000;
+
 class NativeBackend {
 	private isActive = false;
 	private updateGeneration = 0;
+	private pendingUpdateVersion: string | null = null;
+	/** 最近一次发送给后端的播放模式 */
+	private playMode: { isShuffling: boolean; repeatMode: RepeatMode } | null =
+		null;
+	private coverStatus: { ncmId: number | null; state: CoverState } | null =
+		null;
+	private coverPalette: { ncmId: number | null; colors: string[] } | null =
+		null;
+	private lastSessionSummary: SessionSummary | null = null;
+	private presenceOwner: PresenceOwner | null = null;
+	private backendConfig: Record<string, unknown> | null = null;
+	private sessionState = { smtc: false, discord: false };
+	/** 等待 `CommandCompleted` 事件的异步命令，按关联 ID 索引 */
+	private pendingCommands = new Map<
+		string,
+		(result: CommandResult | null) => void
+	>();
 
 	private call<K extends keyof NativeApiMap>(
 		func: K,
@@ -52,27 +94,33 @@ class NativeBackend {
 		);
 	}
 
-	private dispatch<T extends keyof AppMessage>(
+	/**
+	 * @param requestId 关联 ID，后端处理该命令时的日志、返回结果和事件都会带上它
+	 */
+	private dispatch<T extends keyof AppMessage, R = unknown>(
 		type: T,
 		payload: AppMessage[T],
-	) {
-		const command = JSON.stringify({ type, payload });
+		requestId?: string,
+	): CommandResult<R> | null {
+		const command = JSON.stringify({ type, payload, request_id: requestId });
 		const resultJson = this.call("dispatch", [command]);
+		const label = requestId ? `${type} (${requestId})` : type;
 
 		if (!resultJson) {
-			logger.error(`命令 '${type}' 未收到任何返回结果。`, "Native Bridge");
-			return;
+			logger.error(`命令 '${label}' 未收到任何返回结果。`, "Native Bridge");
+			return null;
 		}
 
 		try {
-			const result: CommandResult = JSON.parse(resultJson);
+			const result: CommandResult<R> = JSON.parse(resultJson);
 			if (result.status === "Error") {
 				logger.error(
-					`后端执行命令 '${type}' 时发生错误:`,
+					`后端执行命令 '${label}' 时发生错误:`,
 					"Native Bridge",
 					result.message,
 				);
 			}
+			return result;
 		} catch (e) {
 			logger.error(
 				`解析后端返回结果失败:`,
@@ -81,9 +129,48 @@ class NativeBackend {
 				"\n原始结果:",
 				resultJson,
 			);
+			return null;
 		}
 	}
 
+	/**
+	 * 发送在后端工作线程上执行的命令，结果通过 `CommandCompleted` 事件返回
+	 */
+	private dispatchAsync<T extends keyof AppMessage, R = unknown>(
+		type: T,
+		payload: AppMessage[T],
+	): Promise<CommandResult<R> | null> {
+		const requestId = newRequestId();
+		return new Promise((resolve) => {
+			const timer = setTimeout(() => {
+				this.pendingCommands.delete(requestId);
+				logger.warn(
+					`等待命令 '${type} (${requestId})' 的结果超时`,
+					"Native Bridge",
+				);
+				resolve(null);
+			}, ASYNC_COMMAND_TIMEOUT_MS);
+
+			this.pendingCommands.set(requestId, (result) => {
+				clearTimeout(timer);
+				this.pendingCommands.delete(requestId);
+				if (result?.status === "Error") {
+					logger.error(
+						`后端执行命令 '${type} (${requestId})' 时发生错误:`,
+						"Native Bridge",
+						result.message,
+					);
+				}
+				resolve(result as CommandResult<R> | null);
+			});
+
+			const accepted = this.dispatch(type, payload, requestId);
+			if (accepted?.status !== "Success") {
+				this.pendingCommands.get(requestId)?.(null);
+			}
+		});
+	}
+
 	public initialize(control_handler: (msg: ControlMessage) => void) {
 		if (this.isActive) return;
 		this.call("terminate");
@@ -94,15 +181,163 @@ class NativeBackend {
 
 		window.addEventListener("beforeunload", () => {
 			if (this.isActive) {
-				this.disableDiscordRpc();
-				this.disableSmtcSession();
+				// terminate 会关闭所有输出端。这里不能调用 disableSink，
+				// 否则每次退出都会把禁用状态写入配置文件
 				this.call("terminate");
 			}
 		});
 
 		const eventCallback = (eventJson: string) => {
 			try {
-				const event: SmtcEvent = JSON.parse(eventJson);
+				const event: SequencedSmtcEvent = JSON.parse(eventJson);
+				this.dispatch("AckEvent", { seq: event.seq });
+
+				if (event.type === "UpdateReady") {
+					this.pendingUpdateVersion = event.version;
+					logger.info(
+						`新版本 ${event.version} 已下载，可以在下次启动时应用`,
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "SessionStateChanged") {
+					this.sessionState.smtc = event.enabled;
+					logger.info(
+						`后端报告 SMTC 会话已${event.enabled ? "启用" : "关闭"}`,
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "DiscordStatus") {
+					this.sessionState.discord = event.connected;
+					return;
+				}
+
+				if (event.type === "PlayModeApplied") {
+					// 后端应用的是较早的播放模式时重新发送最新的，让媒体浮窗与网易云保持一致
+					const latest = this.playMode;
+					if (
+						latest &&
+						(latest.isShuffling !== event.is_shuffling ||
+							latest.repeatMode !== event.repeat_mode)
+					) {
+						this.dispatch("UpdatePlayMode", latest);
+					}
+					return;
+				}
+
+				if (event.type === "CommandCompleted") {
+					if (event.request_id) {
+						this.pendingCommands.get(event.request_id)?.({
+							request_id: event.request_id,
+							status: event.status,
+							message: event.message ?? undefined,
+							data: event.data ?? undefined,
+						});
+					}
+					return;
+				}
+
+				if (event.type === "QuiesceStateChanged") {
+					logger.info(
+						event.quiesced ? "后端已暂停" : "后端已恢复",
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "ConfigChanged") {
+					this.backendConfig = event.config;
+					logger.info("后端配置文件已重新加载", "Native Bridge");
+					return;
+				}
+
+				if (event.type === "PlayStateMismatch") {
+					logger.warn(
+						`播放状态为 ${event.reported}，但音频输出表明实际为 ${event.observed}，后端已自动修正`,
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "AudioDeviceChanged") {
+					logger.info(
+						event.name
+							? `默认输出设备已切换到 ${event.name}`
+							: "没有可用的音频输出设备",
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "CoverStatus") {
+					this.coverStatus = { ncmId: event.ncm_id, state: event.state };
+					if (event.state === "Failed") {
+						logger.warn(
+							`歌曲 ${event.ncm_id ?? "未知"} 的封面未能显示在媒体浮窗中`,
+							"Native Bridge",
+						);
+					}
+					return;
+				}
+
+				if (event.type === "CoverPalette") {
+					this.coverPalette = { ncmId: event.ncm_id, colors: event.colors };
+					return;
+				}
+
+				if (event.type === "PresenceOwnerChanged") {
+					this.presenceOwner = event.owner;
+					if (event.owner && event.owner.name !== "InfLink-rs") {
+						logger.info(
+							`Discord 状态正由 ${event.owner.name ?? "其他插件"} 发布，暂时让出`,
+							"Native Bridge",
+						);
+					}
+					return;
+				}
+
+				if (event.type === "DurationCorrected") {
+					logger.info(
+						`歌曲 ${event.ncm_id ?? "未知"} 的实际时长为 ${Math.round(event.actual_ms / 1000)} 秒，与报告的 ${Math.round(event.reported_ms / 1000)} 秒不一致，已修正`,
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "SessionSummary") {
+					const minutes = Math.round(event.listened_ms / 60000);
+					logger.info(
+						`本次收听了 ${minutes} 分钟，共 ${event.tracks_played} 首歌`,
+						"Native Bridge",
+					);
+					this.lastSessionSummary = {
+						startedAt: event.started_at,
+						endedAt: event.ended_at,
+						tracksPlayed: event.tracks_played,
+						listenedMs: event.listened_ms,
+					};
+					return;
+				}
+
+				if (event.type === "CoverTooLarge") {
+					logger.warn(
+						`歌曲 ${event.ncm_id ?? "未知"} 的封面 (${Math.round(event.decoded_bytes / 1024)} KB) 超过后端上限 ${Math.round(event.limit_bytes / 1024)} KB，已改用封面 URL`,
+						"Native Bridge",
+					);
+					return;
+				}
+
+				if (event.type === "HighCpuUsage") {
+					logger.warn(
+						`插件线程的 CPU 占用持续偏高 (${event.percent.toFixed(1)}%)，占用最高的线程为 ${event.busiest_thread}`,
+						"Native Bridge",
+					);
+					return;
+				}
+
 				control_handler(event);
 			} catch (e) {
 				logger.error("解析后端事件失败:", "Native Bridge", e);
@@ -112,47 +347,203 @@ class NativeBackend {
 		this.call("registerEventCallback", [eventCallback]);
 	}
 
+	/**
+	 * 获取后端内存中保留的最近日志，按时间从旧到新排列
+	 */
+	public getRecentLogs(level?: LogLevel, limit?: number): RecentLogEntry[] {
+		if (!this.isActive) return [];
+		const result = this.dispatch<"GetRecentLogs", RecentLogEntry[]>(
+			"GetRecentLogs",
+			{ level, limit },
+		);
+		return result?.data ?? [];
+	}
+
+	public getLogLocation(): LogLocation | null {
+		if (!this.isActive) return null;
+		const result = this.dispatch<"GetLogPath", LogLocation>(
+			"GetLogPath",
+			undefined,
+		);
+		return result?.data ?? null;
+	}
+
+	public getDiagnostics(): Diagnostics | null {
+		if (!this.isActive) return null;
+		const result = this.dispatch<"GetDiagnostics", Diagnostics>(
+			"GetDiagnostics",
+			undefined,
+		);
+		return result?.data ?? null;
+	}
+
+	public getSchema(): BackendSchemas | null {
+		if (!this.isActive) return null;
+		const result = this.dispatch<"GetSchema", BackendSchemas>(
+			"GetSchema",
+			undefined,
+		);
+		return result?.data ?? null;
+	}
+
+	/**
+	 * 读取后端缓存的当前封面，其他插件可以直接复用而不需要再下载一次
+	 */
+	public async getCurrentCover(
+		payload: GetCurrentCoverPayload = {},
+	): Promise<CurrentCover | null> {
+		if (!this.isActive) return null;
+		const result = await this.dispatchAsync<"GetCurrentCover", CurrentCover>(
+			"GetCurrentCover",
+			payload,
+		);
+		return result?.data ?? null;
+	}
+
+	/**
+	 * 由后端渲染当前歌曲的分享文本并写入剪贴板，返回复制的文本
+	 */
+	public copyShareText(template?: string): string | null {
+		if (!this.isActive) return null;
+		const result = this.dispatch<"CopyShareText", string>("CopyShareText", {
+			template,
+		});
+		return result?.data ?? null;
+	}
+
+	/**
+	 * 用当前的 Discord 配置渲染示例元数据，返回会发送给 Discord 的 Activity
+	 *
+	 * 暂停且配置为隐藏时返回 `null`
+	 */
+	public async previewPresence(
+		payload: PreviewPresencePayload,
+	): Promise<DiscordActivityPreview | null> {
+		if (!this.isActive) return null;
+		const result = await this.dispatchAsync<
+			"PreviewPresence",
+			DiscordActivityPreview | null
+		>("PreviewPresence", payload);
+		return result?.data ?? null;
+	}
+
+	/**
+	 * 运行后端自检，Discord 未启用时会跳过对应的检查
+	 */
+	public async selfTest(): Promise<SelfTestCheck[] | null> {
+		if (!this.isActive) return null;
+		const result = await this.dispatchAsync<"SelfTest", SelfTestCheck[]>(
+			"SelfTest",
+			undefined,
+		);
+		return result?.data ?? null;
+	}
+
+	public openLogFolder() {
+		if (!this.isActive) return;
+		this.dispatch("OpenLogFolder", undefined);
+	}
+
+	public setLogRetention(retention: LogRetentionPayload): boolean {
+		if (!this.isActive) return false;
+		const result = this.dispatch("SetLogRetention", retention);
+		return result?.status === "Success";
+	}
+
+	/**
+	 * 最近一首歌曲的封面处理状态，封面失败时设置界面可以提示用户重试
+	 */
+	public getCoverStatus() {
+		return this.coverStatus;
+	}
+
+	/**
+	 * 后端从最近一首歌曲的封面中提取的主要颜色，可用于界面配色
+	 *
+	 * 需要在后端配置中设置 `smtc.palette_colors`
+	 */
+	public getCoverPalette() {
+		return this.coverPalette;
+	}
+
+	/**
+	 * 当前发布 Discord 状态的插件，需要在后端配置中开启 `discord.cooperative`
+	 */
+	public getPresenceOwner() {
+		return this.presenceOwner;
+	}
+
+	/**
+	 * 最近一次结束的收听会话，可用于显示 "本次收听了 2 小时" 之类的提示
+	 */
+	public getLastSessionSummary() {
+		return this.lastSessionSummary;
+	}
+
+	/**
+	 * 后端实际的会话状态，后端关闭时会通过事件重置，
+	 * 设置界面应以此显示开关状态，而不是只看保存的配置
+	 */
+	public getSessionState(): { smtc: boolean; discord: boolean } {
+		return { ...this.sessionState };
+	}
+
+	/**
+	 * 最近一次从配置文件重新加载的后端配置，本次运行中未重新加载过时为 null
+	 */
+	public getBackendConfig(): Record<string, unknown> | null {
+		return this.backendConfig;
+	}
+
+	/**
+	 * 已下载并校验通过的新版本，没有时为 null
+	 */
+	public getPendingUpdateVersion(): string | null {
+		return this.pendingUpdateVersion;
+	}
+
+	/**
+	 * 用已下载的新版本替换插件文件，在下次启动网易云时生效
+	 *
+	 * 要替换的插件文件由后端确定
+	 */
+	public applyUpdate(): boolean {
+		if (!this.isActive) return false;
+		const result = this.dispatch("ApplyUpdate", undefined);
+		return result?.status === "Success";
+	}
+
+	/**
+	 * 通过指定的输出端发送一首测试歌曲，返回失败原因，成功时返回 `null`
+	 */
+	public async testSink(sink: SinkId): Promise<string | null> {
+		if (!this.isActive) return "后端未启用";
+		const result = await this.dispatchAsync("TestSink", { sink });
+		if (result?.status === "Success") return null;
+		return result?.message ?? "未知错误";
+	}
+
 	public setBackendLogLevel(level: LogLevel) {
 		this.call("setLogLevel", [level]);
 		logger.info(`设置后端日志级别为: ${level}`, "Native Bridge");
 	}
 
+	/**
+	 * 使用 EnvFilter 格式的指令设置后端日志级别，可以只针对特定模块，
+	 * 例如 `backend::discord=trace,backend=info`
+	 */
+	public setBackendLogDirectives(directives: string) {
+		this.call("setLogLevel", [directives]);
+		logger.info(`设置后端日志指令为: ${directives}`, "Native Bridge");
+	}
+
 	private registerLogger() {
-		const logCallback = (logJson: string) => {
+		const logCallback = (logsJson: string) => {
 			try {
-				const entry: LogEntry = JSON.parse(logJson);
-				const level = entry.level.toLowerCase();
-
-				if (!isLogLevel(level)) {
-					logger.log(`[InfLink BE|${entry.target}] ${entry.message}`);
-					return;
+				const entries: LogEntry[] = JSON.parse(logsJson);
+				for (const entry of entries) {
+					this.printBackendLog(entry);
 				}
-
-				const pluginPart = "InfLink BE";
-				const sourcePart = entry.target;
-
-				const badgePluginCss = [
-					"color: white",
-					"background-color: #946143ff",
-					"padding: 1px 4px",
-					"border-radius: 3px 0 0 3px",
-					"font-weight: bold",
-				].join(";");
-
-				const badgeSourceCss = [
-					"color: white",
-					"background-color: #5a6268",
-					"padding: 1px 4px",
-					"border-radius: 0 3px 3px 0",
-				].join(";");
-
-				const logMethod = console[level] ?? console.log;
-				logMethod(
-					`%c${pluginPart}%c${sourcePart}`,
-					badgePluginCss,
-					badgeSourceCss,
-					entry.message,
-				);
 			} catch (e) {
 				logger.error("解析后端日志失败:", "Native Bridge", e);
 			}
@@ -160,6 +551,41 @@ class NativeBackend {
 		this.call("registerLogger", [logCallback]);
 	}
 
+	private printBackendLog(entry: LogEntry) {
+		const level = entry.level.toLowerCase();
+
+		if (!isLogLevel(level)) {
+			logger.log(`[InfLink BE|${entry.target}] ${entry.message}`);
+			return;
+		}
+
+		const pluginPart = "InfLink BE";
+		const sourcePart = entry.target;
+
+		const badgePluginCss = [
+			"color: white",
+			"background-color: #946143ff",
+			"padding: 1px 4px",
+			"border-radius: 3px 0 0 3px",
+			"font-weight: bold",
+		].join(";");
+
+		const badgeSourceCss = [
+			"color: white",
+			"background-color: #5a6268",
+			"padding: 1px 4px",
+			"border-radius: 0 3px 3px 0",
+		].join(";");
+
+		const logMethod = console[level] ?? console.log;
+		logMethod(
+			`%c${pluginPart}%c${sourcePart}`,
+			badgePluginCss,
+			badgeSourceCss,
+			entry.message,
+		);
+	}
+
 	public disable() {
 		if (!this.isActive) return;
 		this.isActive = false;
@@ -168,28 +594,58 @@ class NativeBackend {
 		logger.info("已终止后端", "Native Bridge");
 	}
 
-	public enableSmtcSession() {
-		if (!this.isActive) return;
-		this.dispatch("EnableSmtc", undefined);
-		logger.info("启用 SMTC 会话", "Native Bridge");
+	public enableSink(sink: SinkId) {
+		this.setSinkEnabled(sink, true);
+	}
+
+	public disableSink(sink: SinkId) {
+		this.setSinkEnabled(sink, false);
 	}
 
-	public disableSmtcSession() {
+	private setSinkEnabled(sink: SinkId, enabled: boolean) {
 		if (!this.isActive) return;
-		this.dispatch("DisableSmtc", undefined);
-		logger.info("禁用 SMTC 会话", "Native Bridge");
+		const requestId = newRequestId();
+		this.dispatch(
+			enabled ? "EnableSink" : "DisableSink",
+			{ sink },
+			requestId,
+		);
+		if (sink === "Smtc") {
+			this.sessionState.smtc = enabled;
+		} else {
+			this.sessionState.discord = enabled;
+		}
+		logger.info(
+			`${enabled ? "启用" : "禁用"}输出端 ${sink} (${requestId})`,
+			"Native Bridge",
+		);
 	}
 
-	public enableDiscordRpc() {
+	/**
+	 * 停止所有输出端和后台任务但不卸载插件，便于排查性能问题
+	 *
+	 * 暂停期间启用或禁用输出端只会被记录，调用 `resume` 后生效
+	 */
+	public quiesce() {
 		if (!this.isActive) return;
-		this.dispatch("EnableDiscord", undefined);
-		logger.info("启用 Discord RPC", "Native Bridge");
+		this.dispatch("Quiesce", undefined);
 	}
 
-	public disableDiscordRpc() {
+	public resume() {
 		if (!this.isActive) return;
-		this.dispatch("DisableDiscord", undefined);
-		logger.info("禁用 Discord RPC", "Native Bridge");
+		this.dispatch("Resume", undefined);
+	}
+
+	/**
+	 * 每个输出端当前是否启用，以及配置文件中记录的状态
+	 */
+	public listSinks(): SinkState[] | null {
+		if (!this.isActive) return null;
+		const result = this.dispatch<"ListSinks", SinkState[]>(
+			"ListSinks",
+			undefined,
+		);
+		return result?.data ?? null;
 	}
 
 	public updateDiscordConfig(config: DiscordConfigPayload) {
@@ -198,6 +654,14 @@ class NativeBackend {
 		logger.debug(`更新 Discord 配置`, "Native Bridge", config);
 	}
 
+	/**
+	 * 报告当前所在的一起听房间，不在房间中时传入 `null`
+	 */
+	public updateListenTogether(room: ListenTogetherRoom | null) {
+		if (!this.isActive) return;
+		this.dispatch("UpdateListenTogether", { room });
+	}
+
 	public async update(songInfo: SongInfo): Promise<void> {
 		this.updateGeneration++;
 		const generation = this.updateGeneration;
@@ -235,6 +699,9 @@ class NativeBackend {
 			cover: cover?.base64 || cover?.url ? cover : null,
 			ncmId: songInfo.ncmId,
 			duration: songInfo.duration,
+			albumId: songInfo.albumId,
+			artistIds: songInfo.artistIds,
+			context: songInfo.context,
 		};
 	}
 
@@ -280,8 +747,24 @@ class NativeBackend {
 		isShuffling: boolean;
 		repeatMode: RepeatMode;
 	}) {
+		this.playMode = playMode;
 		this.dispatch("UpdatePlayMode", playMode);
 	}
+
+	/**
+	 * 禁用当前模式下没有意义的按钮，例如私人 FM 没有上一首
+	 */
+	public setControls(controls: ControlsPayload) {
+		this.dispatch("SetControls", controls);
+	}
+
+	/**
+	 * 提前下载下一首歌曲的封面，切歌时媒体浮窗可以立即显示
+	 */
+	public prefetchCover(ncmId: number, coverUrl: string) {
+		if (!this.isActive) return;
+		this.dispatch("PrefetchCover", { ncmId, cover: { url: coverUrl } });
+	}
 }
 
 export const NativeBackendInstance = new NativeBackend();
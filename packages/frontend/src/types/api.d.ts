@@ -21,8 +21,14 @@ export interface SongInfo {
 	ncmId: number;
 	/** 单位毫秒 */
 	duration?: number | undefined;
+	albumId?: number | undefined;
+	artistIds?: number[] | undefined;
+	/** 私人 FM 等电台模式下为 `Radio` */
+	context?: PlaybackContext | undefined;
 }
 
+export type PlaybackContext = "Normal" | "Radio";
+
 export interface TimelineInfo {
 	/** 单位毫秒 */
 	currentTime: number;
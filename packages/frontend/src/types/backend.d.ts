@@ -1,4 +1,11 @@
-import type { PlaybackStatus, PlayMode, RepeatMode, VolumeInfo } from "./api";
+import type { LogLevel } from "../utils/logger";
+import type {
+	PlaybackContext,
+	PlaybackStatus,
+	PlayMode,
+	RepeatMode,
+	VolumeInfo,
+} from "./api";
 
 export type ControlMessage =
 	| { type: "Play" }
@@ -7,11 +14,15 @@ export type ControlMessage =
 	| { type: "NextSong" }
 	| { type: "PreviousSong" }
 	| { type: "Seek"; position_ms: number }
+	| { type: "SeekRelative"; delta_ms: number }
 	| { type: "ToggleShuffle" }
 	| { type: "ToggleRepeat" }
 	| { type: "SetRepeat"; mode: RepeatMode }
 	| { type: "SetVolume"; level: number }
-	| { type: "ToggleMute" };
+	| { type: "ToggleMute" }
+	| { type: "TogglePlayPause" }
+	| { type: "VolumeUp" }
+	| { type: "VolumeDown" };
 
 export type SmtcEvent =
 	| { type: "Play" }
@@ -21,7 +32,78 @@ export type SmtcEvent =
 	| { type: "PreviousSong" }
 	| { type: "ToggleShuffle" }
 	| { type: "ToggleRepeat" }
-	| { type: "Seek"; position_ms: number };
+	| { type: "Seek"; position_ms: number }
+	| { type: "SeekRelative"; delta_ms: number }
+	| { type: "TogglePlayPause" }
+	| { type: "VolumeUp" }
+	| { type: "VolumeDown" }
+	| { type: "ToggleMute" }
+	| {
+			type: "PlayStateMismatch";
+			reported: PlaybackStatus;
+			observed: PlaybackStatus;
+	  }
+	/** 系统的默认输出设备改变，为空表示没有可用的输出设备 */
+	| { type: "AudioDeviceChanged"; name: string | null }
+	| { type: "HighCpuUsage"; percent: number; busiest_thread: string }
+	| { type: "CoverStatus"; ncm_id: number | null; state: CoverState }
+	/** 封面解码后超过后端的大小上限，有封面 URL 时改由系统下载 */
+	| {
+			type: "CoverTooLarge";
+			ncm_id: number | null;
+			decoded_bytes: number;
+			limit_bytes: number;
+	  }
+	/** 封面的主要颜色，按占比从高到低排列，格式为 `#rrggbb` */
+	| { type: "CoverPalette"; ncm_id: number | null; colors: string[] }
+	/** 元数据中的时长与时间线总长不一致 (例如试听片段)，已改用时间线的总长 */
+	| {
+			type: "DurationCorrected";
+			ncm_id: number | null;
+			reported_ms: number;
+			actual_ms: number;
+	  }
+	/** 一段连续的收听已结束，时间均为 Unix 毫秒时间戳 */
+	| {
+			type: "SessionSummary";
+			started_at: number;
+			ended_at: number;
+			tracks_played: number;
+			listened_ms: number;
+	  }
+	| { type: "UpdateReady"; version: string }
+	/** 后端关闭 SMTC 会话时发送，之后不会再收到事件 */
+	| { type: "SessionStateChanged"; enabled: boolean }
+	| { type: "DiscordStatus"; connected: boolean }
+	/** 开启 `discord.cooperative` 后，发布 Discord 状态的插件改变 */
+	| { type: "PresenceOwnerChanged"; owner: PresenceOwner | null }
+	/** 播放模式已应用到 SMTC */
+	| {
+			type: "PlayModeApplied";
+			is_shuffling: boolean;
+			repeat_mode: RepeatMode;
+	  }
+	/** 异步命令执行完成，通过 `request_id` 对应到命令，字段与命令的返回值相同 */
+	| {
+			type: "CommandCompleted";
+			status: "Success" | "Error";
+			message: string | null;
+			data: unknown;
+	  }
+	/** 插件被 `Quiesce` 暂停或被 `Resume` 恢复 */
+	| { type: "QuiesceStateChanged"; quiesced: boolean }
+	/** 后端配置文件被修改并已重新加载，`config` 是新的完整配置 */
+	| { type: "ConfigChanged"; config: Record<string, unknown> };
+
+/** `Ready` 表示封面已交给 SMTC，使用 URL 的封面由系统在显示时加载 */
+export type CoverState = "Fetching" | "Ready" | "Failed";
+
+/** 后端分发的事件都带有序号，需要通过 `AckEvent` 确认 */
+export type SequencedSmtcEvent = SmtcEvent & {
+	seq: number;
+	/** 处理某个命令期间分发的事件带有该命令的关联 ID */
+	request_id?: string;
+};
 
 /**
  * FFI 边界使用的元数据类型，主要是 blob 转换为 base64 字符串以便跨 FFI 边界传递
@@ -33,12 +115,33 @@ export interface MetadataPayload {
 	cover: MetadataCoverPayload | null;
 	ncmId: number;
 	duration?: number | undefined;
+	playlist?: PlaylistInfo | undefined;
+	albumId?: number | undefined;
+	artistIds?: number[] | undefined;
+	context?: PlaybackContext | undefined;
+}
+
+export interface PlaylistInfo {
+	name: string;
+	/** 当前歌曲在播放列表中的位置，从 1 开始 */
+	position: number;
+	length: number;
 }
 
 export interface MetadataCoverPayload {
 	base64?: string | undefined;
 	url?: string | undefined;
 }
+
+export interface SeekRelativePayload {
+	/** 正数为快进 */
+	deltaMs: number;
+}
+
+export interface PrefetchCoverPayload {
+	ncmId: number;
+	cover: MetadataCoverPayload;
+}
 export interface PlayStatePayload {
 	status: PlaybackStatus;
 }
@@ -48,6 +151,16 @@ export interface TimelinePayload {
 }
 
 export interface PlayModePayload extends PlayMode {}
+
+/** 系统媒体浮窗中各个按钮是否可用，省略的字段视为可用 */
+export interface ControlsPayload {
+	play?: boolean;
+	pause?: boolean;
+	next?: boolean;
+	previous?: boolean;
+	stop?: boolean;
+	seek?: boolean;
+}
 export interface VolumePayload extends VolumeInfo {}
 
 export type AppMessage = {
@@ -55,17 +168,141 @@ export type AppMessage = {
 	UpdatePlayState: PlayStatePayload;
 	UpdateTimeline: TimelinePayload;
 	UpdatePlayMode: PlayModePayload;
+	SetControls: ControlsPayload;
+	PrefetchCover: PrefetchCoverPayload;
 
-	EnableSmtc: undefined;
-	DisableSmtc: undefined;
+	TogglePlayPause: undefined;
+	VolumeUp: undefined;
+	VolumeDown: undefined;
+	Mute: undefined;
+	SeekRelative: SeekRelativePayload;
+
+	EnableSink: { sink: SinkId };
+	DisableSink: { sink: SinkId };
+	ListSinks: undefined;
+	SelfTest: undefined;
+	Quiesce: undefined;
+	Resume: undefined;
 
-	EnableDiscord: undefined;
-	DisableDiscord: undefined;
 	DiscordConfig: DiscordConfigPayload;
+	UpdateListenTogether: { room: ListenTogetherRoom | null };
+
+	GetRecentLogs: GetRecentLogsPayload;
+	GetLogPath: undefined;
+	OpenLogFolder: undefined;
+	GetDiagnostics: undefined;
+	GetSchema: undefined;
+	SetLogRetention: LogRetentionPayload;
+	AckEvent: { seq: number };
+	ApplyUpdate: undefined;
+	TestSink: { sink: SinkId };
+	GetCurrentCover: GetCurrentCoverPayload;
+	/** 占位符: `{title}` `{artist}` `{album}` `{url}` `{id}` `{duration}` */
+	CopyShareText: { template?: string };
+	PreviewPresence: PreviewPresencePayload;
 };
 
+export interface PreviewPresencePayload {
+	metadata: MetadataPayload;
+	status: PlaybackStatus;
+	/** 播放进度 (毫秒) */
+	currentTime?: number;
+}
+
+/** `PreviewPresence` 返回的 Activity，与发送给 Discord 的 JSON 相同 */
+export interface DiscordActivityPreview {
+	name?: string;
+	details?: string;
+	state?: string;
+	timestamps?: { start?: number; end?: number };
+	assets?: {
+		large_image?: string;
+		large_text?: string;
+		small_image?: string;
+		small_text?: string;
+	};
+	buttons?: { label: string; url: string }[];
+}
+
+export interface GetRecentLogsPayload {
+	/** 最低日志级别 */
+	level?: LogLevel;
+	/** 最多返回的条数，从最新的日志开始计算 */
+	limit?: number;
+}
+
+export interface LogRetentionPayload {
+	rotation: "hourly" | "daily" | "size";
+	/** 最多保留的日志文件数 */
+	maxLogFiles: number;
+	/** 按大小轮转时单个日志文件的最大大小 (MB) */
+	maxFileSizeMb?: number;
+}
+
 export type DiscordDisplayMode = "Name" | "State" | "Details";
 
+/** 网易云一起听房间，用于在 Discord 中显示 "2 of 5" */
+export interface ListenTogetherRoom {
+	roomId: string;
+	/** 房间中的当前人数 */
+	size: number;
+	maxSize: number;
+}
+
+export interface GetCurrentCoverPayload {
+	/** 默认为 Base64，File 会写入临时文件并返回路径 */
+	format?: "Base64" | "File";
+	/** 封面最长边的像素数，超过时会等比缩小 */
+	maxSize?: number;
+}
+
+/** 后端缓存的当前封面 */
+export interface CurrentCover {
+	ncmId: number | null;
+	/** 只有 URL 封面时为 null */
+	mimeType: "image/png" | "image/jpeg" | null;
+	base64: string | null;
+	path: string | null;
+	/** 前端只提供了封面 URL 时，后端只能返回 URL */
+	url: string | null;
+}
+
+/** 后端的输出端，统一通过 `EnableSink`/`DisableSink` 管理 */
+export type SinkId = "Smtc" | "Discord";
+
+/** 当前发布 Discord 状态的插件 */
+export interface PresenceOwner {
+	/** 对方没有写入插件名时为 `null` */
+	name: string | null;
+	pid: number | null;
+}
+
+/** `SelfTest` 返回的单项检查结果 */
+export interface SelfTestCheck {
+	name: "timeline" | "coverDecode" | "discord" | "rendererTask";
+	status: "Passed" | "Failed" | "Skipped";
+	/** 失败原因或跳过的原因 */
+	detail: string | null;
+	durationMs: number;
+}
+
+/** `ListSinks` 返回的单个输出端状态 */
+export interface SinkState {
+	sink: SinkId;
+	/** 本次运行中是否已启用 */
+	enabled: boolean;
+	/** 配置文件中记录的状态，也就是用户最近一次的选择 */
+	persisted: boolean;
+}
+
+/** 由 `SessionSummary` 事件转换而来 */
+export interface SessionSummary {
+	startedAt: number;
+	endedAt: number;
+	tracksPlayed: number;
+	listenedMs: number;
+}
+
 export interface DiscordConfigPayload {
 	showWhenPaused: boolean;
 	displayMode: DiscordDisplayMode;
@@ -79,9 +316,13 @@ export type DiscordAppNameMode =
 	| { type: "Album" }
 	| { type: "Custom"; value: string };
 
-export type CommandResult = {
+export type CommandResult<T = unknown> = {
+	/** 命令中带有的关联 ID */
+	request_id?: string;
 	status: "Success" | "Error";
 	message?: string;
+	/** 查询类命令的返回数据 */
+	data?: T;
 };
 
 export type LogEntry = {
@@ -89,3 +330,130 @@ export type LogEntry = {
 	message: string;
 	target: string;
 };
+
+export interface LogLocation {
+	logDir: string;
+	/** 最新的日志文件，目录中还没有日志文件时为 null */
+	currentFile: string | null;
+}
+
+export type RecentLogEntry = LogEntry & {
+	/** Unix 时间戳 (毫秒) */
+	timestamp: number;
+};
+
+export interface SpanTiming {
+	target: string;
+	name: string;
+	count: number;
+	totalMs: number;
+	avgMs: number;
+	maxMs: number;
+}
+
+/**
+ * 用于排查问题的后端运行状态
+ */
+export interface EventCallbackStatus {
+	registered: boolean;
+	/** 有事件超时未被确认，回调可能已经失效 */
+	stale: boolean;
+	pendingEvents: number;
+	lastAckedSeq: number;
+	/** 被合并掉的重复按钮事件数 */
+	coalescedButtonEvents: number;
+	/** SMTC 事件处理器中被捕获的 panic 次数 */
+	handlerPanics: number;
+	/** 因为多次 panic 而重新绑定处理器的次数 */
+	handlerRebinds: number;
+}
+
+/** 后端配置和所有命令的 JSON Schema，用于在设置界面生成表单和校验输入 */
+export interface BackendSchemas {
+	config: Record<string, unknown>;
+	commands: Record<string, unknown>;
+}
+
+/** 加载插件的 BetterNCM 和网易云版本 */
+export interface LoaderInfo {
+	betterncmVersion: string;
+	ncmVersion: [number, number, number] | null;
+}
+
+/** 当前系统支持的可选功能 */
+export interface PlatformCapabilities {
+	osVersion: string;
+	smtcTimeline: boolean;
+	smtcShuffle: boolean;
+	smtcAutoRepeat: boolean;
+	globalMediaSessions: boolean;
+}
+
+export interface DiscordConnectionStatus {
+	state: "disconnected" | "connecting" | "connected" | "backoff" | "idle";
+	/** 连续连接失败的次数 */
+	failedAttempts: number;
+	/** 处于退避状态时距离下次重试的时间 */
+	retryInMs?: number;
+	/** 已连接时实际连接到的 Discord 客户端 */
+	client?: {
+		pipe: number;
+		/** 无法判断客户端类型时为 `null` */
+		flavor: "stable" | "ptb" | "canary" | null;
+	};
+}
+
+export interface Diagnostics {
+	version: string;
+	loader: LoaderInfo | null;
+	platform: PlatformCapabilities;
+	dataDir: string | null;
+	/** 数据目录是如何确定的 */
+	dataDirSource: "appData" | "environment" | "portable" | null;
+	/** 仅在后端配置中开启 `logging.span_timing` 时存在 */
+	spanTimings?: SpanTiming[];
+	eventCallback: EventCallbackStatus;
+	discord: DiscordConnectionStatus;
+	/** 是否被 `Quiesce` 命令暂停 */
+	quiesced: boolean;
+	/** 本次启动以来检测到的播放次数 */
+	newPlays: number;
+	/** 暂停很久后继续播放同一首歌，没有计为新播放的次数 */
+	resumedPlays: number;
+	/** 队列积压时丢弃的过时时间线更新数量 */
+	supersededTimelineUpdates: number;
+	/** 根据音频输出修正播放状态的次数 */
+	playStateCorrections: number;
+	/** 默认输出设备的名称 */
+	outputDevice: string | null;
+	/** 当前歌曲实际播放的时长 (毫秒)，不包括暂停和跳转的部分 */
+	trackPlayedMs: number;
+	/** 最近一次采样中插件各线程的 CPU 占用 */
+	threadCpu: ThreadCpuUsage[];
+	/** 受监督的输出端工作线程 */
+	sinkWorkers: SinkWorkerHealth[];
+	/** 最近一次数据目录清理的结果，启动后还没有清理过时为空 */
+	maintenance: MaintenanceReport | null;
+}
+
+export interface MaintenanceReport {
+	finishedAtMs: number;
+	removedFiles: number;
+	freedBytes: number;
+	/** 删除失败的文件数，会在下次清理时重试 */
+	failedFiles: number;
+}
+
+export interface SinkWorkerHealth {
+	sink: SinkId;
+	running: boolean;
+	/** 因为 panic 被重启的次数 */
+	restarts: number;
+	lastPanic: string | null;
+}
+
+export interface ThreadCpuUsage {
+	name: string;
+	/** 占单个逻辑核心的百分比 */
+	percent: number;
+}